@@ -48,7 +48,7 @@ async function intercept(event) {
     }
   }
 
-  return offlineResponse(req)
+  return offlineResponse(event.request)
 }
 
 /**
@@ -87,7 +87,7 @@ async function fromWasm(req) {
   const params = {
     method: req.method,
     url: stripUrlHost(req),
-    form: await getFormData(req),
+    body: await getBody(req),
     headers: Object.fromEntries(req.headers),
   }
 
@@ -108,12 +108,10 @@ function stripUrlHost(req) {
  * @param {Request} req
  * @returns {Promise<string?>}
  */
-async function getFormData(req) {
-  if (req.method === "GET") {
+async function getBody(req) {
+  if (req.method === "GET" || req.method === "HEAD") {
     return null
   }
 
-  const form = await req.formData()
-
-  return new URLSearchParams([...form]).toString()
+  return await req.clone().text()
 }
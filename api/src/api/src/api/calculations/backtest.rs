@@ -0,0 +1,342 @@
+use axum::extract::{Query, State};
+use chrono::{Datelike, NaiveDate};
+use lib::{
+    AppError, AppResult, Json, Response,
+    infra::{DbState, Series, SeriesPoint},
+};
+use rust_decimal::{Decimal, MathematicalOps};
+use rust_decimal_macros::dec;
+
+use super::{
+    fixed_income::{BUSINESS_DAYS_PER_YEAR, income_tax_rate},
+    tax_tables::round_cents,
+};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Params {
+    initial: Decimal,
+    /// Deposited at the start of every month after the first.
+    contribution: Option<Decimal>,
+    from: NaiveDate,
+    to: NaiveDate,
+    index: Index,
+    /// Percent of the index for CDI and SELIC investments, 100 by default.
+    percent: Option<Decimal>,
+    /// Annual rate added to the index, as in IPCA + 6%.
+    spread: Option<Decimal>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Index {
+    Cdi,
+    Selic,
+    Ipca,
+    /// Poupança, from the TR and SELIC series. Exempt from income tax.
+    Savings,
+}
+
+#[derive(serde::Serialize)]
+pub struct BacktestModel {
+    params: Params,
+    invested: Decimal,
+    gross: Decimal,
+    income_tax: Decimal,
+    net: Decimal,
+    /// Net profit over the amount invested, in percent.
+    profit_percent: Decimal,
+    /// Days or months of history the result was accumulated over.
+    periods: usize,
+}
+
+pub async fn handler(
+    State(db): State<DbState>,
+    Query(params): Query<Params>,
+) -> Response<BacktestModel> {
+    if params.to <= params.from {
+        return Err(AppError::Validation(
+            "`to` must be after `from`".to_string(),
+        ));
+    }
+
+    let factors = factors(&db, &params).await?;
+
+    Ok(Json(backtest(params, &factors)))
+}
+
+/// Growth factor for each period between `from` and `to`, dated when it starts.
+async fn factors(db: &DbState, params: &Params) -> AppResult<Vec<(NaiveDate, Decimal)>> {
+    let last = params.to.pred_opt().expect("valid date");
+    let range = |series| load(db, series, params.from, last);
+
+    let spread = params.spread.unwrap_or_default() / dec!(100) + Decimal::ONE;
+    let percent = params.percent.unwrap_or(dec!(100)) / dec!(100);
+
+    let factors = match params.index {
+        Index::Cdi | Index::Selic => {
+            let series = match params.index {
+                Index::Cdi => Series::Cdi,
+                _ => Series::Selic,
+            };
+            let daily_spread = spread.powd(Decimal::ONE / Decimal::from(BUSINESS_DAYS_PER_YEAR));
+
+            range(series)
+                .await?
+                .into_iter()
+                .map(|p| {
+                    let factor = Decimal::ONE + p.value / dec!(100) * percent;
+                    (p.date, factor * daily_spread)
+                })
+                .collect()
+        }
+        Index::Ipca => {
+            let monthly_spread = spread.powd(Decimal::ONE / dec!(12));
+
+            load(db, Series::Ipca, first_of_month(params.from), last)
+                .await?
+                .into_iter()
+                .map(|p| {
+                    (
+                        p.date,
+                        (Decimal::ONE + p.value / dec!(100)) * monthly_spread,
+                    )
+                })
+                .collect()
+        }
+        Index::Savings => {
+            let tr = load(db, Series::Tr, first_of_month(params.from), last).await?;
+            // The SELIC in effect when each month starts may come from before `from`.
+            let selic = load(db, Series::Selic, NaiveDate::MIN, last).await?;
+
+            tr.into_iter()
+                .map(|p| {
+                    let daily = selic
+                        .iter()
+                        .rev()
+                        .find(|s| s.date <= p.date)
+                        .map(|s| s.value)
+                        .ok_or_else(|| {
+                            AppError::Validation(format!("No SELIC data before {}", p.date))
+                        })?;
+
+                    let annual = ((Decimal::ONE + daily / dec!(100))
+                        .powu(BUSINESS_DAYS_PER_YEAR.into())
+                        - Decimal::ONE)
+                        * dec!(100);
+                    let monthly = if annual > dec!(8.5) {
+                        dec!(0.5)
+                    } else {
+                        annual * dec!(0.7) / dec!(12)
+                    };
+
+                    Ok((p.date, Decimal::ONE + (monthly + p.value) / dec!(100)))
+                })
+                .collect::<AppResult<_>>()?
+        }
+    };
+
+    Ok(factors)
+}
+
+async fn load(
+    db: &DbState,
+    series: Series,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> AppResult<Vec<SeriesPoint>> {
+    let points = db.series.range(series, from, to).await?;
+
+    if points.is_empty() {
+        return Err(AppError::Validation(format!(
+            "No {series:?} data imported between {from} and {to}"
+        )));
+    }
+
+    Ok(points)
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("first day of the month")
+}
+
+struct Deposit {
+    date: NaiveDate,
+    amount: Decimal,
+    units: Decimal,
+}
+
+/// Accumulates the deposits as fund units, so each one pays income tax for its own holding time.
+fn backtest(params: Params, factors: &[(NaiveDate, Decimal)]) -> BacktestModel {
+    let contribution = params.contribution.unwrap_or_default();
+    let mut quota = Decimal::ONE;
+    let mut deposits = vec![Deposit {
+        date: params.from,
+        amount: params.initial,
+        units: params.initial,
+    }];
+
+    let mut month = (params.from.year(), params.from.month());
+
+    for &(date, factor) in factors {
+        if (date.year(), date.month()) != month {
+            month = (date.year(), date.month());
+
+            if !contribution.is_zero() {
+                deposits.push(Deposit {
+                    date,
+                    amount: contribution,
+                    units: contribution / quota,
+                });
+            }
+        }
+
+        quota *= factor;
+    }
+
+    let exempt = matches!(params.index, Index::Savings);
+
+    let (invested, gross, income_tax) = deposits.iter().fold(
+        (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO),
+        |(invested, gross, tax), deposit| {
+            let value = deposit.units * quota;
+            let profit = (value - deposit.amount).max(Decimal::ZERO);
+            let days = (params.to - deposit.date).num_days();
+
+            let deposit_tax = if exempt {
+                Decimal::ZERO
+            } else {
+                profit * income_tax_rate(days)
+            };
+
+            (invested + deposit.amount, gross + value, tax + deposit_tax)
+        },
+    );
+
+    let (gross, income_tax) = (round_cents(gross), round_cents(income_tax));
+    let net = gross - income_tax;
+
+    let profit_percent = if invested.is_zero() {
+        Decimal::ZERO
+    } else {
+        round_cents((net - invested) / invested * dec!(100))
+    };
+
+    BacktestModel {
+        params,
+        invested,
+        gross,
+        income_tax,
+        net,
+        profit_percent,
+        periods: factors.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn params(index: Index, from: NaiveDate, to: NaiveDate) -> Params {
+        Params {
+            initial: dec!(1000),
+            contribution: None,
+            from,
+            to,
+            index,
+            percent: None,
+            spread: None,
+        }
+    }
+
+    async fn db(points: Vec<SeriesPoint>) -> DbState {
+        let db = DbState::in_memory();
+        db.series.import(&points).await.unwrap();
+        db
+    }
+
+    fn monthly(series: Series, year: i32, values: &[Decimal]) -> Vec<SeriesPoint> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| SeriesPoint {
+                series,
+                date: date(year, i as u32 + 1, 1),
+                value,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn cdi_percent() {
+        let points = (1..=10)
+            .map(|day| SeriesPoint {
+                series: Series::Cdi,
+                date: date(2024, 1, day),
+                value: dec!(0.05),
+            })
+            .collect();
+        let db = db(points).await;
+
+        let params = Params {
+            percent: Some(dec!(200)),
+            ..params(Index::Cdi, date(2024, 1, 1), date(2024, 1, 11))
+        };
+
+        let factors = factors(&db, &params).await.unwrap();
+        let result = backtest(params, &factors);
+
+        // 1.001 ^ 10
+        assert_eq!(result.periods, 10);
+        assert_eq!(result.gross, dec!(1010.05));
+        assert_eq!(result.income_tax, dec!(2.26));
+    }
+
+    #[tokio::test]
+    async fn ipca_with_contributions() {
+        let db = db(monthly(Series::Ipca, 2024, &[dec!(1); 3])).await;
+
+        let params = Params {
+            contribution: Some(dec!(100)),
+            ..params(Index::Ipca, date(2024, 1, 1), date(2024, 4, 1))
+        };
+
+        let factors = factors(&db, &params).await.unwrap();
+        let result = backtest(params, &factors);
+
+        // 1000 * 1.01^3 + 100 * 1.01^2 + 100 * 1.01
+        assert_eq!(result.invested, dec!(1200));
+        assert_eq!(result.gross, dec!(1233.31));
+    }
+
+    #[tokio::test]
+    async fn savings_follow_the_selic_rule() {
+        let mut points = monthly(Series::Tr, 2024, &[dec!(0.1), dec!(0.1)]);
+        points.push(SeriesPoint {
+            series: Series::Selic,
+            date: date(2023, 12, 29),
+            value: dec!(0.043739),
+        });
+        let db = db(points).await;
+
+        let params = params(Index::Savings, date(2024, 1, 1), date(2024, 3, 1));
+
+        let factors = factors(&db, &params).await.unwrap();
+        let result = backtest(params, &factors);
+
+        // SELIC at ~11.65% a year, so 0.5% + TR a month.
+        assert_eq!(result.gross, dec!(1012.04));
+        assert_eq!(result.income_tax, Decimal::ZERO);
+    }
+
+    #[tokio::test]
+    async fn missing_history_is_rejected() {
+        let db = db(vec![]).await;
+        let params = params(Index::Cdi, date(2020, 1, 1), date(2024, 1, 1));
+
+        assert!(factors(&db, &params).await.is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Brazilian business days, following the ANBIMA calendar of national holidays.
+
+use std::collections::HashSet;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+/// Easter Sunday, by the anonymous Gregorian algorithm.
+pub fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).expect("valid easter date")
+}
+
+/// National holidays that close the financial market in `year`.
+pub fn holidays(year: i32) -> Vec<NaiveDate> {
+    let date = |month, day| NaiveDate::from_ymd_opt(year, month, day).expect("valid holiday");
+    let easter = easter(year);
+
+    let mut holidays = vec![
+        date(1, 1),
+        easter - Days::new(48), // Carnival Monday
+        easter - Days::new(47), // Carnival Tuesday
+        easter - Days::new(2),  // Good Friday
+        date(4, 21),
+        date(5, 1),
+        easter + Days::new(60), // Corpus Christi
+        date(9, 7),
+        date(10, 12),
+        date(11, 2),
+        date(11, 15),
+        date(12, 25),
+    ];
+
+    // Dia Nacional de Zumbi e da Consciência Negra, national since Lei 14.759/2023.
+    if year >= 2024 {
+        holidays.push(date(11, 20));
+    }
+
+    holidays.sort();
+    holidays
+}
+
+pub fn is_business_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !holidays(date.year()).contains(&date)
+}
+
+/// Business days counted once over a period, to count them between any of its dates in
+/// constant time.
+pub struct BusinessDays {
+    start: NaiveDate,
+    /// Business days from `start` up to, not including, each date of the period.
+    before: Vec<u32>,
+}
+
+impl BusinessDays {
+    /// Covers the dates from `start` to `end`, both inclusive.
+    pub fn new(start: NaiveDate, end: NaiveDate) -> Self {
+        let holidays: HashSet<_> = (start.year()..=end.year()).flat_map(holidays).collect();
+
+        let mut before = vec![0];
+        let mut count = 0;
+        for day in start.iter_days().take_while(|d| *d < end) {
+            if !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) && !holidays.contains(&day) {
+                count += 1;
+            }
+            before.push(count);
+        }
+
+        Self { start, before }
+    }
+
+    /// Business days from `from` (inclusive) to `to` (exclusive), the ANBIMA "du" count. Both
+    /// must be within the period.
+    pub fn between(&self, from: NaiveDate, to: NaiveDate) -> u32 {
+        self.before[self.index(to)] - self.before[self.index(from)]
+    }
+
+    fn index(&self, date: NaiveDate) -> usize {
+        (date - self.start).num_days() as usize
+    }
+}
+
+/// Next business day on or after `date`.
+pub fn next_business_day(date: NaiveDate) -> NaiveDate {
+    date.iter_days()
+        .find(|d| is_business_day(*d))
+        .expect("a business day ahead")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn easter_dates() {
+        assert_eq!(easter(2024), date(2024, 3, 31));
+        assert_eq!(easter(2025), date(2025, 4, 20));
+        assert_eq!(easter(2026), date(2026, 4, 5));
+    }
+
+    #[test]
+    fn carnival_is_not_a_business_day() {
+        assert!(!is_business_day(date(2025, 3, 3)));
+        assert!(!is_business_day(date(2025, 3, 4)));
+        assert!(is_business_day(date(2025, 3, 5)));
+        assert!(!is_business_day(date(2024, 11, 20)));
+        assert!(is_business_day(date(2023, 11, 20)));
+    }
+
+    #[test]
+    fn business_days_in_a_year() {
+        // 262 weekdays in 2024, 9 of them holidays.
+        let days = BusinessDays::new(date(2024, 1, 1), date(2026, 1, 1));
+        let year = days.between(date(2024, 1, 1), date(2025, 1, 1));
+        assert_eq!(year, 253);
+        // 261 weekdays in 2025, 9 of them holidays.
+        let year = days.between(date(2025, 1, 1), date(2026, 1, 1));
+        assert_eq!(year, 252);
+        assert_eq!(next_business_day(date(2025, 4, 18)), date(2025, 4, 22));
+    }
+
+    #[test]
+    fn counts_any_range_of_the_period() {
+        let days = BusinessDays::new(date(2024, 1, 1), date(2026, 1, 1));
+
+        assert_eq!(days.between(date(2024, 1, 1), date(2026, 1, 1)), 505);
+        assert_eq!(days.between(date(2025, 1, 1), date(2026, 1, 1)), 252);
+        // Carnival, from Friday to Wednesday.
+        assert_eq!(days.between(date(2025, 2, 28), date(2025, 3, 5)), 1);
+        assert_eq!(days.between(date(2025, 3, 5), date(2025, 3, 5)), 0);
+    }
+}
@@ -0,0 +1,712 @@
+//! Fixed-income products and how they are taxed, compounded per business day of the
+//! [`calendar`](super::calendar).
+
+use axum::extract::State;
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
+use lib::{AppError, AppResult, Json, Response, infra::DbState};
+use rust_decimal::{Decimal, MathematicalOps};
+use rust_decimal_macros::dec;
+
+use super::{
+    MAX_RATE, Rates,
+    calendar::{self, BusinessDays},
+    check_rate,
+    tax_tables::round_cents,
+    too_large,
+};
+
+pub const BUSINESS_DAYS_PER_YEAR: u32 = 252;
+/// Contributions are deposited every 30 days.
+pub const CONTRIBUTION_DAYS: u32 = 30;
+/// 40 years, beyond the maturity of any product compared.
+pub const MAX_DAYS: u32 = 40 * 365;
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct Product {
+    pub name: String,
+    #[serde(flatten)]
+    pub index: Index,
+    #[serde(default)]
+    pub tax: TaxTreatment,
+    /// Annual custody fee in percent, like B3's 0.20% for Tesouro Direto.
+    pub custody_fee: Option<Decimal>,
+}
+
+/// What the product yields, all rates are annual percentages.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(tag = "index", rename_all = "snake_case")]
+pub enum Index {
+    /// CDBs, LCIs and LCAs paying a percentage of the CDI.
+    Cdi { percent: Decimal },
+    /// Tesouro Selic, the SELIC plus the treasury spread.
+    Selic,
+    /// Tesouro IPCA+, the expected IPCA plus a real rate.
+    IpcaPlus { spread: Decimal },
+    /// Tesouro Prefixado and prefixed CDBs.
+    Prefixed { rate: Decimal },
+    /// Poupança, always exempt and paid monthly.
+    Savings,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxTreatment {
+    /// IOF under 30 days, then the regressive income tax table.
+    #[default]
+    Regressive,
+    /// LCI, LCA and poupança.
+    Exempt,
+}
+
+pub struct Horizon {
+    /// Date of the first deposit.
+    pub start: NaiveDate,
+    pub initial: Decimal,
+    /// Deposited every 30 days.
+    pub contribution: Decimal,
+    /// Calendar days until the redemption.
+    pub days: u32,
+    /// Expected annual IPCA, for IPCA+ products.
+    pub ipca: Option<Decimal>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ProductResult {
+    pub name: String,
+    /// Gross annual rate the product was simulated with.
+    annual_rate: Decimal,
+    invested: Decimal,
+    gross: Decimal,
+    custody: Decimal,
+    iof: Decimal,
+    income_tax: Decimal,
+    pub net: Decimal,
+    profit_percent: Decimal,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Params {
+    initial: Decimal,
+    contribution: Option<Decimal>,
+    days: u32,
+    /// Starts the simulation on this date, with the rates effective on it, instead of today.
+    date: Option<NaiveDate>,
+    ipca: Option<Decimal>,
+    products: Vec<Product>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ComparisonModel {
+    rates: Rates,
+    results: Vec<ProductResult>,
+}
+
+pub async fn handler(
+    State(db): State<DbState>,
+    Json(params): Json<Params>,
+) -> Response<ComparisonModel> {
+    let rates = Rates::load(db.rates.as_ref(), params.date).await?;
+
+    let horizon = Horizon {
+        start: params.date.unwrap_or_else(|| Utc::now().date_naive()),
+        initial: params.initial,
+        contribution: params.contribution.unwrap_or_default(),
+        days: params.days,
+        ipca: params.ipca,
+    };
+
+    let results = compare(&params.products, &rates, &horizon)?;
+
+    Ok(Json(ComparisonModel { rates, results }))
+}
+
+pub fn compare(
+    products: &[Product],
+    rates: &Rates,
+    horizon: &Horizon,
+) -> AppResult<Vec<ProductResult>> {
+    products
+        .iter()
+        .map(|product| simulate(product, rates, horizon))
+        .collect()
+}
+
+impl Product {
+    /// Also checks the rates given for the product, and the rate they add up to.
+    fn annual_rate(&self, rates: &Rates, ipca: Option<Decimal>) -> AppResult<Decimal> {
+        let rate = match &self.index {
+            Index::Cdi { percent } => {
+                if *percent < Decimal::ZERO || *percent > MAX_RATE {
+                    return Err(AppError::Validation(format!(
+                        "{} must pay between 0% and {MAX_RATE}% of the CDI",
+                        self.name
+                    )));
+                }
+
+                // The percentage applies to the daily CDI, not the annual one.
+                let daily = daily_factor(rates.cdi)? - Decimal::ONE;
+                let daily = Decimal::ONE + daily * percent / dec!(100);
+                (daily
+                    .checked_powu(BUSINESS_DAYS_PER_YEAR.into())
+                    .ok_or_else(too_large)?
+                    - Decimal::ONE)
+                    * dec!(100)
+            }
+            Index::Selic => rates.treasury(),
+            Index::IpcaPlus { spread } => {
+                let ipca = ipca.ok_or_else(|| {
+                    AppError::Validation(format!("{} needs the expected IPCA", self.name))
+                })?;
+                check_rate(ipca, "expected IPCA")?;
+                check_rate(*spread, &format!("{} spread", self.name))?;
+
+                ((Decimal::ONE + ipca / dec!(100)) * (Decimal::ONE + spread / dec!(100))
+                    - Decimal::ONE)
+                    * dec!(100)
+            }
+            Index::Prefixed { rate } => *rate,
+            Index::Savings => {
+                let monthly = Decimal::ONE + rates.savings_monthly() / dec!(100);
+                (monthly.checked_powu(12).ok_or_else(too_large)? - Decimal::ONE) * dec!(100)
+            }
+        };
+        check_rate(rate, &self.name)?;
+
+        Ok(rate)
+    }
+
+    fn tax(&self) -> TaxTreatment {
+        match self.index {
+            Index::Savings => TaxTreatment::Exempt,
+            _ => self.tax,
+        }
+    }
+
+    /// Growth of one real in each period it pays, a month for poupança and a business day
+    /// otherwise.
+    fn period_factor(&self, annual_rate: Decimal) -> AppResult<Decimal> {
+        match self.index {
+            Index::Savings => (Decimal::ONE + annual_rate / dec!(100))
+                .checked_powd(Decimal::ONE / dec!(12))
+                .ok_or_else(too_large),
+            _ => daily_factor(annual_rate),
+        }
+    }
+
+    /// Periods paid to money applied on `from` by `to`.
+    fn periods(&self, from: NaiveDate, to: NaiveDate, calendar: &BusinessDays) -> u32 {
+        match self.index {
+            Index::Savings => anniversaries(from, to),
+            _ => calendar.between(from, to),
+        }
+    }
+
+    /// Left of each real after the custody fee, per business day.
+    fn custody_factor(&self) -> AppResult<Decimal> {
+        let Some(fee) = self.custody_fee else {
+            return Ok(Decimal::ONE);
+        };
+        if fee < Decimal::ZERO || fee > dec!(100) {
+            return Err(AppError::Validation(format!(
+                "The custody fee of {} must be between 0% and 100%",
+                self.name
+            )));
+        }
+
+        Ok(Decimal::ONE / daily_factor(fee)?)
+    }
+
+    /// IOF and income tax over the `profit` of a deposit held for `days`.
+    fn taxes(&self, profit: Decimal, days: u32) -> (Decimal, Decimal) {
+        match self.tax() {
+            TaxTreatment::Exempt => (Decimal::ZERO, Decimal::ZERO),
+            TaxTreatment::Regressive => {
+                let iof = profit * iof_rate(days);
+                (iof, (profit - iof) * income_tax_rate(days.into()))
+            }
+        }
+    }
+}
+
+/// `amount` after growing by `factor` over `periods`.
+fn grow(amount: Decimal, factor: Decimal, periods: u32) -> AppResult<Decimal> {
+    factor
+        .checked_powu(periods.into())
+        .and_then(|growth| amount.checked_mul(growth))
+        .ok_or_else(too_large)
+}
+
+/// Date `days` after `start`, if not over [`MAX_DAYS`].
+fn horizon_end(start: NaiveDate, days: u32) -> AppResult<NaiveDate> {
+    if days > MAX_DAYS {
+        return Err(AppError::Validation(format!(
+            "The simulation can take at most {MAX_DAYS} days"
+        )));
+    }
+
+    start
+        .checked_add_days(Days::new(days.into()))
+        .ok_or_else(|| AppError::Validation("The redemption date is out of range".to_string()))
+}
+
+/// Accrues the deposits of a horizon until its redemption.
+struct Accrual<'a> {
+    product: &'a Product,
+    annual_rate: Decimal,
+    period_factor: Decimal,
+    custody_factor: Decimal,
+    calendar: BusinessDays,
+    start: NaiveDate,
+    days: u32,
+    end: NaiveDate,
+}
+
+/// What a deposit turned into on the redemption.
+struct Deposit {
+    gross: Decimal,
+    custody: Decimal,
+    iof: Decimal,
+    income_tax: Decimal,
+}
+
+impl<'a> Accrual<'a> {
+    fn new(product: &'a Product, rates: &Rates, horizon: &Horizon) -> AppResult<Self> {
+        let annual_rate = product.annual_rate(rates, horizon.ipca)?;
+        let end = horizon_end(horizon.start, horizon.days)?;
+
+        Ok(Self {
+            product,
+            annual_rate,
+            period_factor: product.period_factor(annual_rate)?,
+            custody_factor: product.custody_factor()?,
+            calendar: BusinessDays::new(horizon.start, end),
+            start: horizon.start,
+            days: horizon.days,
+            end,
+        })
+    }
+
+    /// Dates of the initial deposit and of each contribution until the redemption.
+    fn dates(&self) -> impl Iterator<Item = NaiveDate> + '_ {
+        (0..=self.days / CONTRIBUTION_DAYS)
+            .map(|month| {
+                // Money can only be applied on business days.
+                let date = self.start + Days::new((month * CONTRIBUTION_DAYS).into());
+                calendar::next_business_day(date)
+            })
+            .take_while(|date| *date <= self.end)
+    }
+
+    fn deposit(&self, amount: Decimal, date: NaiveDate) -> AppResult<Deposit> {
+        let days = (self.end - date).num_days() as u32;
+        let business_days = self.calendar.between(date, self.end);
+
+        let periods = self.product.periods(date, self.end, &self.calendar);
+        let gross = grow(amount, self.period_factor, periods)?;
+        let after_custody = grow(gross, self.custody_factor, business_days)?;
+        let profit = (after_custody - amount).max(Decimal::ZERO);
+        let (iof, income_tax) = self.product.taxes(profit, days);
+
+        Ok(Deposit {
+            gross,
+            custody: gross - after_custody,
+            iof,
+            income_tax,
+        })
+    }
+}
+
+impl Deposit {
+    fn net(&self) -> Decimal {
+        self.gross - self.custody - self.iof - self.income_tax
+    }
+}
+
+pub fn simulate(product: &Product, rates: &Rates, horizon: &Horizon) -> AppResult<ProductResult> {
+    let accrual = Accrual::new(product, rates, horizon)?;
+
+    let mut result = ProductResult {
+        name: product.name.clone(),
+        annual_rate: accrual.annual_rate.round_dp(4),
+        invested: Decimal::ZERO,
+        gross: Decimal::ZERO,
+        custody: Decimal::ZERO,
+        iof: Decimal::ZERO,
+        income_tax: Decimal::ZERO,
+        net: Decimal::ZERO,
+        profit_percent: Decimal::ZERO,
+    };
+
+    for (month, date) in accrual.dates().enumerate() {
+        let amount = if month == 0 {
+            horizon.initial
+        } else {
+            horizon.contribution
+        };
+        if amount.is_zero() {
+            continue;
+        }
+
+        let deposit = accrual.deposit(amount, date)?;
+        // Custody and taxes take part of the gross, so only these two can overflow.
+        result.invested = result.invested.checked_add(amount).ok_or_else(too_large)?;
+        result.gross = result
+            .gross
+            .checked_add(deposit.gross)
+            .ok_or_else(too_large)?;
+        result.custody += deposit.custody;
+        result.iof += deposit.iof;
+        result.income_tax += deposit.income_tax;
+    }
+
+    result.gross = round_cents(result.gross);
+    result.custody = round_cents(result.custody);
+    result.iof = round_cents(result.iof);
+    result.income_tax = round_cents(result.income_tax);
+    result.net = result.gross - result.custody - result.iof - result.income_tax;
+
+    if !result.invested.is_zero() {
+        let profit = (result.net - result.invested)
+            .checked_div(result.invested)
+            .and_then(|profit| profit.checked_mul(dec!(100)))
+            .ok_or_else(too_large)?;
+        result.profit_percent = round_cents(profit);
+    }
+
+    Ok(result)
+}
+
+/// Net value on the redemption of the initial deposit, and of one real of every monthly
+/// contribution. The net value of any other contribution is proportional, as taxes are
+/// charged over the profit of each deposit.
+pub fn contribution_values(
+    product: &Product,
+    rates: &Rates,
+    horizon: &Horizon,
+) -> AppResult<(Decimal, Decimal)> {
+    let accrual = Accrual::new(product, rates, horizon)?;
+
+    let mut dates = accrual.dates();
+    let initial = match dates.next() {
+        Some(date) => accrual.deposit(horizon.initial, date)?.net(),
+        None => Decimal::ZERO,
+    };
+    let per_real = dates.try_fold(Decimal::ZERO, |total, date| {
+        let net = accrual.deposit(Decimal::ONE, date)?.net();
+        total.checked_add(net).ok_or_else(too_large)
+    })?;
+
+    Ok((initial, per_real))
+}
+
+/// Net value of one real invested on `start` and redeemed on each of `dates`, in order.
+///
+/// The yield accrues from one date to the next, instead of simulating every redemption from
+/// the start.
+pub fn redemptions(
+    product: &Product,
+    rates: &Rates,
+    ipca: Option<Decimal>,
+    start: NaiveDate,
+    dates: &[NaiveDate],
+) -> AppResult<Vec<Decimal>> {
+    let Some(&last) = dates.last() else {
+        return Ok(vec![]);
+    };
+    let days = (last - start).num_days().max(0);
+    horizon_end(start, days.try_into().unwrap_or(u32::MAX))?;
+
+    let annual_rate = product.annual_rate(rates, ipca)?;
+    let period_factor = product.period_factor(annual_rate)?;
+    let custody_factor = product.custody_factor()?;
+    // Money can only be applied on business days.
+    let applied = calendar::next_business_day(start);
+    let calendar = BusinessDays::new(start, last.max(applied));
+
+    let mut previous = applied;
+    let mut periods = 0;
+    let mut value = Decimal::ONE;
+
+    let mut values = Vec::with_capacity(dates.len());
+    for &date in dates {
+        let date = date.max(previous);
+        let paid = product.periods(applied, date, &calendar);
+        let business_days = calendar.between(previous, date);
+
+        value = grow(value, period_factor, paid - periods)?;
+        value = grow(value, custody_factor, business_days)?;
+        periods = paid;
+        previous = date;
+
+        let profit = (value - Decimal::ONE).max(Decimal::ZERO);
+        let (iof, income_tax) = product.taxes(profit, (date - applied).num_days() as u32);
+        values.push(value - iof - income_tax);
+    }
+
+    Ok(values)
+}
+
+/// Growth in one business day of an annual `rate` in percent.
+pub fn daily_factor(rate: Decimal) -> AppResult<Decimal> {
+    (Decimal::ONE + rate / dec!(100))
+        .checked_powd(Decimal::ONE / Decimal::from(BUSINESS_DAYS_PER_YEAR))
+        .ok_or_else(too_large)
+}
+
+/// Monthly anniversaries of a poupança deposit, the only days it pays.
+fn anniversaries(from: NaiveDate, to: NaiveDate) -> u32 {
+    let months = (to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32;
+    let months = months.max(0) as u32;
+
+    // Deposits on the 31st have their anniversary on the last day of shorter months.
+    if months > 0 && from + Months::new(months) > to {
+        months - 1
+    } else {
+        months
+    }
+}
+
+/// Regressive income tax table for fixed income, by days held.
+pub fn income_tax_rate(days: i64) -> Decimal {
+    match days {
+        ..=180 => dec!(0.225),
+        181..=360 => dec!(0.20),
+        361..=720 => dec!(0.175),
+        _ => dec!(0.15),
+    }
+}
+
+/// Share of the yield taken by IOF on redemptions before 30 days (Decreto 6.306/2007).
+pub fn iof_rate(days: u32) -> Decimal {
+    const TABLE: [u8; 30] = [
+        96, 93, 90, 86, 83, 80, 76, 73, 70, 66, 63, 60, 56, 53, 50, 46, 43, 40, 36, 33, 30, 26, 23,
+        20, 16, 13, 10, 6, 3, 0,
+    ];
+
+    match days {
+        0 => Decimal::ONE,
+        1..=30 => Decimal::from(TABLE[days as usize - 1]) / dec!(100),
+        _ => Decimal::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> Rates {
+        Rates {
+            selic: dec!(15),
+            cdi: dec!(14.9),
+            treasury_spread: dec!(0.1736),
+            fgts: dec!(3),
+        }
+    }
+
+    fn product(name: &str, index: Index, tax: TaxTreatment) -> Product {
+        Product {
+            name: name.to_string(),
+            index,
+            tax,
+            custody_fee: None,
+        }
+    }
+
+    fn horizon(days: u32) -> Horizon {
+        Horizon {
+            start: NaiveDate::from_ymd_opt(2025, 1, 2).unwrap(),
+            initial: dec!(1000),
+            contribution: Decimal::ZERO,
+            days,
+            ipca: Some(dec!(4.5)),
+        }
+    }
+
+    #[test]
+    fn one_year_of_cdi() {
+        let cdb = product(
+            "CDB",
+            Index::Cdi { percent: dec!(100) },
+            TaxTreatment::Regressive,
+        );
+
+        let result = simulate(&cdb, &rates(), &horizon(365)).unwrap();
+
+        // 252 business days of CDI compound to the annual CDI.
+        assert_eq!(result.annual_rate, dec!(14.9));
+        assert_eq!(result.gross, dec!(1149.00));
+        assert_eq!(result.income_tax, dec!(26.07));
+    }
+
+    #[test]
+    fn exempt_products_beat_equivalent_taxed_ones() {
+        let cdb = product(
+            "CDB",
+            Index::Cdi { percent: dec!(100) },
+            TaxTreatment::Regressive,
+        );
+        let lci = product(
+            "LCI",
+            Index::Cdi { percent: dec!(90) },
+            TaxTreatment::Exempt,
+        );
+
+        let results = compare(&[cdb, lci], &rates(), &horizon(365)).unwrap();
+
+        assert_eq!(results[1].income_tax, Decimal::ZERO);
+        assert!(results[1].net > results[0].net);
+    }
+
+    #[test]
+    fn iof_before_30_days() {
+        let cdb = product(
+            "CDB",
+            Index::Prefixed { rate: dec!(12) },
+            TaxTreatment::Regressive,
+        );
+
+        let result = simulate(&cdb, &rates(), &horizon(10)).unwrap();
+
+        assert_eq!(iof_rate(10), dec!(0.66));
+        assert!(result.iof > result.income_tax);
+        assert_eq!(iof_rate(30), Decimal::ZERO);
+    }
+
+    #[test]
+    fn tesouro_custody_and_ipca() {
+        let selic = Product {
+            custody_fee: Some(dec!(0.2)),
+            ..product("Tesouro Selic", Index::Selic, TaxTreatment::Regressive)
+        };
+        let ipca = product(
+            "Tesouro IPCA+",
+            Index::IpcaPlus { spread: dec!(7) },
+            TaxTreatment::Regressive,
+        );
+
+        let results = compare(&[selic, ipca.clone()], &rates(), &horizon(365)).unwrap();
+
+        assert_eq!(results[0].custody, dec!(2.30));
+        // (1.045 * 1.07 - 1)
+        assert_eq!(results[1].annual_rate, dec!(11.815));
+
+        let without_ipca = Horizon {
+            ipca: None,
+            ..horizon(365)
+        };
+        assert!(simulate(&ipca, &rates(), &without_ipca).is_err());
+    }
+
+    #[test]
+    fn rejects_long_horizons() {
+        let cdb = product(
+            "CDB",
+            Index::Cdi { percent: dec!(100) },
+            TaxTreatment::Regressive,
+        );
+
+        assert!(simulate(&cdb, &rates(), &horizon(MAX_DAYS)).is_ok());
+        assert!(simulate(&cdb, &rates(), &horizon(u32::MAX)).is_err());
+
+        let at_the_end_of_time = Horizon {
+            start: NaiveDate::MAX,
+            ..horizon(1)
+        };
+        assert!(simulate(&cdb, &rates(), &at_the_end_of_time).is_err());
+    }
+
+    #[test]
+    fn rejects_rates_out_of_range() {
+        let out_of_range = [
+            product(
+                "CDB",
+                Index::Prefixed { rate: dec!(-100) },
+                TaxTreatment::Regressive,
+            ),
+            product(
+                "CDB",
+                Index::Prefixed { rate: dec!(1e6) },
+                TaxTreatment::Regressive,
+            ),
+            product(
+                "CDB",
+                Index::Cdi { percent: dec!(-10) },
+                TaxTreatment::Regressive,
+            ),
+            product(
+                "IPCA+",
+                Index::IpcaPlus { spread: dec!(-150) },
+                TaxTreatment::Regressive,
+            ),
+            Product {
+                custody_fee: Some(dec!(-1)),
+                ..product("Tesouro Selic", Index::Selic, TaxTreatment::Regressive)
+            },
+        ];
+        for product in &out_of_range {
+            assert!(simulate(product, &rates(), &horizon(365)).is_err());
+        }
+
+        let deflation = Horizon {
+            ipca: Some(dec!(-100)),
+            ..horizon(365)
+        };
+        let ipca = product(
+            "IPCA+",
+            Index::IpcaPlus { spread: dec!(5) },
+            TaxTreatment::Regressive,
+        );
+        assert!(simulate(&ipca, &rates(), &deflation).is_err());
+
+        // Compounding for decades at an absurd rate outgrows a decimal.
+        let absurd = product(
+            "CDB",
+            Index::Prefixed { rate: dec!(500) },
+            TaxTreatment::Regressive,
+        );
+        let monthly = Horizon {
+            contribution: dec!(1000),
+            ..horizon(MAX_DAYS)
+        };
+        assert!(simulate(&absurd, &rates(), &monthly).is_err());
+        assert!(contribution_values(&absurd, &rates(), &monthly).is_err());
+        let start = horizon(0).start;
+        let redemption = start + Days::new(MAX_DAYS.into());
+        assert!(redemptions(&absurd, &rates(), None, start, &[redemption]).is_err());
+    }
+
+    #[test]
+    fn redemptions_accrue_like_separate_simulations() {
+        let start = horizon(0).start;
+        let dates: Vec<_> = [10, 58, 365, 800]
+            .into_iter()
+            .map(|days| start + Days::new(days))
+            .collect();
+
+        for index in [Index::Cdi { percent: dec!(100) }, Index::Savings] {
+            let cdb = Product {
+                custody_fee: Some(dec!(0.2)),
+                ..product("CDB", index, TaxTreatment::Regressive)
+            };
+            let values = redemptions(&cdb, &rates(), None, start, &dates).unwrap();
+
+            for (date, value) in dates.iter().zip(values) {
+                let days = (*date - start).num_days() as u32;
+                let net = simulate(&cdb, &rates(), &horizon(days)).unwrap().net;
+                // The simulation rounds each tax on its own.
+                assert!((value * dec!(1000) - net).abs() <= dec!(0.01));
+            }
+        }
+    }
+
+    #[test]
+    fn savings_pay_monthly() {
+        let savings = product("Poupança", Index::Savings, TaxTreatment::Regressive);
+
+        let result = simulate(&savings, &rates(), &horizon(58)).unwrap();
+
+        // Only one anniversary, on February 2nd.
+        assert_eq!(result.gross, dec!(1005.00));
+        assert_eq!(result.income_tax, Decimal::ZERO);
+    }
+}
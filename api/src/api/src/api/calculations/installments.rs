@@ -1,39 +1,248 @@
-use axum::{Json, extract::Query};
-use rust_decimal::Decimal;
+use axum::extract::State;
+use chrono::{Months, NaiveDate, Utc};
+use lib::{AppError, AppResult, Json, Response, infra::DbState};
+use rust_decimal::{Decimal, MathematicalOps};
 use rust_decimal_macros::dec;
 
+use super::{
+    Rates, check_rate,
+    fixed_income::{self, Index, Product, TaxTreatment},
+    internal_rate, price_installment,
+    tax_tables::round_cents,
+    too_large,
+};
+
+/// Ten years, longer than any store or card plan.
+const MAX_INSTALLMENTS: u16 = 120;
+
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Params {
+    /// Price when paying in installments, before any interest.
     financed: Decimal,
     installments: u16,
+    /// Price when paying everything today.
     upfront: Decimal,
-    cdi: Decimal,
-    tax: Decimal,
+    /// Monthly interest of the installment plan, in percent.
+    interest: Option<Decimal>,
+    /// Expected annual inflation, in percent.
+    ipca: Option<Decimal>,
+    /// Where the money is kept until each installment is due, a CDB at 100% of the CDI by default.
+    product: Option<Product>,
+    /// Purchase date, also picks the rates. Defaults to today.
+    date: Option<NaiveDate>,
 }
 
 #[derive(serde::Serialize)]
 pub struct InstallmentsModel {
     params: Params,
+    installment: Decimal,
+    total: Decimal,
+    /// Monthly rate, in percent, that turns the installments into the upfront price.
+    monthly_cet: Decimal,
+    annual_cet: Decimal,
+    /// What the installments cost today, discounted by the net yield of the product.
+    present_value: Decimal,
+    /// Sum of the installments in today's money.
+    real_total: Decimal,
+    /// Positive when paying in installments and investing the difference is better.
     savings: Decimal,
+    months: Vec<Month>,
 }
 
-pub async fn handler(Query(params): Query<Params>) -> Json<InstallmentsModel> {
-    let discount = params.financed - params.upfront;
-    let installment_value = params.financed / Decimal::from(params.installments);
+#[derive(serde::Serialize)]
+pub struct Month {
+    month: u16,
+    due_date: NaiveDate,
+    installment: Decimal,
+    present_value: Decimal,
+    real_value: Decimal,
+    /// What is left invested after paying this installment, having invested the upfront price.
+    balance: Decimal,
+}
+
+pub async fn handler(
+    State(db): State<DbState>,
+    Json(params): Json<Params>,
+) -> Response<InstallmentsModel> {
+    let rates = Rates::load(db.rates.as_ref(), params.date).await?;
+
+    Ok(Json(decide(params, &rates)?))
+}
 
-    let monthly_rate = (params.cdi * (dec!(1) - params.tax)) / dec!(12) / dec!(100);
+fn decide(params: Params, rates: &Rates) -> AppResult<InstallmentsModel> {
+    if params.installments == 0 {
+        return Err(AppError::Validation(
+            "At least one installment is required".to_string(),
+        ));
+    }
+    if params.installments > MAX_INSTALLMENTS {
+        return Err(AppError::Validation(format!(
+            "At most {MAX_INSTALLMENTS} installments are allowed"
+        )));
+    }
+    if params.financed <= Decimal::ZERO || params.upfront <= Decimal::ZERO {
+        return Err(AppError::Validation(
+            "Both prices must be positive".to_string(),
+        ));
+    }
+    if params
+        .interest
+        .is_some_and(|interest| interest <= dec!(-100))
+    {
+        return Err(AppError::Validation(
+            "The interest must be above -100%".to_string(),
+        ));
+    }
+    if let Some(ipca) = params.ipca {
+        check_rate(ipca, "IPCA")?;
+    }
 
-    let earnings = (0..params.installments)
-        .fold((Decimal::ZERO, params.financed), |(e, r), _| {
-            let earnings = e + r * monthly_rate;
-            let remaining = r - installment_value;
+    let start = params.date.unwrap_or_else(|| Utc::now().date_naive());
+    let count = params.installments;
+    let installment = price_installment(
+        params.financed,
+        params.interest.unwrap_or_default() / dec!(100),
+        count,
+    )
+    .map(round_cents)
+    .ok_or_else(|| AppError::Validation("The interest rate is too high".to_string()))?;
 
-            (earnings, remaining)
-        })
-        .0;
+    let product = params.product.clone().unwrap_or_else(|| Product {
+        name: "CDB".to_string(),
+        index: Index::Cdi { percent: dec!(100) },
+        tax: TaxTreatment::Regressive,
+        custody_fee: None,
+    });
+    let monthly_inflation =
+        (Decimal::ONE + params.ipca.unwrap_or_default() / dec!(100)).powd(Decimal::ONE / dec!(12));
 
-    Json(InstallmentsModel {
-        savings: earnings - discount,
+    let due_dates = (1..=count)
+        .map(|month| start.checked_add_months(Months::new(month.into())))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| AppError::Validation("The due dates are out of range".to_string()))?;
+    // Net growth of money invested today and redeemed on each due date.
+    let growths = fixed_income::redemptions(&product, rates, params.ipca, start, &due_dates)?;
+
+    let mut months = Vec::with_capacity(count.into());
+    let mut balance = params.upfront;
+    let mut previous_growth = Decimal::ONE;
+
+    for ((month, due_date), growth) in (1..=count).zip(due_dates).zip(growths) {
+        balance = balance
+            .checked_mul(growth / previous_growth)
+            .and_then(|balance| balance.checked_sub(installment))
+            .ok_or_else(too_large)?;
+        previous_growth = growth;
+
+        months.push(Month {
+            month,
+            due_date,
+            installment,
+            present_value: round_cents(installment / growth),
+            real_value: round_cents(installment / monthly_inflation.powu(month.into())),
+            balance: round_cents(balance),
+        });
+    }
+
+    let present_value = months.iter().map(|m| m.present_value).sum();
+    let monthly_cet = internal_rate(params.upfront, &vec![installment; count.into()]);
+
+    Ok(InstallmentsModel {
+        installment,
+        total: installment * Decimal::from(count),
+        monthly_cet: (monthly_cet * dec!(100)).round_dp(4),
+        annual_cet: (((Decimal::ONE + monthly_cet).powu(12) - Decimal::ONE) * dec!(100))
+            .round_dp(4),
+        present_value,
+        real_total: months.iter().map(|m| m.real_value).sum(),
+        savings: params.upfront - present_value,
+        months,
         params,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> Rates {
+        Rates {
+            selic: dec!(15),
+            cdi: dec!(14.9),
+            treasury_spread: dec!(0.1736),
+            fgts: dec!(3),
+        }
+    }
+
+    fn params(financed: Decimal, upfront: Decimal, interest: Option<Decimal>) -> Params {
+        Params {
+            financed,
+            installments: 10,
+            upfront,
+            interest,
+            ipca: Some(dec!(4.5)),
+            product: None,
+            date: NaiveDate::from_ymd_opt(2025, 1, 2),
+        }
+    }
+
+    #[test]
+    fn interest_free_without_discount() {
+        let result = decide(params(dec!(1000), dec!(1000), None), &rates()).unwrap();
+
+        assert_eq!(result.installment, dec!(100));
+        assert_eq!(result.monthly_cet, Decimal::ZERO);
+        assert_eq!(result.months.len(), 10);
+        // Investing the money until each installment is due is always better.
+        assert!(result.savings > Decimal::ZERO);
+        assert!(result.real_total < dec!(1000));
+        assert!(result.months[9].balance > Decimal::ZERO);
+    }
+
+    #[test]
+    fn upfront_discount_is_the_hidden_interest() {
+        let result = decide(params(dec!(1000), dec!(900), None), &rates()).unwrap();
+
+        // 10 installments of 100 for 900 today is about 1.96% a month.
+        assert_eq!(result.monthly_cet.round_dp(2), dec!(1.96));
+        assert!(result.savings < Decimal::ZERO);
+    }
+
+    #[test]
+    fn interest_bearing_plan() {
+        let result = decide(params(dec!(1000), dec!(1000), Some(dec!(2))), &rates()).unwrap();
+
+        assert_eq!(result.installment, dec!(111.33));
+        assert_eq!(result.monthly_cet.round_dp(2), dec!(2.00));
+        assert_eq!(result.annual_cet.round_dp(2), dec!(26.83));
+    }
+
+    #[test]
+    fn rejects_plans_out_of_range() {
+        for (financed, upfront, interest) in [
+            (dec!(0), dec!(1000), None),
+            (dec!(1000), dec!(0), None),
+            (dec!(1000), dec!(1000), Some(dec!(-100))),
+        ] {
+            assert!(decide(params(financed, upfront, interest), &rates()).is_err());
+        }
+
+        let deflation = Params {
+            ipca: Some(dec!(-100)),
+            ..params(dec!(1000), dec!(1000), None)
+        };
+        assert!(decide(deflation, &rates()).is_err());
+
+        let forever = Params {
+            installments: MAX_INSTALLMENTS + 1,
+            ..params(dec!(1000), dec!(1000), None)
+        };
+        assert!(decide(forever, &rates()).is_err());
+
+        let longest = Params {
+            installments: MAX_INSTALLMENTS,
+            ..params(dec!(1000), dec!(1000), None)
+        };
+        assert_eq!(decide(longest, &rates()).unwrap().months.len(), 120);
+    }
+}
@@ -0,0 +1,525 @@
+use axum::extract::State;
+use chrono::{Months, NaiveDate, Utc};
+use lib::{
+    AppError, AppResult, Json, Response,
+    infra::{DbState, Series},
+};
+use rust_decimal::{Decimal, MathematicalOps, prelude::ToPrimitive};
+use rust_decimal_macros::dec;
+
+use super::{check_rate, internal_rate, price_installment, tax_tables::round_cents, too_large};
+
+/// 40 years, longer than any real-estate financing offered.
+const MAX_MONTHS: u16 = 480;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Params {
+    principal: Decimal,
+    months: u16,
+    /// Annual effective interest rate, in percent.
+    rate: Decimal,
+    system: System,
+    /// Monetary correction of the balance, as in TR + 10% for real-estate financing.
+    correction: Option<Correction>,
+    /// Life insurance (MIP), monthly percent over the balance.
+    mip: Option<Decimal>,
+    /// Property insurance (DFI), monthly percent over the property value.
+    dfi: Option<Decimal>,
+    property_value: Option<Decimal>,
+    /// Charged upfront, such as appraisal and registration fees. Only affects the CET.
+    fees: Option<Decimal>,
+    #[serde(default)]
+    extra: Vec<Extra>,
+    /// Picks the series history when the correction rate is not given. Defaults to today.
+    date: Option<NaiveDate>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum System {
+    /// Tabela Price, fixed installments.
+    Price,
+    /// Sistema de Amortização Constante, decreasing installments.
+    Sac,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Correction {
+    index: CorrectionIndex,
+    /// Expected annual rate, in percent. Defaults to the last 12 months imported.
+    rate: Option<Decimal>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CorrectionIndex {
+    Tr,
+    Ipca,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Extra {
+    /// Paid together with this installment.
+    month: u16,
+    amount: Decimal,
+    mode: ExtraMode,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtraMode {
+    ReduceTerm,
+    ReduceInstallment,
+}
+
+#[derive(serde::Serialize)]
+pub struct LoanModel {
+    params: Params,
+    /// Monthly interest rate, in percent.
+    monthly_rate: Decimal,
+    /// Monthly correction of the balance, in percent.
+    monthly_correction: Decimal,
+    /// Months it actually took, less than asked when extra payments reduced the term.
+    term: u16,
+    total_paid: Decimal,
+    total_interest: Decimal,
+    total_correction: Decimal,
+    total_insurance: Decimal,
+    monthly_cet: Decimal,
+    annual_cet: Decimal,
+    schedule: Vec<Row>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Row {
+    month: u16,
+    correction: Decimal,
+    interest: Decimal,
+    amortization: Decimal,
+    installment: Decimal,
+    mip: Decimal,
+    dfi: Decimal,
+    extra: Decimal,
+    /// Installment plus insurance and extra payments.
+    payment: Decimal,
+    balance: Decimal,
+}
+
+pub async fn handler(State(db): State<DbState>, Json(params): Json<Params>) -> Response<LoanModel> {
+    let correction = match &params.correction {
+        Some(Correction {
+            rate: Some(rate), ..
+        }) => {
+            check_rate(*rate, "correction")?;
+            monthly(*rate)
+        }
+        Some(Correction { index, rate: None }) => {
+            let date = params.date.unwrap_or_else(|| Utc::now().date_naive());
+            last_year(&db, *index, date).await?
+        }
+        None => Decimal::ZERO,
+    };
+
+    Ok(Json(simulate(params, correction)?))
+}
+
+/// Monthly rate equivalent to an annual rate, both in percent.
+fn monthly(annual: Decimal) -> Decimal {
+    ((Decimal::ONE + annual / dec!(100)).powd(Decimal::ONE / dec!(12)) - Decimal::ONE) * dec!(100)
+}
+
+/// Average monthly rate of the series over the 12 months before `date`, in percent.
+async fn last_year(db: &DbState, index: CorrectionIndex, date: NaiveDate) -> AppResult<Decimal> {
+    let series = match index {
+        CorrectionIndex::Tr => Series::Tr,
+        CorrectionIndex::Ipca => Series::Ipca,
+    };
+    let from = date - Months::new(12);
+    let points = db.series.range(series, from, date).await?;
+
+    if points.is_empty() {
+        return Err(AppError::Validation(format!(
+            "No {series:?} data imported between {from} and {date}"
+        )));
+    }
+
+    let factor = points
+        .iter()
+        .map(|p| Decimal::ONE + p.value / dec!(100))
+        .product::<Decimal>();
+    let count = Decimal::from(points.len());
+
+    Ok((factor.powd(Decimal::ONE / count) - Decimal::ONE) * dec!(100))
+}
+
+fn simulate(params: Params, monthly_correction: Decimal) -> AppResult<LoanModel> {
+    if params.months == 0 {
+        return Err(AppError::Validation(
+            "The loan needs at least one month".to_string(),
+        ));
+    }
+    if params.months > MAX_MONTHS {
+        return Err(AppError::Validation(format!(
+            "The loan can take at most {MAX_MONTHS} months"
+        )));
+    }
+    check_rate(params.rate, "interest")?;
+    if params.principal <= Decimal::ZERO {
+        return Err(AppError::Validation(
+            "The principal must be positive".to_string(),
+        ));
+    }
+    if params.dfi.is_some() && params.property_value.is_none() {
+        return Err(AppError::Validation(
+            "The DFI insurance needs the property value".to_string(),
+        ));
+    }
+    let optional = [
+        ("MIP insurance", params.mip),
+        ("DFI insurance", params.dfi),
+        ("property value", params.property_value),
+        ("fees", params.fees),
+    ];
+    if let Some((name, _)) = optional
+        .iter()
+        .find(|(_, value)| value.is_some_and(|value| value < Decimal::ZERO))
+    {
+        return Err(AppError::Validation(format!(
+            "The {name} can't be negative"
+        )));
+    }
+    if params.fees.unwrap_or_default() >= params.principal {
+        return Err(AppError::Validation(
+            "The fees must be less than the principal".to_string(),
+        ));
+    }
+    if params.extra.iter().any(|e| e.amount <= Decimal::ZERO) {
+        return Err(AppError::Validation(
+            "Extra payments must be positive".to_string(),
+        ));
+    }
+
+    let rate = monthly(params.rate) / dec!(100);
+    let correction = monthly_correction / dec!(100);
+    let mip = params.mip.unwrap_or_default() / dec!(100);
+    let dfi = params
+        .property_value
+        .unwrap_or_default()
+        .checked_mul(params.dfi.unwrap_or_default() / dec!(100))
+        .map(round_cents)
+        .ok_or_else(too_large)?;
+
+    let mut schedule = Vec::with_capacity(params.months.into());
+    let mut balance = params.principal;
+    let mut remaining = params.months;
+    let mut month = 0;
+
+    while balance > Decimal::ZERO && remaining > 0 {
+        month += 1;
+
+        // Corrected faster than it's paid, the balance can outgrow a decimal.
+        let correction = round_cents(balance.checked_mul(correction).ok_or_else(too_large)?);
+        balance = balance.checked_add(correction).ok_or_else(too_large)?;
+
+        let interest = round_cents(balance.checked_mul(rate).ok_or_else(too_large)?);
+        let amortization = if remaining == 1 {
+            balance
+        } else {
+            match params.system {
+                System::Price => {
+                    let installment =
+                        price_installment(balance, rate, remaining).ok_or_else(|| {
+                            AppError::Validation("The interest rate is too high".to_string())
+                        })?;
+                    round_cents(installment) - interest
+                }
+                System::Sac => round_cents(balance / Decimal::from(remaining)),
+            }
+            .min(balance)
+        };
+        let installment = amortization.checked_add(interest).ok_or_else(too_large)?;
+        let mip = round_cents(balance.checked_mul(mip).ok_or_else(too_large)?);
+
+        balance -= amortization;
+        remaining -= 1;
+
+        let mut extra = Decimal::ZERO;
+
+        for payment in params.extra.iter().filter(|e| e.month == month) {
+            let amount = payment.amount.min(balance);
+            balance -= amount;
+            extra += amount;
+
+            if payment.mode == ExtraMode::ReduceTerm && balance > Decimal::ZERO {
+                remaining = remaining.min(term(
+                    params.system,
+                    balance,
+                    rate,
+                    installment,
+                    amortization,
+                ));
+            }
+        }
+
+        schedule.push(Row {
+            month,
+            correction,
+            interest,
+            amortization,
+            installment,
+            mip,
+            dfi,
+            extra,
+            payment: total([installment, mip, dfi, extra])?,
+            balance,
+        });
+    }
+
+    let payments = schedule.iter().map(|r| r.payment).collect::<Vec<_>>();
+    let present = params.principal - params.fees.unwrap_or_default();
+    let monthly_cet = internal_rate(present, &payments);
+
+    Ok(LoanModel {
+        monthly_rate: (rate * dec!(100)).round_dp(4),
+        monthly_correction: monthly_correction.round_dp(4),
+        term: month,
+        total_paid: total(payments.iter().copied())?,
+        total_interest: total(schedule.iter().map(|r| r.interest))?,
+        total_correction: total(schedule.iter().map(|r| r.correction))?,
+        total_insurance: total(schedule.iter().flat_map(|r| [r.mip, r.dfi]))?,
+        monthly_cet: (monthly_cet * dec!(100)).round_dp(4),
+        annual_cet: (((Decimal::ONE + monthly_cet).powu(12) - Decimal::ONE) * dec!(100))
+            .round_dp(4),
+        schedule,
+        params,
+    })
+}
+
+/// Sum of `values`, failing instead of overflowing.
+fn total(values: impl IntoIterator<Item = Decimal>) -> AppResult<Decimal> {
+    values.into_iter().try_fold(Decimal::ZERO, |total, value| {
+        total.checked_add(value).ok_or_else(too_large)
+    })
+}
+
+/// Months left to pay `balance` keeping the current installment (Price) or amortization (SAC).
+/// `u16::MAX` when it never would, such as when the installment doesn't cover the interest.
+fn term(
+    system: System,
+    balance: Decimal,
+    rate: Decimal,
+    installment: Decimal,
+    amortization: Decimal,
+) -> u16 {
+    let months = match system {
+        System::Sac => balance.checked_div(amortization),
+        System::Price if rate.is_zero() => balance.checked_div(installment),
+        System::Price => installment
+            .checked_div(installment - balance * rate)
+            .filter(|ratio| *ratio > Decimal::ONE)
+            .and_then(|ratio| ratio.checked_ln())
+            .and_then(|ln| ln.checked_div((Decimal::ONE + rate).checked_ln()?)),
+    };
+
+    months
+        .filter(|months| *months > Decimal::ZERO)
+        .and_then(|months| months.ceil().to_u16())
+        .unwrap_or(u16::MAX)
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::MAX_RATE, *};
+
+    fn params(system: System) -> Params {
+        Params {
+            principal: dec!(120000),
+            months: 120,
+            rate: dec!(12),
+            system,
+            correction: None,
+            mip: None,
+            dfi: None,
+            property_value: None,
+            fees: None,
+            extra: vec![],
+            date: None,
+        }
+    }
+
+    #[test]
+    fn price_has_fixed_installments() {
+        let result = simulate(params(System::Price), Decimal::ZERO).unwrap();
+
+        assert_eq!(result.term, 120);
+        assert_eq!(
+            result.schedule[0].installment,
+            result.schedule[60].installment
+        );
+        assert_eq!(result.schedule[119].balance, Decimal::ZERO);
+        // Without fees or insurance the CET is the interest rate itself.
+        assert_eq!(result.annual_cet.round_dp(1), dec!(12.0));
+    }
+
+    #[test]
+    fn sac_has_constant_amortization() {
+        let result = simulate(params(System::Sac), Decimal::ZERO).unwrap();
+
+        assert_eq!(result.schedule[0].amortization, dec!(1000));
+        assert_eq!(result.schedule[119].amortization, dec!(1000));
+        assert!(result.schedule[0].installment > result.schedule[119].installment);
+
+        let price = simulate(params(System::Price), Decimal::ZERO).unwrap();
+        assert!(result.total_interest < price.total_interest);
+    }
+
+    #[test]
+    fn extra_payments() {
+        let extra = |mode| Extra {
+            month: 12,
+            amount: dec!(30000),
+            mode,
+        };
+
+        let shorter = Params {
+            extra: vec![extra(ExtraMode::ReduceTerm)],
+            ..params(System::Price)
+        };
+        let result = simulate(shorter, Decimal::ZERO).unwrap();
+
+        assert!(result.term < 120);
+        let kept = result.schedule[12].installment;
+
+        let cheaper = Params {
+            extra: vec![extra(ExtraMode::ReduceInstallment)],
+            ..params(System::Price)
+        };
+        let result = simulate(cheaper, Decimal::ZERO).unwrap();
+
+        assert_eq!(result.term, 120);
+        // Rounding the term up trims the kept installment a little, far less than reducing it.
+        assert!(result.schedule[12].installment < kept * dec!(0.8));
+    }
+
+    #[test]
+    fn correction_and_insurance_raise_the_cost() {
+        let financing = Params {
+            mip: Some(dec!(0.02)),
+            dfi: Some(dec!(0.01)),
+            property_value: Some(dec!(200000)),
+            fees: Some(dec!(3000)),
+            ..params(System::Sac)
+        };
+        let result = simulate(financing, monthly(dec!(2))).unwrap();
+
+        assert_eq!(result.schedule[0].correction, dec!(198.19));
+        assert_eq!(result.schedule[0].mip, dec!(24.04));
+        assert_eq!(result.schedule[0].dfi, dec!(20));
+        assert!(result.total_correction > Decimal::ZERO);
+        assert!(result.annual_cet > dec!(14));
+    }
+
+    #[test]
+    fn rejects_terms_and_rates_out_of_range() {
+        let long = Params {
+            months: MAX_MONTHS + 1,
+            ..params(System::Price)
+        };
+        assert!(simulate(long, Decimal::ZERO).is_err());
+
+        let negative = Params {
+            rate: dec!(-100),
+            ..params(System::Price)
+        };
+        assert!(simulate(negative, Decimal::ZERO).is_err());
+
+        // The longest term at a steep rate still works, CET included.
+        for system in [System::Price, System::Sac] {
+            let steep = Params {
+                months: MAX_MONTHS,
+                rate: dec!(400),
+                ..params(system)
+            };
+            let result = simulate(steep, Decimal::ZERO).unwrap();
+            assert_eq!(result.term, MAX_MONTHS);
+        }
+
+        // Paying off all but a few cents leaves nothing to amortize each month.
+        let almost_paid = Params {
+            extra: vec![Extra {
+                month: 1,
+                amount: dec!(118999.95),
+                mode: ExtraMode::ReduceTerm,
+            }],
+            ..params(System::Sac)
+        };
+        assert!(simulate(almost_paid, Decimal::ZERO).is_ok());
+    }
+
+    #[test]
+    fn rejects_runaway_corrections() {
+        let steep = Params {
+            rate: MAX_RATE + Decimal::ONE,
+            ..params(System::Price)
+        };
+        assert!(simulate(steep, Decimal::ZERO).is_err());
+
+        // Corrected by the highest rate accepted, the balance outgrows a decimal.
+        let corrected = Params {
+            months: MAX_MONTHS,
+            ..params(System::Sac)
+        };
+        assert!(simulate(corrected, monthly(MAX_RATE)).is_err());
+    }
+
+    #[test]
+    fn rejects_negative_amounts() {
+        let negative = [
+            Params {
+                mip: Some(dec!(-0.02)),
+                ..params(System::Sac)
+            },
+            Params {
+                dfi: Some(dec!(-0.01)),
+                property_value: Some(dec!(200000)),
+                ..params(System::Sac)
+            },
+            Params {
+                dfi: Some(dec!(0.01)),
+                property_value: Some(dec!(-200000)),
+                ..params(System::Sac)
+            },
+            Params {
+                fees: Some(dec!(-3000)),
+                ..params(System::Sac)
+            },
+            Params {
+                fees: Some(dec!(120000)),
+                ..params(System::Sac)
+            },
+            Params {
+                extra: vec![Extra {
+                    month: 12,
+                    amount: dec!(-30000),
+                    mode: ExtraMode::ReduceTerm,
+                }],
+                ..params(System::Sac)
+            },
+        ];
+
+        for params in negative {
+            assert!(simulate(params, Decimal::ZERO).is_err());
+        }
+    }
+
+    #[test]
+    fn dfi_needs_the_property_value() {
+        let params = Params {
+            dfi: Some(dec!(0.01)),
+            ..params(System::Sac)
+        };
+
+        assert!(simulate(params, Decimal::ZERO).is_err());
+    }
+}
@@ -1,15 +1,144 @@
+mod backtest;
+mod calendar;
+pub mod fixed_income;
 pub mod installments;
+mod loan;
+mod payslip;
 pub mod savings;
+mod severance;
+mod tax_tables;
 mod wage_deduction;
 
 use axum::{Router, routing};
+use chrono::{NaiveDate, Utc};
+use lib::{
+    AppError, AppResult,
+    infra::{DbState, Rate, RateKind},
+    repository::RateRepository,
+};
+use rust_decimal::{Decimal, MathematicalOps};
+use rust_decimal_macros::dec;
 
-pub fn router() -> Router {
+pub fn router(db: DbState) -> Router {
     Router::new()
         .route("/wage-deduction", routing::get(wage_deduction::handler))
-        .route("/installments", routing::get(installments::handler))
+        .route("/payslip", routing::get(payslip::handler))
+        .route("/severance", routing::get(severance::handler))
+        .route("/installments", routing::post(installments::handler))
         .route("/savings", routing::get(savings::handler))
+        .route("/fixed-income", routing::post(fixed_income::handler))
+        .route("/backtest", routing::get(backtest::handler))
+        .route("/loan", routing::post(loan::handler))
+        .with_state(db)
 }
 
-pub const SELIC: f64 = 15.0;
-pub const CDI: f64 = SELIC - 0.1;
+/// Annual rates, in percent, effective on a given date.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Rates {
+    pub selic: Decimal,
+    pub cdi: Decimal,
+    pub treasury_spread: Decimal,
+    pub fgts: Decimal,
+}
+
+impl Rates {
+    /// Rates effective on `date`, today if `None`.
+    pub async fn load(repository: &dyn RateRepository, date: Option<NaiveDate>) -> AppResult<Self> {
+        let date = date.unwrap_or_else(|| Utc::now().date_naive());
+        let rates = repository.in_effect(date).await?;
+
+        let find = |kind| {
+            rates
+                .iter()
+                .find(|r: &&Rate| r.kind == kind)
+                .map(|r| r.value)
+                .ok_or_else(|| {
+                    AppError::Validation(format!("No {kind:?} rate effective on {date}"))
+                })
+        };
+
+        Ok(Self {
+            selic: find(RateKind::Selic)?,
+            cdi: find(RateKind::Cdi)?,
+            treasury_spread: find(RateKind::TreasurySpread)?,
+            fgts: find(RateKind::Fgts)?,
+        })
+    }
+
+    pub fn treasury(&self) -> Decimal {
+        self.selic + self.treasury_spread
+    }
+
+    /// Monthly savings (poupança) yield: 0.5% while the SELIC is above 8.5%, else 70% of it.
+    pub fn savings_monthly(&self) -> Decimal {
+        if self.selic > dec!(8.5) {
+            dec!(0.5)
+        } else {
+            self.selic * dec!(0.7) / dec!(12)
+        }
+    }
+}
+
+/// Highest annual rate, in percent, the calculators take. Far above any real one.
+pub const MAX_RATE: Decimal = dec!(1000);
+
+/// The balance outgrew what can be represented, only possible at absurd rates.
+pub fn too_large() -> AppError {
+    AppError::Validation("The balance grows too large to simulate".to_string())
+}
+
+/// Rejects annual rates, in percent, at or below -100%, which would take more than everything,
+/// or above [`MAX_RATE`].
+pub fn check_rate(rate: Decimal, name: &str) -> AppResult<()> {
+    if rate <= dec!(-100) || rate > MAX_RATE {
+        return Err(AppError::Validation(format!(
+            "The {name} rate must be above -100% and at most {MAX_RATE}%"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Fixed installment of the French amortization table (Tabela Price), `rate` per period. `None`
+/// when the rate is too far from zero for that many periods to be represented.
+pub fn price_installment(principal: Decimal, rate: Decimal, count: u16) -> Option<Decimal> {
+    if rate.is_zero() {
+        return Some(principal / Decimal::from(count));
+    }
+
+    let discount = Decimal::ONE.checked_div((Decimal::ONE + rate).checked_powi(count.into())?)?;
+    principal
+        .checked_mul(rate)?
+        .checked_div(Decimal::ONE - discount)
+}
+
+/// Rate per period at which `payments`, starting one period from now, are worth `present` today.
+/// Found by bisection, used as the CET (custo efetivo total).
+pub fn internal_rate(present: Decimal, payments: &[Decimal]) -> Decimal {
+    // `None` when too large to represent, which only happens for negative rates.
+    let present_value = |rate: Decimal| {
+        let mut discount = Decimal::ONE;
+        let mut value = Decimal::ZERO;
+
+        for payment in payments {
+            discount = discount.checked_div(Decimal::ONE + rate)?;
+            value = value.checked_add(payment.checked_mul(discount)?)?;
+        }
+
+        Some(value)
+    };
+
+    let (mut low, mut high) = (dec!(-0.1), dec!(10));
+
+    for _ in 0..100 {
+        let mid = (low + high) / dec!(2);
+
+        // The present value falls as the rate rises.
+        match present_value(mid) {
+            Some(value) if value <= present => high = mid,
+            _ => low = mid,
+        }
+    }
+
+    (low + high) / dec!(2)
+}
@@ -0,0 +1,382 @@
+use axum::extract::Query;
+use chrono::NaiveDate;
+use lib::{AppError, AppResult, Json, Response};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::tax_tables::{self, round_cents};
+
+const FGTS_RATE: Decimal = dec!(0.08);
+/// Employees pay at most 6% of the base wage for the transportation voucher.
+const TRANSPORTATION_SHARE: Decimal = dec!(0.06);
+const MONTH_DAYS: Decimal = dec!(30);
+const MAX_SOLD_VACATION_DAYS: u8 = 10;
+
+#[derive(serde::Deserialize)]
+pub struct Params {
+    wage: Decimal,
+    dependents: Option<u16>,
+    year: Option<i32>,
+    reference_date: Option<NaiveDate>,
+    /// Vacation days taken this month, paid with the 1/3 bonus.
+    vacation_days: Option<u8>,
+    /// Vacation days sold back to the employer (abono pecuniário).
+    sold_vacation_days: Option<u8>,
+    thirteenth: Option<Thirteenth>,
+    /// Months worked in the year, for a proportional 13th salary.
+    thirteenth_months: Option<u8>,
+    plr: Option<Decimal>,
+    /// Monthly cost of the transportation voucher.
+    transportation_voucher: Option<Decimal>,
+    meal_voucher: Option<Decimal>,
+    health_plan: Option<Decimal>,
+    /// Court-ordered alimony, deductible from the IRRF base.
+    alimony: Option<Decimal>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Thirteenth {
+    /// Paid until November, half of the 13th salary without any deduction.
+    First,
+    /// Paid until December, with INSS and IRRF over the whole 13th salary.
+    Second,
+}
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Item {
+    Salary,
+    Vacation,
+    VacationBonus,
+    SoldVacation,
+    SoldVacationBonus,
+    ThirteenthAdvance,
+    Thirteenth,
+    Plr,
+    Inss,
+    Irrf,
+    InssThirteenth,
+    IrrfThirteenth,
+    IrrfPlr,
+    TransportationVoucher,
+    MealVoucher,
+    HealthPlan,
+    Alimony,
+}
+
+#[derive(serde::Serialize)]
+pub struct Line {
+    item: Item,
+    /// Days, months or rate the amount was calculated from.
+    reference: Option<Decimal>,
+    amount: Decimal,
+}
+
+#[derive(serde::Serialize)]
+pub struct PayslipModel {
+    earnings: Vec<Line>,
+    deductions: Vec<Line>,
+    total_earnings: Decimal,
+    total_deductions: Decimal,
+    net: Decimal,
+    /// Deposited by the employer, not deducted from the payslip.
+    fgts: Decimal,
+    inss_base: Decimal,
+    irrf_base: Decimal,
+    simplified_discount: bool,
+    reference_date: NaiveDate,
+}
+
+pub async fn handler(Query(params): Query<Params>) -> Response<PayslipModel> {
+    let date = tax_tables::reference_date(params.reference_date, params.year)?;
+
+    Ok(Json(payslip(&params, date)?))
+}
+
+#[derive(Default)]
+struct Payslip {
+    earnings: Vec<Line>,
+    deductions: Vec<Line>,
+}
+
+impl Payslip {
+    fn earning(&mut self, item: Item, reference: Option<Decimal>, amount: Decimal) -> Decimal {
+        push(&mut self.earnings, item, reference, amount)
+    }
+
+    fn deduction(&mut self, item: Item, reference: Option<Decimal>, amount: Decimal) -> Decimal {
+        push(&mut self.deductions, item, reference, amount)
+    }
+}
+
+fn push(lines: &mut Vec<Line>, item: Item, reference: Option<Decimal>, amount: Decimal) -> Decimal {
+    let amount = round_cents(amount);
+
+    if !amount.is_zero() {
+        lines.push(Line {
+            item,
+            reference,
+            amount,
+        });
+    }
+
+    amount
+}
+
+fn payslip(params: &Params, date: NaiveDate) -> AppResult<PayslipModel> {
+    let (inss_table, irrf_table) = tax_tables::payroll_tables(date)?;
+
+    let vacation_days = params.vacation_days.unwrap_or_default();
+    let sold_days = params.sold_vacation_days.unwrap_or_default();
+
+    if sold_days > MAX_SOLD_VACATION_DAYS {
+        return Err(AppError::Validation(format!(
+            "At most {MAX_SOLD_VACATION_DAYS} vacation days can be sold"
+        )));
+    }
+    if vacation_days + sold_days > 30 {
+        return Err(AppError::Validation(
+            "Vacation days can't exceed 30".to_string(),
+        ));
+    }
+
+    let dependents = params.dependents.unwrap_or_default();
+    let daily_wage = params.wage / MONTH_DAYS;
+    let mut slip = Payslip::default();
+
+    let worked_days = Decimal::from(30 - vacation_days);
+    let salary = slip.earning(Item::Salary, Some(worked_days), daily_wage * worked_days);
+
+    let vacation_days = Decimal::from(vacation_days);
+    let vacation = slip.earning(
+        Item::Vacation,
+        Some(vacation_days),
+        daily_wage * vacation_days,
+    );
+    let vacation_bonus = slip.earning(Item::VacationBonus, None, vacation / dec!(3));
+
+    // The abono pecuniário and its bonus are exempt from INSS, IRRF and FGTS.
+    let sold_days = Decimal::from(sold_days);
+    let sold = slip.earning(Item::SoldVacation, Some(sold_days), daily_wage * sold_days);
+    slip.earning(Item::SoldVacationBonus, None, sold / dec!(3));
+
+    let monthly = salary + vacation + vacation_bonus;
+    let mut fgts_base = monthly;
+
+    let inss = slip.deduction(Item::Inss, None, inss_table.contribution(monthly));
+
+    let alimony = params.alimony.unwrap_or_default();
+    let irrf = irrf_table.withholding(monthly, inss, dependents, alimony);
+    slip.deduction(Item::Irrf, Some(irrf.rate), irrf.tax);
+
+    if let Some(thirteenth) = params.thirteenth {
+        let months = params.thirteenth_months.unwrap_or(12).min(12);
+        let months = Decimal::from(months);
+        let full = params.wage * months / dec!(12);
+        let advance = round_cents(full / dec!(2));
+
+        match thirteenth {
+            Thirteenth::First => {
+                slip.earning(Item::ThirteenthAdvance, Some(months), advance);
+                fgts_base += advance;
+            }
+            Thirteenth::Second => {
+                let full = slip.earning(Item::Thirteenth, Some(months), full);
+                slip.deduction(Item::ThirteenthAdvance, None, advance);
+                fgts_base += full - advance;
+
+                let inss =
+                    slip.deduction(Item::InssThirteenth, None, inss_table.contribution(full));
+                let irrf = irrf_table.exclusive_withholding(full, inss, dependents);
+                slip.deduction(Item::IrrfThirteenth, Some(irrf.rate), irrf.tax);
+            }
+        }
+    }
+
+    if let Some(plr) = params.plr {
+        let plr = slip.earning(Item::Plr, None, plr);
+
+        let plr_table = tax_tables::plr_table(date)
+            .ok_or_else(|| AppError::Validation(format!("No PLR table available for {date}")))?;
+        slip.deduction(Item::IrrfPlr, None, plr_table.tax(plr));
+    }
+
+    if let Some(cost) = params.transportation_voucher {
+        slip.deduction(
+            Item::TransportationVoucher,
+            Some(TRANSPORTATION_SHARE),
+            cost.min(salary * TRANSPORTATION_SHARE),
+        );
+    }
+
+    slip.deduction(
+        Item::MealVoucher,
+        None,
+        params.meal_voucher.unwrap_or_default(),
+    );
+    slip.deduction(
+        Item::HealthPlan,
+        None,
+        params.health_plan.unwrap_or_default(),
+    );
+    slip.deduction(Item::Alimony, None, alimony);
+
+    let total_earnings = slip.earnings.iter().map(|l| l.amount).sum();
+    let total_deductions = slip.deductions.iter().map(|l| l.amount).sum();
+
+    Ok(PayslipModel {
+        earnings: slip.earnings,
+        deductions: slip.deductions,
+        total_earnings,
+        total_deductions,
+        net: total_earnings - total_deductions,
+        fgts: round_cents(fgts_base * FGTS_RATE),
+        inss_base: monthly,
+        irrf_base: irrf.base,
+        simplified_discount: irrf.simplified_discount,
+        reference_date: date,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(wage: Decimal) -> Params {
+        Params {
+            wage,
+            dependents: None,
+            year: None,
+            reference_date: None,
+            vacation_days: None,
+            sold_vacation_days: None,
+            thirteenth: None,
+            thirteenth_months: None,
+            plr: None,
+            transportation_voucher: None,
+            meal_voucher: None,
+            health_plan: None,
+            alimony: None,
+        }
+    }
+
+    fn amount(lines: &[Line], item: Item) -> Decimal {
+        lines
+            .iter()
+            .find(|l| l.item == item)
+            .map(|l| l.amount)
+            .unwrap_or_default()
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()
+    }
+
+    #[test]
+    fn monthly_with_benefits() {
+        let params = Params {
+            transportation_voucher: Some(dec!(400)),
+            meal_voucher: Some(dec!(50)),
+            health_plan: Some(dec!(120)),
+            ..params(dec!(5000))
+        };
+
+        let result = payslip(&params, date()).unwrap();
+
+        assert_eq!(amount(&result.deductions, Item::Inss), dec!(509.60));
+        assert_eq!(amount(&result.deductions, Item::Irrf), dec!(312.89));
+        assert_eq!(
+            amount(&result.deductions, Item::TransportationVoucher),
+            dec!(300)
+        );
+        assert_eq!(result.total_deductions, dec!(1292.49));
+        assert_eq!(result.net, dec!(3707.51));
+        assert_eq!(result.fgts, dec!(400));
+    }
+
+    #[test]
+    fn vacation_with_sold_days() {
+        let params = Params {
+            vacation_days: Some(20),
+            sold_vacation_days: Some(10),
+            ..params(dec!(3000))
+        };
+
+        let result = payslip(&params, date()).unwrap();
+
+        assert_eq!(amount(&result.earnings, Item::Vacation), dec!(2000));
+        assert_eq!(amount(&result.earnings, Item::VacationBonus), dec!(666.67));
+        assert_eq!(amount(&result.earnings, Item::SoldVacation), dec!(1000));
+        assert_eq!(
+            amount(&result.earnings, Item::SoldVacationBonus),
+            dec!(333.33)
+        );
+        assert_eq!(result.inss_base, dec!(3666.67));
+        assert_eq!(result.total_earnings, dec!(5000));
+    }
+
+    #[test]
+    fn thirteenth_installments() {
+        let first = Params {
+            thirteenth: Some(Thirteenth::First),
+            ..params(dec!(4000))
+        };
+
+        let result = payslip(&first, date()).unwrap();
+
+        assert_eq!(
+            amount(&result.earnings, Item::ThirteenthAdvance),
+            dec!(2000)
+        );
+        assert_eq!(result.fgts, dec!(480));
+
+        let second = Params {
+            thirteenth: Some(Thirteenth::Second),
+            ..params(dec!(4000))
+        };
+
+        let result = payslip(&second, date()).unwrap();
+
+        assert_eq!(amount(&result.earnings, Item::Thirteenth), dec!(4000));
+        assert_eq!(
+            amount(&result.deductions, Item::ThirteenthAdvance),
+            dec!(2000)
+        );
+        assert_eq!(
+            amount(&result.deductions, Item::InssThirteenth),
+            dec!(373.41)
+        );
+        // 4000 - 373.41 = 3626.59, taxed at 15% without the simplified discount.
+        assert_eq!(
+            amount(&result.deductions, Item::IrrfThirteenth),
+            dec!(149.83)
+        );
+    }
+
+    #[test]
+    fn plr_and_alimony() {
+        let params = Params {
+            plr: Some(dec!(10000)),
+            alimony: Some(dec!(1000)),
+            ..params(dec!(5000))
+        };
+
+        let result = payslip(&params, date()).unwrap();
+
+        assert_eq!(amount(&result.deductions, Item::IrrfPlr), dec!(176.94));
+        assert!(!result.simplified_discount);
+        // 5000 - 509.60 - 1000 = 3490.40, taxed at 15%.
+        assert_eq!(amount(&result.deductions, Item::Irrf), dec!(129.40));
+    }
+
+    #[test]
+    fn rejects_too_many_sold_days() {
+        let params = Params {
+            sold_vacation_days: Some(15),
+            ..params(dec!(3000))
+        };
+
+        assert!(payslip(&params, date()).is_err());
+    }
+}
@@ -1,113 +1,145 @@
-use axum::{Json, extract::Query};
-use rust_decimal::{Decimal, prelude::FromPrimitive};
+use axum::extract::{Query, State};
+use chrono::{NaiveDate, Utc};
+use lib::{AppError, AppResult, Json, Response, infra::DbState};
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
-use super::{CDI, SELIC};
+use super::{
+    Rates,
+    fixed_income::{
+        self, CONTRIBUTION_DAYS, Horizon, Index, MAX_DAYS, Product, ProductResult, TaxTreatment,
+    },
+};
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Params {
     pub initial: Decimal,
     pub contribution: Decimal,
     pub months: u16,
+    /// Starts the simulation on this date, with the rates effective on it, instead of today.
+    pub date: Option<NaiveDate>,
 }
 
 #[derive(serde::Serialize)]
 pub struct SavingsModel {
-    revenue: Revenue,
+    revenue: Vec<ProductResult>,
     params: Params,
+    rates: Rates,
 }
 
-#[derive(serde::Serialize, Debug)]
-pub struct Revenue {
-    pub savings: Decimal,
-    pub nubank: Decimal,
-    pub picpay: Decimal,
-    pub treasury: Decimal,
-    pub selic: Decimal,
-    pub fgts: Decimal,
-}
-
-struct SavingSimulation {
-    total_invested: Decimal,
-    savings: Decimal,
+pub async fn handler(
+    State(db): State<DbState>,
+    Query(params): Query<Params>,
+) -> Response<SavingsModel> {
+    let rates = Rates::load(db.rates.as_ref(), params.date).await?;
+    let revenue = get_revenue(&params, &rates)?;
+
+    Ok(Json(SavingsModel {
+        params,
+        revenue,
+        rates,
+    }))
 }
 
-const TREASURY_2027: f64 = SELIC + 0.1736;
-const SAVINGS_RATE: f64 = if SELIC > 8.5 {
-    0.5
-} else {
-    SELIC * 70. / 100. / 12.
-};
-
-pub async fn handler(Query(params): Query<Params>) -> Json<SavingsModel> {
-    let revenue = get_revenue(&params);
-
-    Json(SavingsModel { params, revenue })
-}
-
-pub fn get_revenue(params: &Params) -> Revenue {
-    let c = |monthly_fee| {
-        let s = get_gross(params, monthly_fee);
-        s.savings - get_ir(s.total_invested, s.savings, params.months)
+/// Products the savings simulator compares by default.
+pub fn default_products(rates: &Rates) -> Vec<Product> {
+    let product = |name: &str, index, tax, custody_fee| Product {
+        name: name.to_string(),
+        index,
+        tax,
+        custody_fee,
     };
 
-    Revenue {
-        savings: c(SAVINGS_RATE),
-        nubank: c(CDI * 100. / 100. / 12.),
-        picpay: c(CDI * 102. / 100. / 12.),
-        treasury: c(TREASURY_2027 / 12.),
-        selic: c(TREASURY_2027 / 12.),
-        fgts: get_gross(params, 3. / 12.).savings,
-    }
+    vec![
+        product("savings", Index::Savings, TaxTreatment::Exempt, None),
+        product(
+            "nubank",
+            Index::Cdi { percent: dec!(100) },
+            TaxTreatment::Regressive,
+            None,
+        ),
+        product(
+            "picpay",
+            Index::Cdi { percent: dec!(102) },
+            TaxTreatment::Regressive,
+            None,
+        ),
+        product(
+            "treasury",
+            Index::Selic,
+            TaxTreatment::Regressive,
+            Some(dec!(0.2)),
+        ),
+        product(
+            "lci",
+            Index::Cdi { percent: dec!(90) },
+            TaxTreatment::Exempt,
+            None,
+        ),
+        product(
+            "fgts",
+            Index::Prefixed { rate: rates.fgts },
+            TaxTreatment::Exempt,
+            None,
+        ),
+    ]
 }
 
-fn get_gross(params: &Params, monthly_rate: f64) -> SavingSimulation {
-    let monthly_rate = Decimal::from_f64(monthly_rate).unwrap();
-
-    let mut savings = params.initial;
-
-    for _ in 0..params.months {
-        savings *= dec!(1) + monthly_rate / dec!(100);
-        savings += params.contribution;
+pub fn get_revenue(params: &Params, rates: &Rates) -> AppResult<Vec<ProductResult>> {
+    let max_months = MAX_DAYS / CONTRIBUTION_DAYS;
+    if u32::from(params.months) > max_months {
+        return Err(AppError::Validation(format!(
+            "The simulation can take at most {max_months} months"
+        )));
     }
 
-    savings += params.contribution;
-
-    SavingSimulation {
-        savings,
-        total_invested: {
-            let initial = params.initial;
-            let contribution = params.contribution;
-            initial + contribution * Decimal::from_u16(params.months).unwrap()
-        },
-    }
-}
-
-fn get_ir(invested: Decimal, gross: Decimal, months: u16) -> Decimal {
-    let ir_fee = match months {
-        0..=6 => dec!(22.5),
-        7..=12 => dec!(20.0),
-        13..=23 => dec!(17.5),
-        24.. => dec!(15.0),
+    let horizon = Horizon {
+        start: params.date.unwrap_or_else(|| Utc::now().date_naive()),
+        initial: params.initial,
+        contribution: params.contribution,
+        days: u32::from(params.months) * CONTRIBUTION_DAYS,
+        ipca: None,
     };
 
-    let revenue = gross - invested;
-
-    ir_fee * revenue / dec!(100)
+    fixed_income::compare(&default_products(rates), rates, &horizon)
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::NaiveDate;
     use rust_decimal_macros::dec;
 
+    use super::super::Rates;
+
     #[test]
     fn savings() {
-        let s = super::get_revenue(&super::Params {
+        let rates = Rates {
+            selic: dec!(15),
+            cdi: dec!(14.9),
+            treasury_spread: dec!(0.1736),
+            fgts: dec!(3),
+        };
+
+        let revenue = super::get_revenue(
+            &super::Params {
+                initial: dec!(1000),
+                contribution: dec!(0),
+                months: 10,
+                date: NaiveDate::from_ymd_opt(2025, 1, 2),
+            },
+            &rates,
+        )
+        .unwrap();
+
+        let picpay = revenue.iter().find(|r| r.name == "picpay").unwrap();
+        assert_eq!(picpay.net, dec!(1099.23));
+
+        let forever = super::Params {
             initial: dec!(1000),
             contribution: dec!(0),
-            months: 10,
-        });
-
-        assert_eq!(s.picpay, dec!(1086.5673405142189276291334422));
+            months: u16::MAX,
+            date: None,
+        };
+        assert!(super::get_revenue(&forever, &rates).is_err());
     }
 }
@@ -0,0 +1,417 @@
+use axum::extract::Query;
+use chrono::{Datelike, Months, NaiveDate};
+use lib::{AppError, AppResult, Json, Response};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::tax_tables::{self, round_cents};
+
+const FGTS_RATE: Decimal = dec!(0.08);
+const MONTH_DAYS: Decimal = dec!(30);
+/// Lei 12.506/2011: 30 days plus 3 per full year of service, up to 90.
+const NOTICE_BASE_DAYS: i64 = 30;
+const NOTICE_DAYS_PER_YEAR: i64 = 3;
+const NOTICE_MAX_DAYS: i64 = 90;
+
+#[derive(serde::Deserialize)]
+pub struct Params {
+    wage: Decimal,
+    dependents: Option<u16>,
+    hire_date: NaiveDate,
+    /// Last day worked, also picks the tax tables.
+    termination_date: NaiveDate,
+    reason: Dismissal,
+    notice: Option<Notice>,
+    /// FGTS deposited during the contract, before this termination.
+    fgts_balance: Option<Decimal>,
+    /// Full vacation periods earned and not taken yet.
+    expired_vacations: Option<u8>,
+    /// First installment of the 13th salary already paid this year.
+    thirteenth_advance: Option<Decimal>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Dismissal {
+    WithoutCause,
+    WithCause,
+    Resignation,
+    /// Art. 484-A of the CLT, half of the notice and of the FGTS fine.
+    MutualAgreement,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Notice {
+    Worked,
+    /// Paid by the employer, or deducted from a resigning employee who doesn't serve it.
+    #[default]
+    Indemnified,
+}
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Item {
+    SalaryBalance,
+    Notice,
+    Thirteenth,
+    ProportionalVacation,
+    ProportionalVacationBonus,
+    ExpiredVacation,
+    ExpiredVacationBonus,
+}
+
+#[derive(serde::Serialize)]
+pub struct Component {
+    item: Item,
+    /// Days or months the amount was calculated from.
+    reference: Option<Decimal>,
+    amount: Decimal,
+    inss: Decimal,
+    irrf: Decimal,
+}
+
+#[derive(serde::Serialize)]
+pub struct SeveranceModel {
+    components: Vec<Component>,
+    /// Unserved notice of a resignation.
+    notice_deduction: Decimal,
+    thirteenth_advance: Decimal,
+    total_earnings: Decimal,
+    total_deductions: Decimal,
+    net: Decimal,
+    notice_days: i64,
+    /// Contract end including the indemnified notice.
+    projected_end: NaiveDate,
+    fgts_deposit: Decimal,
+    fgts_fine: Decimal,
+    /// What the employee can withdraw from the FGTS account, fine included.
+    fgts_withdrawable: Decimal,
+}
+
+pub async fn handler(Query(params): Query<Params>) -> Response<SeveranceModel> {
+    Ok(Json(severance(&params)?))
+}
+
+impl Dismissal {
+    fn fgts_fine(self) -> Decimal {
+        match self {
+            Dismissal::WithoutCause => dec!(0.4),
+            Dismissal::MutualAgreement => dec!(0.2),
+            Dismissal::WithCause | Dismissal::Resignation => Decimal::ZERO,
+        }
+    }
+
+    fn fgts_withdrawal(self) -> Decimal {
+        match self {
+            Dismissal::WithoutCause => Decimal::ONE,
+            Dismissal::MutualAgreement => dec!(0.8),
+            Dismissal::WithCause | Dismissal::Resignation => Decimal::ZERO,
+        }
+    }
+
+    /// Share of the notice the employer pays when it isn't worked.
+    fn paid_notice(self) -> Decimal {
+        match self {
+            Dismissal::WithoutCause => Decimal::ONE,
+            Dismissal::MutualAgreement => dec!(0.5),
+            Dismissal::WithCause | Dismissal::Resignation => Decimal::ZERO,
+        }
+    }
+
+    /// Dismissal with cause loses the proportional 13th and vacation.
+    fn proportional_rights(self) -> bool {
+        self != Dismissal::WithCause
+    }
+}
+
+fn severance(params: &Params) -> AppResult<SeveranceModel> {
+    if params.termination_date < params.hire_date {
+        return Err(AppError::Validation(
+            "Termination date must be after the hire date".to_string(),
+        ));
+    }
+
+    let date = params.termination_date;
+    let (inss_table, irrf_table) = tax_tables::payroll_tables(date)?;
+    let dependents = params.dependents.unwrap_or_default();
+    let daily_wage = params.wage / MONTH_DAYS;
+    let reason = params.reason;
+    let notice = params.notice.unwrap_or_default();
+
+    let mut components = vec![];
+
+    let days = Decimal::from(date.day().min(30));
+    let salary = round_cents(daily_wage * days);
+    let inss = inss_table.contribution(salary);
+    let irrf = irrf_table.withholding(salary, inss, dependents, Decimal::ZERO);
+    components.push(Component {
+        item: Item::SalaryBalance,
+        reference: Some(days),
+        amount: salary,
+        inss,
+        irrf: irrf.tax,
+    });
+
+    let notice_days = notice_days(params.hire_date, date);
+    let mut projected_end = date;
+    let mut notice_deduction = Decimal::ZERO;
+    let mut fgts_base = salary;
+
+    if notice == Notice::Indemnified {
+        let paid_days = Decimal::from(notice_days) * reason.paid_notice();
+
+        if paid_days > Decimal::ZERO {
+            // Indemnified notice pays FGTS, but no INSS or IRRF.
+            let amount = round_cents(daily_wage * paid_days);
+            fgts_base += amount;
+            projected_end = date + chrono::Duration::days(notice_days);
+
+            components.push(Component {
+                item: Item::Notice,
+                reference: Some(paid_days),
+                amount,
+                inss: Decimal::ZERO,
+                irrf: Decimal::ZERO,
+            });
+        } else if reason == Dismissal::Resignation {
+            notice_deduction = round_cents(params.wage);
+        }
+    }
+
+    if reason.proportional_rights() {
+        let months = Decimal::from(thirteenth_months(params.hire_date, projected_end));
+        let amount = round_cents(params.wage * months / dec!(12));
+
+        if !amount.is_zero() {
+            let inss = inss_table.contribution(amount);
+            let irrf = irrf_table.exclusive_withholding(amount, inss, dependents);
+            fgts_base += amount;
+
+            components.push(Component {
+                item: Item::Thirteenth,
+                reference: Some(months),
+                amount,
+                inss,
+                irrf: irrf.tax,
+            });
+        }
+
+        let months = Decimal::from(vacation_months(params.hire_date, projected_end));
+        let vacation = round_cents(params.wage * months / dec!(12));
+
+        push_vacation(
+            &mut components,
+            (Item::ProportionalVacation, Item::ProportionalVacationBonus),
+            months,
+            vacation,
+        );
+    }
+
+    // Vacation paid on termination is indemnified, free of INSS, IRRF and FGTS.
+    let expired = Decimal::from(params.expired_vacations.unwrap_or_default());
+    push_vacation(
+        &mut components,
+        (Item::ExpiredVacation, Item::ExpiredVacationBonus),
+        expired,
+        round_cents(params.wage * expired),
+    );
+
+    let thirteenth_advance = if reason.proportional_rights() {
+        params.thirteenth_advance.unwrap_or_default()
+    } else {
+        Decimal::ZERO
+    };
+
+    let total_earnings: Decimal = components.iter().map(|c| c.amount).sum();
+    let withheld: Decimal = components.iter().map(|c| c.inss + c.irrf).sum();
+    let total_deductions = withheld + notice_deduction + thirteenth_advance;
+
+    let fgts_deposit = round_cents(fgts_base * FGTS_RATE);
+    let fgts_account = params.fgts_balance.unwrap_or_default() + fgts_deposit;
+    let fgts_fine = round_cents(fgts_account * reason.fgts_fine());
+
+    Ok(SeveranceModel {
+        components,
+        notice_deduction,
+        thirteenth_advance,
+        total_earnings,
+        total_deductions,
+        net: total_earnings - total_deductions,
+        notice_days,
+        projected_end,
+        fgts_deposit,
+        fgts_fine,
+        fgts_withdrawable: round_cents(fgts_account * reason.fgts_withdrawal()) + fgts_fine,
+    })
+}
+
+fn push_vacation(
+    components: &mut Vec<Component>,
+    (item, bonus_item): (Item, Item),
+    reference: Decimal,
+    amount: Decimal,
+) {
+    if amount.is_zero() {
+        return;
+    }
+
+    for (item, amount) in [(item, amount), (bonus_item, round_cents(amount / dec!(3)))] {
+        components.push(Component {
+            item,
+            reference: Some(reference),
+            amount,
+            inss: Decimal::ZERO,
+            irrf: Decimal::ZERO,
+        });
+    }
+}
+
+fn full_years(from: NaiveDate, to: NaiveDate) -> u32 {
+    let mut years = to.year() - from.year();
+
+    if (to.month(), to.day()) < (from.month(), from.day()) {
+        years -= 1;
+    }
+
+    years.max(0) as u32
+}
+
+fn notice_days(hire: NaiveDate, termination: NaiveDate) -> i64 {
+    let years = i64::from(full_years(hire, termination));
+
+    (NOTICE_BASE_DAYS + NOTICE_DAYS_PER_YEAR * years).min(NOTICE_MAX_DAYS)
+}
+
+/// Months of the final year with at least 15 days worked.
+fn thirteenth_months(hire: NaiveDate, end: NaiveDate) -> u32 {
+    (1..=end.month())
+        .filter(|&month| {
+            let first = NaiveDate::from_ymd_opt(end.year(), month, 1).expect("valid month");
+            let last = (first + Months::new(1)).pred_opt().expect("valid date");
+
+            let worked = (last.min(end) - first.max(hire)).num_days() + 1;
+            worked >= 15
+        })
+        .count() as u32
+}
+
+/// Months since the last anniversary of the contract, counting 15 days or more as a month.
+fn vacation_months(hire: NaiveDate, end: NaiveDate) -> u32 {
+    // Periods are counted up to the day after the last one worked.
+    let end = end.succ_opt().expect("valid date");
+    let start = hire + Months::new(12 * full_years(hire, end));
+
+    let mut months = 0;
+    while start + Months::new(months + 1) <= end {
+        months += 1;
+    }
+
+    let remaining = (end - (start + Months::new(months))).num_days();
+    if remaining >= 15 {
+        months += 1;
+    }
+
+    months.min(12)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn params(reason: Dismissal) -> Params {
+        Params {
+            wage: dec!(3000),
+            dependents: None,
+            hire_date: date(2022, 3, 10),
+            termination_date: date(2025, 6, 20),
+            reason,
+            notice: None,
+            fgts_balance: Some(dec!(10000)),
+            expired_vacations: None,
+            thirteenth_advance: None,
+        }
+    }
+
+    fn amount(result: &SeveranceModel, item: Item) -> Decimal {
+        result
+            .components
+            .iter()
+            .find(|c| c.item == item)
+            .map(|c| c.amount)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn without_cause() {
+        let result = severance(&params(Dismissal::WithoutCause)).unwrap();
+
+        assert_eq!(result.notice_days, 39);
+        assert_eq!(result.projected_end, date(2025, 7, 29));
+        assert_eq!(amount(&result, Item::SalaryBalance), dec!(2000));
+        assert_eq!(amount(&result, Item::Notice), dec!(3900));
+        assert_eq!(amount(&result, Item::Thirteenth), dec!(1750));
+        assert_eq!(amount(&result, Item::ProportionalVacation), dec!(1250));
+        assert_eq!(
+            amount(&result, Item::ProportionalVacationBonus),
+            dec!(416.67)
+        );
+        assert_eq!(result.total_deductions, dec!(291.96));
+        assert_eq!(result.net, dec!(9024.71));
+        assert_eq!(result.fgts_deposit, dec!(612));
+        assert_eq!(result.fgts_fine, dec!(4244.80));
+        assert_eq!(result.fgts_withdrawable, dec!(14856.80));
+    }
+
+    #[test]
+    fn mutual_agreement() {
+        let result = severance(&params(Dismissal::MutualAgreement)).unwrap();
+
+        assert_eq!(amount(&result, Item::Notice), dec!(1950));
+        // 10000 + 8% of 2000 + 1950 + 1750.
+        assert_eq!(result.fgts_deposit, dec!(456));
+        assert_eq!(result.fgts_fine, dec!(2091.20));
+        assert_eq!(
+            result.fgts_withdrawable,
+            dec!(10456) * dec!(0.8) + dec!(2091.20)
+        );
+    }
+
+    #[test]
+    fn resignation_without_notice() {
+        let result = severance(&params(Dismissal::Resignation)).unwrap();
+
+        assert_eq!(amount(&result, Item::Notice), dec!(0));
+        assert_eq!(result.notice_deduction, dec!(3000));
+        assert_eq!(result.projected_end, date(2025, 6, 20));
+        assert_eq!(result.fgts_withdrawable, dec!(0));
+        // January to June.
+        assert_eq!(amount(&result, Item::Thirteenth), dec!(1500));
+    }
+
+    #[test]
+    fn with_cause_keeps_only_expired_vacation() {
+        let params = Params {
+            expired_vacations: Some(1),
+            ..params(Dismissal::WithCause)
+        };
+
+        let result = severance(&params).unwrap();
+
+        assert_eq!(amount(&result, Item::Thirteenth), dec!(0));
+        assert_eq!(amount(&result, Item::ProportionalVacation), dec!(0));
+        assert_eq!(amount(&result, Item::ExpiredVacation), dec!(3000));
+        assert_eq!(amount(&result, Item::ExpiredVacationBonus), dec!(1000));
+        assert_eq!(result.fgts_fine, dec!(0));
+    }
+
+    #[test]
+    fn notice_is_capped() {
+        assert_eq!(notice_days(date(1990, 1, 1), date(2025, 1, 1)), 90);
+        assert_eq!(notice_days(date(2025, 1, 1), date(2025, 6, 1)), 30);
+    }
+}
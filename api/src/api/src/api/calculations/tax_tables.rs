@@ -0,0 +1,521 @@
+//! Brazilian payroll tax tables (INSS and IRRF), each valid from the date it was published.
+//!
+//! To add a new year, append its tables to [`INSS_TABLES`], [`IRRF_TABLES`] and [`PLR_TABLES`],
+//! keeping them sorted by `valid_from`.
+
+use chrono::{NaiveDate, Utc};
+use lib::{AppError, AppResult};
+use rust_decimal::{Decimal, RoundingStrategy};
+use rust_decimal_macros::dec;
+
+pub struct InssTable {
+    pub valid_from: NaiveDate,
+    /// Progressive brackets, the last one ends at the contribution ceiling.
+    pub brackets: &'static [InssBracket],
+}
+
+pub struct InssBracket {
+    pub up_to: Decimal,
+    pub rate: Decimal,
+}
+
+pub struct IrrfTable {
+    pub valid_from: NaiveDate,
+    pub brackets: &'static [IrrfBracket],
+    pub dependent_deduction: Decimal,
+    /// Monthly simplified discount, used instead of the legal deductions when it is larger.
+    pub simplified_discount: Decimal,
+    pub reduction: Option<IrrfReduction>,
+}
+
+pub struct IrrfBracket {
+    pub up_to: Decimal,
+    pub rate: Decimal,
+    pub deduction: Decimal,
+}
+
+/// Tax reduction from Lei 15.270/2025, based on the gross taxable income.
+pub struct IrrfReduction {
+    /// Income up to this value pays no tax at all.
+    pub exempt_up_to: Decimal,
+    pub partial_up_to: Decimal,
+    /// Between the exempt and partial limits the reduction is `constant - factor * income`.
+    pub constant: Decimal,
+    pub factor: Decimal,
+}
+
+const fn date(year: i32, month: u32) -> NaiveDate {
+    match NaiveDate::from_ymd_opt(year, month, 1) {
+        Some(date) => date,
+        None => panic!("invalid table date"),
+    }
+}
+
+pub static INSS_TABLES: &[InssTable] = &[
+    InssTable {
+        valid_from: date(2023, 5),
+        brackets: &[
+            InssBracket {
+                up_to: dec!(1320.00),
+                rate: dec!(0.075),
+            },
+            InssBracket {
+                up_to: dec!(2571.29),
+                rate: dec!(0.09),
+            },
+            InssBracket {
+                up_to: dec!(3856.94),
+                rate: dec!(0.12),
+            },
+            InssBracket {
+                up_to: dec!(7507.49),
+                rate: dec!(0.14),
+            },
+        ],
+    },
+    InssTable {
+        valid_from: date(2024, 1),
+        brackets: &[
+            InssBracket {
+                up_to: dec!(1412.00),
+                rate: dec!(0.075),
+            },
+            InssBracket {
+                up_to: dec!(2666.68),
+                rate: dec!(0.09),
+            },
+            InssBracket {
+                up_to: dec!(4000.03),
+                rate: dec!(0.12),
+            },
+            InssBracket {
+                up_to: dec!(7786.02),
+                rate: dec!(0.14),
+            },
+        ],
+    },
+    InssTable {
+        valid_from: date(2025, 1),
+        brackets: &[
+            InssBracket {
+                up_to: dec!(1518.00),
+                rate: dec!(0.075),
+            },
+            InssBracket {
+                up_to: dec!(2793.88),
+                rate: dec!(0.09),
+            },
+            InssBracket {
+                up_to: dec!(4190.83),
+                rate: dec!(0.12),
+            },
+            InssBracket {
+                up_to: dec!(8157.41),
+                rate: dec!(0.14),
+            },
+        ],
+    },
+    InssTable {
+        valid_from: date(2026, 1),
+        brackets: &[
+            InssBracket {
+                up_to: dec!(1621.00),
+                rate: dec!(0.075),
+            },
+            InssBracket {
+                up_to: dec!(2902.84),
+                rate: dec!(0.09),
+            },
+            InssBracket {
+                up_to: dec!(4354.27),
+                rate: dec!(0.12),
+            },
+            InssBracket {
+                up_to: dec!(8475.55),
+                rate: dec!(0.14),
+            },
+        ],
+    },
+];
+
+const IRRF_BRACKETS_2023: &[IrrfBracket] = &[
+    IrrfBracket {
+        up_to: dec!(2112.00),
+        rate: dec!(0),
+        deduction: dec!(0),
+    },
+    IrrfBracket {
+        up_to: dec!(2826.65),
+        rate: dec!(0.075),
+        deduction: dec!(158.40),
+    },
+    IrrfBracket {
+        up_to: dec!(3751.05),
+        rate: dec!(0.15),
+        deduction: dec!(370.40),
+    },
+    IrrfBracket {
+        up_to: dec!(4664.68),
+        rate: dec!(0.225),
+        deduction: dec!(651.73),
+    },
+    IrrfBracket {
+        up_to: Decimal::MAX,
+        rate: dec!(0.275),
+        deduction: dec!(884.96),
+    },
+];
+
+const IRRF_BRACKETS_2024: &[IrrfBracket] = &[
+    IrrfBracket {
+        up_to: dec!(2259.20),
+        rate: dec!(0),
+        deduction: dec!(0),
+    },
+    IrrfBracket {
+        up_to: dec!(2826.65),
+        rate: dec!(0.075),
+        deduction: dec!(169.44),
+    },
+    IrrfBracket {
+        up_to: dec!(3751.05),
+        rate: dec!(0.15),
+        deduction: dec!(381.44),
+    },
+    IrrfBracket {
+        up_to: dec!(4664.68),
+        rate: dec!(0.225),
+        deduction: dec!(662.77),
+    },
+    IrrfBracket {
+        up_to: Decimal::MAX,
+        rate: dec!(0.275),
+        deduction: dec!(896.00),
+    },
+];
+
+const IRRF_BRACKETS_2025: &[IrrfBracket] = &[
+    IrrfBracket {
+        up_to: dec!(2428.80),
+        rate: dec!(0),
+        deduction: dec!(0),
+    },
+    IrrfBracket {
+        up_to: dec!(2826.65),
+        rate: dec!(0.075),
+        deduction: dec!(182.16),
+    },
+    IrrfBracket {
+        up_to: dec!(3751.05),
+        rate: dec!(0.15),
+        deduction: dec!(394.16),
+    },
+    IrrfBracket {
+        up_to: dec!(4664.68),
+        rate: dec!(0.225),
+        deduction: dec!(675.49),
+    },
+    IrrfBracket {
+        up_to: Decimal::MAX,
+        rate: dec!(0.275),
+        deduction: dec!(908.73),
+    },
+];
+
+const DEPENDENT_DEDUCTION: Decimal = dec!(189.59);
+
+pub static IRRF_TABLES: &[IrrfTable] = &[
+    IrrfTable {
+        valid_from: date(2023, 5),
+        brackets: IRRF_BRACKETS_2023,
+        dependent_deduction: DEPENDENT_DEDUCTION,
+        simplified_discount: dec!(528.00),
+        reduction: None,
+    },
+    IrrfTable {
+        valid_from: date(2024, 2),
+        brackets: IRRF_BRACKETS_2024,
+        dependent_deduction: DEPENDENT_DEDUCTION,
+        simplified_discount: dec!(564.80),
+        reduction: None,
+    },
+    IrrfTable {
+        valid_from: date(2025, 5),
+        brackets: IRRF_BRACKETS_2025,
+        dependent_deduction: DEPENDENT_DEDUCTION,
+        simplified_discount: dec!(607.20),
+        reduction: None,
+    },
+    // Same brackets as 2025, plus the exemption up to R$ 5.000,00.
+    IrrfTable {
+        valid_from: date(2026, 1),
+        brackets: IRRF_BRACKETS_2025,
+        dependent_deduction: DEPENDENT_DEDUCTION,
+        simplified_discount: dec!(607.20),
+        reduction: Some(IrrfReduction {
+            exempt_up_to: dec!(5000.00),
+            partial_up_to: dec!(7350.00),
+            constant: dec!(978.62),
+            factor: dec!(0.133145),
+        }),
+    },
+];
+
+/// Annual table for profit sharing (PLR), taxed apart from the monthly income.
+pub struct PlrTable {
+    pub valid_from: NaiveDate,
+    pub brackets: &'static [IrrfBracket],
+}
+
+pub static PLR_TABLES: &[PlrTable] = &[
+    PlrTable {
+        valid_from: date(2023, 5),
+        brackets: &[
+            IrrfBracket {
+                up_to: dec!(6677.55),
+                rate: dec!(0),
+                deduction: dec!(0),
+            },
+            IrrfBracket {
+                up_to: dec!(9922.28),
+                rate: dec!(0.075),
+                deduction: dec!(500.82),
+            },
+            IrrfBracket {
+                up_to: dec!(13167.00),
+                rate: dec!(0.15),
+                deduction: dec!(1244.99),
+            },
+            IrrfBracket {
+                up_to: dec!(16380.38),
+                rate: dec!(0.225),
+                deduction: dec!(2232.51),
+            },
+            IrrfBracket {
+                up_to: Decimal::MAX,
+                rate: dec!(0.275),
+                deduction: dec!(3051.53),
+            },
+        ],
+    },
+    PlrTable {
+        valid_from: date(2024, 2),
+        brackets: &[
+            IrrfBracket {
+                up_to: dec!(7407.11),
+                rate: dec!(0),
+                deduction: dec!(0),
+            },
+            IrrfBracket {
+                up_to: dec!(9922.28),
+                rate: dec!(0.075),
+                deduction: dec!(555.53),
+            },
+            IrrfBracket {
+                up_to: dec!(13167.00),
+                rate: dec!(0.15),
+                deduction: dec!(1299.70),
+            },
+            IrrfBracket {
+                up_to: dec!(16380.38),
+                rate: dec!(0.225),
+                deduction: dec!(2287.23),
+            },
+            IrrfBracket {
+                up_to: Decimal::MAX,
+                rate: dec!(0.275),
+                deduction: dec!(3106.25),
+            },
+        ],
+    },
+    PlrTable {
+        valid_from: date(2025, 5),
+        brackets: &[
+            IrrfBracket {
+                up_to: dec!(7640.80),
+                rate: dec!(0),
+                deduction: dec!(0),
+            },
+            IrrfBracket {
+                up_to: dec!(10236.40),
+                rate: dec!(0.075),
+                deduction: dec!(573.06),
+            },
+            IrrfBracket {
+                up_to: dec!(13576.80),
+                rate: dec!(0.15),
+                deduction: dec!(1340.79),
+            },
+            IrrfBracket {
+                up_to: dec!(16891.20),
+                rate: dec!(0.225),
+                deduction: dec!(2359.05),
+            },
+            IrrfBracket {
+                up_to: Decimal::MAX,
+                rate: dec!(0.275),
+                deduction: dec!(3203.61),
+            },
+        ],
+    },
+];
+
+/// Latest table published on or before `date`.
+fn in_force<T>(
+    tables: &'static [T],
+    date: NaiveDate,
+    valid_from: fn(&T) -> NaiveDate,
+) -> Option<&'static T> {
+    tables.iter().rev().find(|table| valid_from(table) <= date)
+}
+
+pub fn inss_table(date: NaiveDate) -> Option<&'static InssTable> {
+    in_force(INSS_TABLES, date, |t| t.valid_from)
+}
+
+pub fn irrf_table(date: NaiveDate) -> Option<&'static IrrfTable> {
+    in_force(IRRF_TABLES, date, |t| t.valid_from)
+}
+
+pub fn plr_table(date: NaiveDate) -> Option<&'static PlrTable> {
+    in_force(PLR_TABLES, date, |t| t.valid_from)
+}
+
+/// Date the tables are picked for: `reference_date`, else the end of `year`, else today.
+pub fn reference_date(
+    reference_date: Option<NaiveDate>,
+    year: Option<i32>,
+) -> AppResult<NaiveDate> {
+    match (reference_date, year) {
+        (Some(date), _) => Ok(date),
+        (None, Some(year)) => NaiveDate::from_ymd_opt(year, 12, 31)
+            .ok_or_else(|| AppError::Validation(format!("Invalid year {year}"))),
+        (None, None) => Ok(Utc::now().date_naive()),
+    }
+}
+
+/// INSS and IRRF tables in force on `date`.
+pub fn payroll_tables(date: NaiveDate) -> AppResult<(&'static InssTable, &'static IrrfTable)> {
+    inss_table(date)
+        .zip(irrf_table(date))
+        .ok_or_else(|| AppError::Validation(format!("No tax tables available for {date}")))
+}
+
+pub fn round_cents(value: Decimal) -> Decimal {
+    value.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+impl InssTable {
+    /// Progressive contribution, capped at the ceiling.
+    pub fn contribution(&self, wage: Decimal) -> Decimal {
+        let mut lower = Decimal::ZERO;
+        let mut total = Decimal::ZERO;
+
+        for bracket in self.brackets {
+            if wage <= lower {
+                break;
+            }
+
+            total += (wage.min(bracket.up_to) - lower) * bracket.rate;
+            lower = bracket.up_to;
+        }
+
+        round_cents(total)
+    }
+}
+
+pub struct IrrfCalculation {
+    pub tax: Decimal,
+    pub base: Decimal,
+    /// Nominal rate of the bracket the base falls in.
+    pub rate: Decimal,
+    pub simplified_discount: bool,
+    pub reduction: Decimal,
+}
+
+impl IrrfTable {
+    /// Tax withheld on `gross` taxable income, given the INSS and any other legal deductions.
+    pub fn withholding(
+        &self,
+        gross: Decimal,
+        inss: Decimal,
+        dependents: u16,
+        other_deductions: Decimal,
+    ) -> IrrfCalculation {
+        let legal = inss + Decimal::from(dependents) * self.dependent_deduction + other_deductions;
+        let simplified_discount = self.simplified_discount > legal;
+
+        self.calculate(
+            gross,
+            legal.max(self.simplified_discount),
+            simplified_discount,
+        )
+    }
+
+    /// Exclusive taxation of the 13th salary, where the simplified discount doesn't apply.
+    pub fn exclusive_withholding(
+        &self,
+        gross: Decimal,
+        inss: Decimal,
+        dependents: u16,
+    ) -> IrrfCalculation {
+        let legal = inss + Decimal::from(dependents) * self.dependent_deduction;
+
+        self.calculate(gross, legal, false)
+    }
+
+    fn calculate(
+        &self,
+        gross: Decimal,
+        deductions: Decimal,
+        simplified_discount: bool,
+    ) -> IrrfCalculation {
+        let base = (gross - deductions).max(Decimal::ZERO);
+        let bracket = bracket(self.brackets, base);
+        let tax = (base * bracket.rate - bracket.deduction).max(Decimal::ZERO);
+
+        let reduction = self
+            .reduction
+            .as_ref()
+            .map(|r| r.amount(gross, tax))
+            .unwrap_or_default();
+
+        IrrfCalculation {
+            tax: round_cents(tax - reduction),
+            base: round_cents(base),
+            rate: bracket.rate,
+            simplified_discount,
+            reduction: round_cents(reduction),
+        }
+    }
+}
+
+impl PlrTable {
+    /// PLR is taxed on its own, with no deductions.
+    pub fn tax(&self, amount: Decimal) -> Decimal {
+        let bracket = bracket(self.brackets, amount);
+
+        round_cents((amount * bracket.rate - bracket.deduction).max(Decimal::ZERO))
+    }
+}
+
+fn bracket(brackets: &[IrrfBracket], base: Decimal) -> &IrrfBracket {
+    brackets
+        .iter()
+        .find(|b| base <= b.up_to)
+        .unwrap_or(&brackets[brackets.len() - 1])
+}
+
+impl IrrfReduction {
+    fn amount(&self, gross: Decimal, tax: Decimal) -> Decimal {
+        let reduction = if gross <= self.exempt_up_to {
+            tax
+        } else if gross <= self.partial_up_to {
+            self.constant - self.factor * gross
+        } else {
+            Decimal::ZERO
+        };
+
+        reduction.clamp(Decimal::ZERO, tax)
+    }
+}
@@ -1,11 +1,19 @@
-use axum::{Json, extract::Query};
+use axum::extract::Query;
+use chrono::NaiveDate;
+use lib::{AppResult, Json, Response};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
+use super::tax_tables::{self, round_cents};
+
 #[derive(serde::Deserialize)]
 pub struct Params {
     wage: Decimal,
-    dependents: Option<Decimal>,
+    dependents: Option<u16>,
+    /// Uses the tables in force at the end of the year.
+    year: Option<i32>,
+    /// Takes precedence over `year`, defaults to today.
+    reference_date: Option<NaiveDate>,
 }
 
 #[derive(serde::Serialize)]
@@ -14,167 +22,143 @@ pub struct WageDeductionsModel {
     inss_percent: Decimal,
     irrf: Decimal,
     irrf_percent: Decimal,
+    irrf_base: Decimal,
+    irrf_reduction: Decimal,
+    simplified_discount: bool,
     total_deductions: Decimal,
     gross: Decimal,
     net: Decimal,
     aliquot_irrf: Decimal,
+    reference_date: NaiveDate,
 }
 
-pub async fn handler(params: Query<Params>) -> Json<WageDeductionsModel> {
-    let deductions = get_deductions(params.wage, params.dependents.unwrap_or_default());
+pub async fn handler(Query(params): Query<Params>) -> Response<WageDeductionsModel> {
+    let date = tax_tables::reference_date(params.reference_date, params.year)?;
+
+    let deductions = get_deductions(params.wage, params.dependents.unwrap_or_default(), date)?;
 
-    Json(deductions)
+    Ok(Json(deductions))
 }
 
-fn get_deductions(gross_wage: Decimal, dependents: Decimal) -> WageDeductionsModel {
-    let ranges = get_irrf_ranges();
-    let dependent_discount = dec!(189.59);
-
-    let a = get_inss(gross_wage);
-
-    let r = a.inss;
-    let n = a.percent;
-    let o = ranges
-        .iter()
-        .find(|x| x.min < gross_wage - r && (x.max > gross_wage - r))
-        .expect("At least one value in range");
-
-    let c = gross_wage - r - dependents * dependent_discount;
-    let l = o.percent / dec!(100);
-    let i = o.parcel_tax;
-    let u = Decimal::max(c * l - i, dec!(0));
-    let s = r + u;
-    let d = (u / c) * dec!(100);
-
-    WageDeductionsModel {
-        inss: r,
-        inss_percent: n,
-        irrf: u,
-        irrf_percent: l,
-        total_deductions: s,
+fn get_deductions(
+    gross_wage: Decimal,
+    dependents: u16,
+    date: NaiveDate,
+) -> AppResult<WageDeductionsModel> {
+    let (inss_table, irrf_table) = tax_tables::payroll_tables(date)?;
+
+    let inss = inss_table.contribution(gross_wage);
+    let irrf = irrf_table.withholding(gross_wage, inss, dependents, Decimal::ZERO);
+
+    let total_deductions = inss + irrf.tax;
+
+    let inss_percent = if gross_wage.is_zero() {
+        Decimal::ZERO
+    } else {
+        inss / gross_wage
+    };
+
+    let aliquot_irrf = if irrf.base.is_zero() {
+        Decimal::ZERO
+    } else {
+        round_cents(irrf.tax / irrf.base * dec!(100))
+    };
+
+    Ok(WageDeductionsModel {
+        inss,
+        inss_percent,
+        irrf: irrf.tax,
+        irrf_percent: irrf.rate,
+        irrf_base: irrf.base,
+        irrf_reduction: irrf.reduction,
+        simplified_discount: irrf.simplified_discount,
+        total_deductions,
         gross: gross_wage,
-        net: gross_wage - s,
-        aliquot_irrf: d,
-    }
+        net: gross_wage - total_deductions,
+        aliquot_irrf,
+        reference_date: date,
+    })
 }
 
-fn get_inss(gross_wage: Decimal) -> InssDeduction {
-    let ranges = get_inss_ranges();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let hundred = dec!(100);
+    fn deductions(wage: Decimal, dependents: u16, date: (i32, u32, u32)) -> WageDeductionsModel {
+        let date = NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap();
 
-    let a = ranges[0].max * (ranges[0].percent / hundred);
-    let r = (ranges[1].max - ranges[0].max) * (ranges[1].percent / hundred);
-    let n = (ranges[2].max - ranges[1].max) * (ranges[2].percent / hundred);
+        get_deductions(wage, dependents, date).unwrap()
+    }
 
-    let (range, inss) = ranges
-        .iter()
-        .enumerate()
-        .find(|(_, x)| x.min <= gross_wage && gross_wage <= x.max)
-        .expect("To find at least one value in range");
+    #[test]
+    fn payslip_2023() {
+        let result = deductions(dec!(3000), 0, (2023, 12, 31));
 
-    let inss_deduction = dec!(0.14) * ranges[3].max - dec!(174.08);
+        assert_eq!(result.inss, dec!(263.06));
+        assert_eq!(result.irrf, dec!(27.00));
+        assert!(result.simplified_discount);
+        assert_eq!(result.net, dec!(2709.94));
+    }
 
-    let o = match range {
-        1 => (inss.percent / hundred) * gross_wage,
-        2 => a + (gross_wage - ranges[0].max) * (ranges[1].percent / hundred),
-        3 => a + r + (gross_wage - ranges[1].max) * (ranges[2].percent / hundred),
-        4 => a + r + n + (gross_wage - ranges[2].max) * (ranges[3].percent / hundred),
-        5 => inss_deduction,
-        _ => 0.into(),
-    };
+    #[test]
+    fn payslip_2024() {
+        let result = deductions(dec!(3000), 0, (2024, 12, 31));
 
-    let c = if o != inss_deduction {
-        o / gross_wage
-    } else {
-        0.into()
-    };
+        assert_eq!(result.inss, dec!(258.82));
+        assert_eq!(result.irrf, dec!(13.20));
+    }
 
-    InssDeduction {
-        inss: o,
-        percent: c,
+    #[test]
+    fn payslip_2025_with_dependents() {
+        let result = deductions(dec!(5000), 0, (2025, 6, 1));
+
+        assert_eq!(result.inss, dec!(509.60));
+        assert_eq!(result.irrf, dec!(312.89));
+        assert!(result.simplified_discount);
+
+        let result = deductions(dec!(5000), 2, (2025, 6, 1));
+
+        assert_eq!(result.irrf, dec!(249.53));
+        assert!(!result.simplified_discount);
     }
-}
 
-struct InssDeduction {
-    inss: Decimal,
-    percent: Decimal,
-}
+    #[test]
+    fn payslip_2026_exemption() {
+        let result = deductions(dec!(5000), 0, (2026, 1, 1));
 
-struct InssRange {
-    percent: Decimal,
-    min: Decimal,
-    max: Decimal,
-}
+        assert_eq!(result.inss, dec!(501.51));
+        assert_eq!(result.irrf, dec!(0));
+        assert_eq!(result.irrf_reduction, dec!(312.89));
 
-struct IrrfRange {
-    percent: Decimal,
-    min: Decimal,
-    max: Decimal,
-    parcel_tax: Decimal,
-}
+        let result = deductions(dec!(6000), 0, (2026, 1, 1));
 
-fn get_inss_ranges() -> [InssRange; 5] {
-    [
-        InssRange {
-            percent: dec!(7.5),
-            min: 0.into(),
-            max: 1320.into(),
-        },
-        InssRange {
-            percent: 9.into(),
-            min: dec!(1320.01),
-            max: dec!(2571.29),
-        },
-        InssRange {
-            percent: 12.into(),
-            min: dec!(2571.3),
-            max: dec!(3856.94),
-        },
-        InssRange {
-            percent: 14.into(),
-            min: dec!(3856.95),
-            max: dec!(7507.49),
-        },
-        InssRange {
-            percent: 100.into(),
-            min: dec!(7507.5),
-            max: Decimal::MAX,
-        },
-    ]
-}
+        assert_eq!(result.inss, dec!(641.51));
+        assert_eq!(result.irrf_reduction, dec!(179.75));
+        assert_eq!(result.irrf, dec!(385.10));
+    }
+
+    #[test]
+    fn inss_ceiling() {
+        let result = deductions(dec!(20000), 0, (2025, 12, 31));
+        assert_eq!(result.inss, dec!(951.63));
+
+        let result = deductions(dec!(20000), 0, (2026, 12, 31));
+        assert_eq!(result.inss, dec!(988.09));
+    }
+
+    #[test]
+    fn table_by_reference_date() {
+        // The 2025 exemption limit only applies from May onwards.
+        let april = deductions(dec!(3000), 0, (2025, 4, 30));
+        let may = deductions(dec!(3000), 0, (2025, 5, 1));
 
-fn get_irrf_ranges() -> [IrrfRange; 5] {
-    [
-        IrrfRange {
-            percent: 0.into(),
-            parcel_tax: 0.into(),
-            min: 0.into(),
-            max: dec!(2112),
-        },
-        IrrfRange {
-            percent: dec!(7.5),
-            parcel_tax: dec!(158.4),
-            min: dec!(2112.01),
-            max: dec!(2826.65),
-        },
-        IrrfRange {
-            percent: 15.into(),
-            parcel_tax: dec!(370.4),
-            min: dec!(2826.66),
-            max: dec!(3751.05),
-        },
-        IrrfRange {
-            percent: dec!(22.5),
-            parcel_tax: dec!(651.73),
-            min: dec!(3751.06),
-            max: dec!(4664.68),
-        },
-        IrrfRange {
-            percent: dec!(27.5),
-            parcel_tax: dec!(884.96),
-            min: dec!(4664.69),
-            max: Decimal::MAX,
-        },
-    ]
+        assert!(april.irrf > may.irrf);
+    }
+
+    #[test]
+    fn before_first_table() {
+        let date = NaiveDate::from_ymd_opt(2022, 12, 31).unwrap();
+
+        assert!(get_deductions(dec!(3000), 0, date).is_err());
+    }
 }
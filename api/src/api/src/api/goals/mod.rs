@@ -0,0 +1,259 @@
+use axum::{
+    Router,
+    extract::{Query, State},
+    routing,
+};
+use chrono::{Datelike, Months, NaiveDate, NaiveTime, Utc};
+use lib::{
+    ActiveLedger, AppError, AppResult, Json, Response,
+    infra::{DbState, Goal},
+};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use rust_decimal_macros::dec;
+
+use crate::application::ApiState;
+
+use super::{
+    calculations::{
+        Rates,
+        fixed_income::{self, Horizon, MAX_DAYS, Product},
+        savings,
+    },
+    user::auth,
+};
+
+/// Full months of expenses averaged for an emergency fund.
+const EXPENSE_MONTHS: u32 = 6;
+
+pub fn router(state: ApiState, db: DbState) -> Router {
+    let auth = axum::middleware::from_fn_with_state(state, auth);
+
+    Router::new()
+        .route("/plan", routing::get(handler))
+        .route_layer(auth)
+        .with_state(db)
+}
+
+#[derive(serde::Deserialize)]
+struct Params {
+    /// One of the savings calculator products, poupança by default.
+    product: Option<String>,
+    /// Plans as if today were this date, with the rates effective on it.
+    date: Option<NaiveDate>,
+}
+
+/// Amounts in cents, like the goals themselves.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GoalPlan {
+    #[serde(flatten)]
+    goal: Goal,
+    /// The goal target, or the emergency fund worth of expenses.
+    effective_target: i64,
+    /// Average monthly expenses the emergency fund target comes from.
+    average_expenses: Option<i64>,
+    /// Net balance on the deadline, keeping the current monthly contribution.
+    projected: i64,
+    on_track: bool,
+    /// Monthly contribution that reaches the target on the deadline. When the deadline is too
+    /// close for any contribution, the amount missing today.
+    required_contribution: i64,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlanModel {
+    product: String,
+    goals: Vec<GoalPlan>,
+}
+
+async fn handler(
+    State(db): State<DbState>,
+    ledger: ActiveLedger,
+    Query(params): Query<Params>,
+) -> Response<PlanModel> {
+    let date = params.date.unwrap_or_else(|| Utc::now().date_naive());
+    let rates = Rates::load(db.rates.as_ref(), Some(date)).await?;
+
+    let name = params.product.as_deref().unwrap_or("savings");
+    let product = savings::default_products(&rates)
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| AppError::Validation(format!("Unknown savings product {name}")))?;
+
+    let goals = db.goals.list(&ledger.id).await?;
+
+    let average_expenses = if goals.iter().any(|g| g.emergency_months.is_some()) {
+        Some(average_expenses(&db, &ledger.id, date).await?)
+    } else {
+        None
+    };
+
+    let goals = goals
+        .into_iter()
+        .map(|goal| {
+            let expenses = goal.emergency_months.and(average_expenses);
+            plan(goal, expenses, &product, &rates, date)
+        })
+        .collect::<AppResult<_>>()?;
+
+    Ok(Json(PlanModel {
+        product: product.name,
+        goals,
+    }))
+}
+
+/// Average expenses of the full months before `date`, in cents.
+async fn average_expenses(db: &DbState, ledger_id: &str, date: NaiveDate) -> AppResult<i64> {
+    let to = date.with_day(1).expect("first day of the month");
+    let from = to - Months::new(EXPENSE_MONTHS);
+    let at_midnight = |date: NaiveDate| date.and_time(NaiveTime::MIN).and_utc();
+
+    let total = db
+        .transactions
+        .expenses(ledger_id, at_midnight(from), at_midnight(to))
+        .await?;
+
+    Ok(total / i64::from(EXPENSE_MONTHS))
+}
+
+fn plan(
+    goal: Goal,
+    average_expenses: Option<i64>,
+    product: &Product,
+    rates: &Rates,
+    date: NaiveDate,
+) -> AppResult<GoalPlan> {
+    let target = match (goal.emergency_months, average_expenses) {
+        (Some(months), Some(expenses)) => expenses * i64::from(months),
+        _ => goal.target,
+    };
+
+    let cents = |amount: i64| Decimal::new(amount, 2);
+    let days = (goal.deadline - date).num_days().max(0);
+    if days > MAX_DAYS.into() {
+        return Err(AppError::Validation(format!(
+            "The deadline of {} is more than {} years away",
+            goal.name,
+            MAX_DAYS / 365
+        )));
+    }
+
+    let horizon = Horizon {
+        start: date,
+        initial: cents(goal.saved),
+        contribution: Decimal::ZERO,
+        days: days as u32,
+        ipca: None,
+    };
+    // The net balance grows linearly with the monthly contribution.
+    let (without, per_real) = fixed_income::contribution_values(product, rates, &horizon)?;
+    let projected = without + per_real * cents(goal.monthly_contribution);
+
+    let missing = (cents(target) - without).max(Decimal::ZERO);
+    let required = if per_real.is_zero() {
+        missing
+    } else {
+        missing / per_real
+    };
+
+    let to_cents = |amount: Decimal| (amount * dec!(100)).ceil().to_i64().unwrap_or(i64::MAX);
+    let projected = to_cents(projected);
+
+    Ok(GoalPlan {
+        effective_target: target,
+        average_expenses: goal.emergency_months.and(average_expenses),
+        projected,
+        on_track: projected >= target,
+        required_contribution: to_cents(required),
+        goal,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> Rates {
+        Rates {
+            selic: dec!(15),
+            cdi: dec!(14.9),
+            treasury_spread: dec!(0.1736),
+            fgts: dec!(3),
+        }
+    }
+
+    fn goal(target: i64, monthly_contribution: i64) -> Goal {
+        Goal {
+            id: "goal".to_string(),
+            ledger_id: "user-1".to_string(),
+            name: "Trip".to_string(),
+            target,
+            deadline: NaiveDate::from_ymd_opt(2026, 1, 2).unwrap(),
+            card_id: None,
+            saved: 100000,
+            monthly_contribution,
+            emergency_months: None,
+        }
+    }
+
+    fn product() -> Product {
+        savings::default_products(&rates()).remove(0)
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, 2).unwrap()
+    }
+
+    #[test]
+    fn required_contribution_reaches_the_target() {
+        let plan = plan(goal(1000000, 50000), None, &product(), &rates(), date()).unwrap();
+
+        assert!(!plan.on_track);
+        assert!(plan.required_contribution > 50000);
+
+        let contribution = plan.required_contribution;
+        let plan = super::plan(
+            goal(1000000, contribution),
+            None,
+            &product(),
+            &rates(),
+            date(),
+        )
+        .unwrap();
+
+        assert!(plan.on_track);
+        assert!(plan.projected - 1000000 < 100);
+    }
+
+    #[test]
+    fn already_saved_goals_need_nothing() {
+        let plan = plan(goal(100000, 0), None, &product(), &rates(), date()).unwrap();
+
+        assert!(plan.on_track);
+        assert_eq!(plan.required_contribution, 0);
+    }
+
+    #[test]
+    fn deadlines_past_the_horizon_are_rejected() {
+        let retirement = Goal {
+            deadline: NaiveDate::from_ymd_opt(2100, 1, 1).unwrap(),
+            ..goal(1000000, 50000)
+        };
+
+        assert!(plan(retirement, None, &product(), &rates(), date()).is_err());
+    }
+
+    #[test]
+    fn emergency_fund_follows_expenses() {
+        let fund = Goal {
+            emergency_months: Some(6),
+            ..goal(0, 0)
+        };
+
+        let plan = plan(fund, Some(350000), &product(), &rates(), date()).unwrap();
+
+        assert_eq!(plan.effective_target, 2100000);
+        assert_eq!(plan.average_expenses, Some(350000));
+    }
+}
@@ -1,15 +1,20 @@
 mod calculations;
+mod goals;
+mod rates;
 mod user;
 
 use axum::{Router, routing};
+use lib::infra::DbState;
 
 use crate::application::ApiState;
 
 pub use user::auth;
 
-pub fn router(state: ApiState) -> Router {
+pub fn router(state: ApiState, db: DbState) -> Router {
     Router::new()
         .route("/health", routing::get(|| async { "healthy!" }))
-        .nest("/calculations", calculations::router())
+        .nest("/calculations", calculations::router(db.clone()))
+        .nest("/goals", goals::router(state.clone(), db.clone()))
+        .nest("/rates", rates::router(state.clone(), db.rates))
         .nest("/user", user::router(state))
 }
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Query, State},
+    routing,
+};
+use chrono::NaiveDate;
+use lib::{
+    Json, Response,
+    infra::{Rate, RateKind},
+    repository::RateRepository,
+};
+
+use crate::application::ApiState;
+
+use super::{
+    calculations::{Rates, check_rate},
+    user::{admin, auth},
+};
+
+pub fn router(state: ApiState, rates: Arc<dyn RateRepository>) -> Router {
+    let auth = axum::middleware::from_fn_with_state(state.clone(), auth);
+    let admin = axum::middleware::from_fn_with_state(state, admin);
+
+    Router::new()
+        .route("/", routing::put(set))
+        .route_layer(admin)
+        .route_layer(auth)
+        .route("/", routing::get(history))
+        .route("/current", routing::get(current))
+        .with_state(rates)
+}
+
+#[derive(serde::Deserialize)]
+struct HistoryParams {
+    kind: Option<RateKind>,
+}
+
+async fn history(
+    State(rates): State<Arc<dyn RateRepository>>,
+    Query(params): Query<HistoryParams>,
+) -> Response<Vec<Rate>> {
+    Ok(Json(rates.history(params.kind).await?))
+}
+
+#[derive(serde::Deserialize)]
+struct CurrentParams {
+    date: Option<NaiveDate>,
+}
+
+async fn current(
+    State(rates): State<Arc<dyn RateRepository>>,
+    Query(params): Query<CurrentParams>,
+) -> Response<Rates> {
+    Ok(Json(Rates::load(rates.as_ref(), params.date).await?))
+}
+
+async fn set(
+    State(rates): State<Arc<dyn RateRepository>>,
+    Json(rate): Json<Rate>,
+) -> Response<Rate> {
+    // Every simulation reads these, a typo here would break them all.
+    check_rate(rate.value, &format!("{:?}", rate.kind))?;
+    rates.set(&rate).await?;
+
+    Ok(Json(rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::{AppError, repository::MemoryStore};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_rates_out_of_range() {
+        let rates: Arc<dyn RateRepository> = Arc::new(MemoryStore::default());
+        let rate = |value| Rate {
+            kind: RateKind::Selic,
+            value,
+            effective_from: NaiveDate::from_ymd_opt(2030, 1, 2).unwrap(),
+        };
+        let seeded = rates.history(None).await.unwrap().len();
+
+        for value in [dec!(-100), dec!(1500)] {
+            let result = set(State(rates.clone()), Json(rate(value))).await;
+            assert!(matches!(result, Err(AppError::Validation(_))));
+        }
+        assert_eq!(rates.history(None).await.unwrap().len(), seeded);
+
+        assert!(
+            set(State(rates.clone()), Json(rate(dec!(15))))
+                .await
+                .is_ok()
+        );
+        assert_eq!(rates.history(None).await.unwrap().len(), seeded + 1);
+    }
+}
@@ -0,0 +1,472 @@
+//! Email and password logins for self-hosted instances, enabled with `LOCAL_AUTH=true`.
+//!
+//! New accounts must follow the verification link mailed to them before logging in. Both that
+//! link and the password reset one carry a random token that works once, and only its SHA-256
+//! hash is stored.
+
+use std::sync::LazyLock;
+
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::{
+        SaltString,
+        rand_core::{OsRng, RngCore},
+    },
+};
+use axum::{extract::State, http::StatusCode, response::Response};
+use chrono::{Duration, Utc};
+use lib::{
+    AppError, AppResult, Json,
+    infra::{Account, AccountToken, Identity, LOCAL_ISSUER, TokenPurpose, User},
+};
+use sha2::{Digest, Sha256};
+
+use crate::application::{ApiState, mailer::Mail};
+
+use super::auth::{AuthError, ClientDevice, start_session};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+const VERIFY_TTL: Duration = Duration::hours(24);
+const RESET_TTL: Duration = Duration::hours(1);
+
+/// Checked against when the email has no account, so both cases take as long.
+static UNKNOWN_ACCOUNT_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password("no account has this password"));
+
+#[derive(serde::Deserialize)]
+pub struct SignUpParams {
+    email: String,
+    name: String,
+    password: String,
+}
+
+/// Always answers the same, so it can't be used to find out who has an account.
+pub async fn sign_up(
+    State(state): State<ApiState>,
+    Json(params): Json<SignUpParams>,
+) -> AppResult<StatusCode> {
+    let email = normalize_email(&params.email)?;
+    check_password(&params.password)?;
+
+    let password = params.password;
+    let account = Account {
+        email: email.clone(),
+        name: params.name.trim().to_string(),
+        password_hash: blocking(move || hash_password(&password)).await,
+        verified_at: None,
+        created_at: Utc::now(),
+    };
+
+    // Signing up again before verifying replaces the password and sends a new link. Otherwise
+    // whoever signed up first with someone's email would get to choose their password.
+    if state.accounts.create(&account).await? {
+        let token = issue_token(&state, &email, TokenPurpose::Verify, VERIFY_TTL).await?;
+        state.mailer.send(Mail {
+            to: email,
+            subject: "Confirm your email".to_string(),
+            body: format!(
+                "Confirm your email to start using Pocket Planner: {}/account/verify?token={token}",
+                state.public_url
+            ),
+        });
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(serde::Deserialize)]
+pub struct TokenParams {
+    token: String,
+}
+
+pub async fn verify(
+    State(state): State<ApiState>,
+    Json(params): Json<TokenParams>,
+) -> AppResult<StatusCode> {
+    let now = Utc::now();
+    let email = use_token(&state, &params.token, TokenPurpose::Verify).await?;
+
+    state.accounts.verify(&email, now).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(serde::Deserialize)]
+pub struct LoginParams {
+    email: String,
+    password: String,
+}
+
+/// Issues the same session cookies as logging in with an identity provider.
+pub async fn login(
+    State(state): State<ApiState>,
+    ClientDevice(device): ClientDevice,
+    Json(params): Json<LoginParams>,
+) -> Result<Response, AuthError> {
+    let email = params.email.trim().to_lowercase();
+    let account = state.accounts.get(&email).await?;
+
+    let hash = account
+        .as_ref()
+        .map(|a| a.password_hash.clone())
+        .unwrap_or_else(|| UNKNOWN_ACCOUNT_HASH.clone());
+    let password = params.password;
+    let matches = blocking(move || verify_password(&password, &hash)).await;
+
+    let Some(account) = account.filter(|_| matches) else {
+        return Err(AuthError::InvalidCredentials);
+    };
+
+    if account.verified_at.is_none() {
+        return Err(AuthError::EmailNotVerified);
+    }
+
+    let user = local_user(&state, &account).await?;
+
+    start_session(state, device, user).await
+}
+
+#[derive(serde::Deserialize)]
+pub struct ForgotPasswordParams {
+    email: String,
+}
+
+/// Mails a reset link if the email has an account, answering the same either way.
+pub async fn forgot_password(
+    State(state): State<ApiState>,
+    Json(params): Json<ForgotPasswordParams>,
+) -> AppResult<StatusCode> {
+    let email = params.email.trim().to_lowercase();
+
+    if state.accounts.get(&email).await?.is_some() {
+        let token = issue_token(&state, &email, TokenPurpose::Reset, RESET_TTL).await?;
+        state.mailer.send(Mail {
+            to: email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Choose a new password within the next hour: {}/account/reset?token={token}",
+                state.public_url
+            ),
+        });
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetPasswordParams {
+    token: String,
+    password: String,
+}
+
+/// Sets the new password and logs out of every device. Following the link also proves the
+/// email belongs to the user, so it verifies the account too.
+pub async fn reset_password(
+    State(state): State<ApiState>,
+    Json(params): Json<ResetPasswordParams>,
+) -> AppResult<StatusCode> {
+    check_password(&params.password)?;
+
+    let now = Utc::now();
+    let email = use_token(&state, &params.token, TokenPurpose::Reset).await?;
+
+    let password = params.password;
+    let hash = blocking(move || hash_password(&password)).await;
+    state.accounts.set_password(&email, &hash).await?;
+    state.accounts.verify(&email, now).await?;
+
+    if let Some(account) = state.accounts.get(&email).await? {
+        let user = local_user(&state, &account).await?;
+        let revoked = state.sessions.revoke_all(&user.id).await?;
+        state.session_cache.remove_user(&user.id);
+        tracing::info!(
+            user = user.id,
+            revoked,
+            "password reset, revoked every session"
+        );
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The user the account logs in as, the same one as other logins with its verified email.
+async fn local_user(state: &ApiState, account: &Account) -> AppResult<User> {
+    state
+        .users
+        .resolve(&Identity {
+            issuer: LOCAL_ISSUER.to_string(),
+            subject: account.email.clone(),
+            email: account.email.clone(),
+            // Only following the mailed link proves the email, not signing up with it.
+            trusted_email: account.verified_at.is_some(),
+            name: account.name.clone(),
+            picture: String::new(),
+        })
+        .await
+}
+
+fn normalize_email(email: &str) -> AppResult<String> {
+    let email = email.trim().to_lowercase();
+
+    match email.split_once('@') {
+        Some((user, domain)) if !user.is_empty() && domain.contains('.') => Ok(email),
+        _ => Err(AppError::Validation(format!("Invalid email: {email}"))),
+    }
+}
+
+fn check_password(password: &str) -> AppResult<()> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::Validation(format!(
+            "The password needs at least {MIN_PASSWORD_LENGTH} characters"
+        )));
+    }
+
+    Ok(())
+}
+
+async fn issue_token(
+    state: &ApiState,
+    email: &str,
+    purpose: TokenPurpose,
+    ttl: Duration,
+) -> AppResult<String> {
+    let token = random_token();
+
+    state
+        .accounts
+        .create_token(&AccountToken {
+            token_hash: token_hash(&token),
+            email: email.to_string(),
+            purpose,
+            expires_at: Utc::now() + ttl,
+            used_at: None,
+        })
+        .await?;
+
+    Ok(token)
+}
+
+async fn use_token(state: &ApiState, token: &str, purpose: TokenPurpose) -> AppResult<String> {
+    state
+        .accounts
+        .use_token(&token_hash(token), purpose, Utc::now())
+        .await?
+        .ok_or_else(|| AppError::Validation("This link is invalid or expired".to_string()))
+}
+
+/// 32 random bytes, hex encoded.
+pub(super) fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+pub(super) fn token_hash(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("argon2 hashes any password")
+        .to_string()
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Argon2 is slow on purpose, so keep it off the async workers.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f)
+        .await
+        .expect("password hashing task")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{http::header::SET_COOKIE, response::IntoResponse};
+    use lib::infra::{DbState, Device};
+
+    use crate::application::mailer::MemoryMailer;
+
+    use super::*;
+
+    fn state() -> (ApiState, Arc<MemoryMailer>) {
+        let mailer = Arc::new(MemoryMailer::default());
+        let state = ApiState::for_tests(&DbState::in_memory(), mailer.clone());
+        (state, mailer)
+    }
+
+    /// Token from the link in the last email sent.
+    fn mailed_token(mailer: &MemoryMailer) -> String {
+        let body = mailer.sent().last().unwrap().body.clone();
+        body.split("token=").nth(1).unwrap().to_string()
+    }
+
+    async fn login(state: &ApiState, password: &str) -> Response {
+        super::login(
+            State(state.clone()),
+            ClientDevice(Device::default()),
+            Json(LoginParams {
+                email: "Ana@Example.com".to_string(),
+                password: password.to_string(),
+            }),
+        )
+        .await
+        .into_response()
+    }
+
+    async fn active_sessions(state: &ApiState) -> usize {
+        let account = state
+            .accounts
+            .get("ana@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        let user = local_user(state, &account).await.unwrap();
+
+        state.sessions.list(&user.id).await.unwrap().len()
+    }
+
+    async fn sign_up(state: &ApiState) -> StatusCode {
+        sign_up_with(state, "correct horse").await
+    }
+
+    async fn sign_up_with(state: &ApiState, password: &str) -> StatusCode {
+        super::sign_up(
+            State(state.clone()),
+            Json(SignUpParams {
+                email: " ana@example.com".to_string(),
+                name: "Ana".to_string(),
+                password: password.to_string(),
+            }),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn signs_up_and_logs_in_after_verifying() {
+        let (state, mailer) = state();
+
+        assert_eq!(sign_up(&state).await, StatusCode::ACCEPTED);
+        assert_eq!(mailer.sent()[0].to, "ana@example.com");
+
+        let response = login(&state, "correct horse").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let token = mailed_token(&mailer);
+        let params = || {
+            Json(TokenParams {
+                token: token.clone(),
+            })
+        };
+        verify(State(state.clone()), params()).await.unwrap();
+        assert!(verify(State(state.clone()), params()).await.is_err());
+
+        assert_eq!(
+            login(&state, "wrong horse").await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let response = login(&state, "correct horse").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get_all(SET_COOKIE).iter().count(), 2);
+        assert_eq!(active_sessions(&state).await, 1);
+    }
+
+    #[tokio::test]
+    async fn signing_up_again_replaces_an_unverified_password() {
+        let (state, mailer) = state();
+
+        // Someone else signs up first with Ana's email, then Ana does.
+        sign_up_with(&state, "squatter password").await;
+        let squatter_token = mailed_token(&mailer);
+        sign_up(&state).await;
+        assert_eq!(mailer.sent().len(), 2);
+
+        let verify_with = |token: String| verify(State(state.clone()), Json(TokenParams { token }));
+        assert!(verify_with(squatter_token).await.is_err());
+        verify_with(mailed_token(&mailer)).await.unwrap();
+
+        assert_eq!(
+            login(&state, "squatter password").await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            login(&state, "correct horse").await.status(),
+            StatusCode::OK
+        );
+
+        // Once verified, signing up again changes nothing.
+        sign_up_with(&state, "squatter password").await;
+        assert_eq!(mailer.sent().len(), 2);
+        assert_eq!(
+            login(&state, "correct horse").await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn resetting_the_password_logs_out_everywhere() {
+        let (state, mailer) = state();
+
+        sign_up(&state).await;
+        let token = mailed_token(&mailer);
+        verify(State(state.clone()), Json(TokenParams { token }))
+            .await
+            .unwrap();
+        login(&state, "correct horse").await;
+
+        let forgot = |email: &str| {
+            forgot_password(
+                State(state.clone()),
+                Json(ForgotPasswordParams {
+                    email: email.to_string(),
+                }),
+            )
+        };
+        assert_eq!(
+            forgot("nobody@example.com").await.unwrap(),
+            StatusCode::ACCEPTED
+        );
+        assert_eq!(mailer.sent().len(), 1);
+        forgot("ana@example.com").await.unwrap();
+
+        let reset = |password: &str| ResetPasswordParams {
+            token: mailed_token(&mailer),
+            password: password.to_string(),
+        };
+        assert!(
+            reset_password(State(state.clone()), Json(reset("short")))
+                .await
+                .is_err()
+        );
+        reset_password(State(state.clone()), Json(reset("battery staple")))
+            .await
+            .unwrap();
+
+        assert_eq!(active_sessions(&state).await, 0);
+        assert_eq!(
+            login(&state, "correct horse").await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            login(&state, "battery staple").await.status(),
+            StatusCode::OK
+        );
+    }
+}
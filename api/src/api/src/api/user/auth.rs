@@ -1,69 +1,211 @@
 use axum::{
-    extract::{Request, State},
+    Extension,
+    extract::{FromRequestParts, OriginalUri, Request, State},
     http::{
-        StatusCode,
-        header::{COOKIE, HeaderValue, SET_COOKIE},
+        Extensions, HeaderMap, StatusCode,
+        header::{AUTHORIZATION, COOKIE, HeaderValue, SET_COOKIE, USER_AGENT},
+        request::Parts,
     },
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use chrono::{Duration, Utc};
 use jsonwebtoken::{self as jwt};
-use lib::{Json, infra::UserClaims};
-use openidconnect::{Nonce, core::CoreIdToken};
+use lib::{
+    AppError, Json,
+    infra::{Device, Session, User, UserClaims},
+};
 use serde_json::json;
+use uuid::Uuid;
+
+use crate::application::{ApiState, client_ip::client_ip, identity::IdentityError};
 
-use crate::application::ApiState;
+use super::tokens;
 
 const ACCESS_COOKIE: &str = "access_token";
 const REFRESH_COOKIE: &str = "refresh_token";
 const ACCESS_COOKIE_PATH: &str = "/api";
 const REFRESH_COOKIE_PATH: &str = "/api/user/session/refresh";
 
+/// Session the request was authenticated with, set by [`auth`].
+#[derive(Clone)]
+pub struct SessionId(pub String);
+
 pub async fn auth(
     State(state): State<ApiState>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, AuthError> {
+    if let Some(token) = bearer_token(&req) {
+        // Nested routers only see the rest of the path.
+        let path = req
+            .extensions()
+            .get::<OriginalUri>()
+            .map_or_else(|| req.uri().path(), |OriginalUri(uri)| uri.path());
+        let claims = tokens::authenticate(&state, token, req.method(), path).await?;
+
+        req.extensions_mut().insert(claims);
+        return Ok(next.run(req).await);
+    }
+
     let token = extract_cookie(&req, ACCESS_COOKIE)?;
     let claims = validate_local_token(&state, token, TokenKind::Access)?;
+
+    if !state.session_cache.is_fresh(&claims.sid) {
+        let now = Utc::now();
+        let session = state.sessions.get(&claims.sid).await?;
+
+        // Tokens issued before user ids named the user by email, refreshing replaces them.
+        if !session.is_some_and(|s| s.is_active(now) && s.user_id == claims.sub) {
+            return Err(AuthError::SessionRevoked);
+        }
+
+        let device = device(&state, req.headers(), req.extensions());
+        state.sessions.seen(&claims.sid, now, &device).await?;
+        state.session_cache.insert(&claims.sid, &claims.sub);
+    }
+
     req.extensions_mut().insert(claims.to_user_claims());
+    req.extensions_mut().insert(SessionId(claims.sid));
+    Ok(next.run(req).await)
+}
+
+/// Runs after [`auth`], only letting through the users listed in `ADMIN_USER_IDS`.
+pub async fn admin(
+    State(state): State<ApiState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AuthError> {
+    let is_admin = req
+        .extensions()
+        .get::<UserClaims>()
+        .is_some_and(|claims| state.admin_ids.contains(&claims.id));
+
+    if !is_admin {
+        return Err(AuthError::NotAdmin);
+    }
+
     Ok(next.run(req).await)
 }
 
 #[derive(serde::Deserialize)]
 pub struct SessionParams {
+    /// ID token issued by one of the identity providers.
     token: String,
+    /// Provider that issued the token. When missing, each configured provider is tried.
+    provider: Option<String>,
 }
 
 pub async fn login(
     State(state): State<ApiState>,
+    ClientDevice(device): ClientDevice,
     Json(params): Json<SessionParams>,
 ) -> Result<Response, AuthError> {
-    let claims = validate_google_token(&state, &params.token).await?;
+    let identity = state
+        .identity_providers
+        .verify(params.provider.as_deref(), &params.token)?;
+    let user = state.users.resolve(&identity).await?;
 
-    let access_token = encode_local_token(&state, &claims, TokenKind::Access)?;
-    let refresh_token = encode_local_token(&state, &claims, TokenKind::Refresh)?;
+    start_session(state, device, user).await
+}
+
+/// Creates a session for a user who just proved who they are, and sets its cookies.
+pub(super) async fn start_session(
+    state: ApiState,
+    device: Device,
+    user: User,
+) -> Result<Response, AuthError> {
+    let claims = user_claims(user);
+    let now = Utc::now();
+    let session = Session {
+        id: Uuid::now_v7().to_string(),
+        user_id: claims.id.clone(),
+        refresh_jti: Uuid::now_v7().to_string(),
+        created_at: now,
+        expires_at: now + refresh_ttl(&state),
+        revoked_at: None,
+        device,
+        last_seen_at: now,
+    };
+    state.sessions.create(&session).await?;
 
-    build_auth_response(state, claims, access_token, refresh_token)
+    build_auth_response(state, claims, &session.id, &session.refresh_jti)
 }
 
+/// Exchanges the refresh token for a new pair. Each refresh token works once: presenting one
+/// that was already exchanged means it leaked, so the whole session is revoked.
 pub async fn refresh(State(state): State<ApiState>, req: Request) -> Result<Response, AuthError> {
+    let device = device(&state, req.headers(), req.extensions());
     let refresh_token = extract_cookie(&req, REFRESH_COOKIE)?;
     let claims = validate_local_token(&state, refresh_token, TokenKind::Refresh)?;
+    let jti = claims
+        .jti
+        .as_deref()
+        .ok_or(AuthError::MissingClaim("jti"))?;
+
+    let now = Utc::now();
+    let next_jti = Uuid::now_v7().to_string();
+    let rotated = state
+        .sessions
+        .rotate(&claims.sid, jti, &next_jti, now + refresh_ttl(&state))
+        .await?;
+
+    if !rotated {
+        let session = state.sessions.get(&claims.sid).await?;
+
+        if session.is_some_and(|s| s.is_active(now)) {
+            tracing::warn!(
+                session = claims.sid,
+                user = claims.sub,
+                "refresh token reused, revoking the session"
+            );
+            state.sessions.revoke(&claims.sub, &claims.sid).await?;
+            state.session_cache.remove(&claims.sid);
+
+            return Err(AuthError::TokenReused);
+        }
+
+        return Err(AuthError::SessionRevoked);
+    }
 
-    let user_claims = claims.to_user_claims();
-    let access_token = encode_local_token(&state, &user_claims, TokenKind::Access)?;
-    let next_refresh_token = encode_local_token(&state, &user_claims, TokenKind::Refresh)?;
+    state.sessions.seen(&claims.sid, now, &device).await?;
+
+    // Picks up profile changes, and the user id for tokens issued before there was one.
+    let user_id = match state.sessions.get(&claims.sid).await? {
+        Some(session) => session.user_id,
+        None => return Err(AuthError::SessionRevoked),
+    };
+    let user = state
+        .users
+        .get(&user_id)
+        .await?
+        .ok_or(AuthError::SessionRevoked)?;
 
-    build_auth_response(state, user_claims, access_token, next_refresh_token)
+    build_auth_response(state, user_claims(user), &claims.sid, &next_jti)
+}
+
+pub(super) fn user_claims(user: User) -> UserClaims {
+    UserClaims {
+        id: user.id,
+        email: user.email,
+        name: user.name,
+        picture: user.picture,
+    }
+}
+
+fn refresh_ttl(state: &ApiState) -> Duration {
+    Duration::seconds(state.refresh_ttl as i64)
 }
 
 fn build_auth_response(
     state: ApiState,
     user_claims: UserClaims,
-    access_token: String,
-    refresh_token: String,
+    session_id: &str,
+    refresh_jti: &str,
 ) -> Result<Response, AuthError> {
+    let access_token = encode_local_token(&state, &user_claims, session_id, None)?;
+    let refresh_token = encode_local_token(&state, &user_claims, session_id, Some(refresh_jti))?;
+
     let mut response = Json(user_claims).into_response();
 
     append_set_cookie(
@@ -91,43 +233,57 @@ fn build_auth_response(
     Ok(response)
 }
 
-pub async fn logout(State(state): State<ApiState>) -> Result<Response, AuthError> {
+/// Revokes the current session. The refresh cookie isn't sent to this path, but the access token
+/// names the session too, and is accepted here even after it expired.
+pub async fn logout(State(state): State<ApiState>, req: Request) -> Result<Response, AuthError> {
+    let claims = extract_cookie(&req, ACCESS_COOKIE)
+        .and_then(|token| decode_local_token(&state, token, TokenKind::Access, false));
+
+    if let Ok(claims) = claims {
+        state.sessions.revoke(&claims.sub, &claims.sid).await?;
+        state.session_cache.remove(&claims.sid);
+    }
+
+    cleared_cookies(&state)
+}
+
+/// Logs out of every device.
+pub async fn logout_all(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<UserClaims>,
+) -> Result<Response, AuthError> {
+    let revoked = state.sessions.revoke_all(&claims.id).await?;
+    state.session_cache.remove_user(&claims.id);
+    tracing::info!(user = claims.id, revoked, "revoked every session");
+
+    cleared_cookies(&state)
+}
+
+fn cleared_cookies(state: &ApiState) -> Result<Response, AuthError> {
     let mut response = StatusCode::NO_CONTENT.into_response();
     append_set_cookie(
         &mut response,
-        clear_cookie(ACCESS_COOKIE, ACCESS_COOKIE_PATH, &state),
+        clear_cookie(ACCESS_COOKIE, ACCESS_COOKIE_PATH, state),
     )?;
     append_set_cookie(
         &mut response,
-        clear_cookie(REFRESH_COOKIE, REFRESH_COOKIE_PATH, &state),
+        clear_cookie(REFRESH_COOKIE, REFRESH_COOKIE_PATH, state),
     )?;
 
     Ok(response)
 }
 
-async fn validate_google_token(state: &ApiState, token: &str) -> Result<UserClaims, AuthError> {
-    let id_token: CoreIdToken = serde_json::from_value(serde_json::Value::String(token.into()))
-        .map_err(|err| AuthError::OpenId(err.to_string()))?;
-
-    let verifier = state.google_client.id_token_verifier();
-
-    let claims = id_token
-        .claims(&verifier, |_: Option<&Nonce>| Ok(()))
-        .map_err(|err| AuthError::OpenId(err.to_string()))?;
-
-    let raw_claims: GoogleProfileClaims = serde_json::from_value(
-        serde_json::to_value(claims).map_err(|err| AuthError::OpenId(err.to_string()))?,
-    )
-    .map_err(|err| AuthError::OpenId(err.to_string()))?;
-
-    raw_claims.try_into()
-}
-
+/// Refresh tokens carry a `jti`, access tokens don't.
 fn encode_local_token(
     state: &ApiState,
     claims: &UserClaims,
-    token_kind: TokenKind,
+    session_id: &str,
+    refresh_jti: Option<&str>,
 ) -> Result<String, AuthError> {
+    let token_kind = match refresh_jti {
+        Some(_) => TokenKind::Refresh,
+        None => TokenKind::Access,
+    };
     let now = chrono::Utc::now().timestamp() as u64;
     let ttl = match token_kind {
         TokenKind::Access => state.access_ttl,
@@ -135,11 +291,13 @@ fn encode_local_token(
     };
 
     let token_claims = AppTokenClaims {
-        sub: claims.email.clone(),
+        sub: claims.id.clone(),
         email: claims.email.clone(),
         name: claims.name.clone(),
         picture: claims.picture.clone(),
         token_type: token_kind,
+        sid: session_id.to_string(),
+        jti: refresh_jti.map(str::to_string),
         iss: state.jwt_issuer.clone(),
         aud: state.jwt_audience.clone(),
         iat: now,
@@ -162,8 +320,18 @@ fn validate_local_token(
     state: &ApiState,
     token: &str,
     expected: TokenKind,
+) -> Result<AppTokenClaims, AuthError> {
+    decode_local_token(state, token, expected, true)
+}
+
+fn decode_local_token(
+    state: &ApiState,
+    token: &str,
+    expected: TokenKind,
+    validate_exp: bool,
 ) -> Result<AppTokenClaims, AuthError> {
     let mut validation = jwt::Validation::new(jwt::Algorithm::HS256);
+    validation.validate_exp = validate_exp;
     validation.set_issuer(&[&state.jwt_issuer]);
     validation.set_audience(&[&state.jwt_audience]);
 
@@ -185,6 +353,32 @@ fn validate_local_token(
     Ok(token_data.claims)
 }
 
+/// Client the request came from, see [`client_ip`] for its address.
+pub struct ClientDevice(pub Device);
+
+impl FromRequestParts<ApiState> for ClientDevice {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &ApiState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(device(state, &parts.headers, &parts.extensions)))
+    }
+}
+
+fn device(state: &ApiState, headers: &HeaderMap, extensions: &Extensions) -> Device {
+    let ip = client_ip(headers, extensions.get(), state.trust_proxy);
+
+    Device {
+        user_agent: headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        ip: ip.map(|ip| ip.to_string()),
+    }
+}
+
 fn extract_cookie<'a>(req: &'a Request, name: &str) -> Result<&'a str, AuthError> {
     let cookies = req
         .headers()
@@ -203,6 +397,15 @@ fn extract_cookie<'a>(req: &'a Request, name: &str) -> Result<&'a str, AuthError
         .ok_or(AuthError::TokenNotPresent)
 }
 
+/// Personal access token from the `Authorization` header, see [`tokens`].
+fn bearer_token(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
 fn build_cookie(name: &str, token: &str, path: &str, state: &ApiState, max_age: u64) -> String {
     let mut cookie = format!("{name}={token}; HttpOnly; Path={path}; SameSite=Lax");
 
@@ -243,6 +446,10 @@ struct AppTokenClaims {
     name: String,
     picture: String,
     token_type: TokenKind,
+    /// Session the token belongs to.
+    sid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
     iss: String,
     aud: String,
     iat: u64,
@@ -252,6 +459,7 @@ struct AppTokenClaims {
 impl AppTokenClaims {
     fn to_user_claims(&self) -> UserClaims {
         UserClaims {
+            id: self.sub.clone(),
             email: self.email.clone(),
             name: self.name.clone(),
             picture: self.picture.clone(),
@@ -259,39 +467,36 @@ impl AppTokenClaims {
     }
 }
 
-#[derive(serde::Deserialize)]
-struct GoogleProfileClaims {
-    email: Option<String>,
-    name: Option<String>,
-    picture: Option<String>,
-}
-
-impl TryFrom<GoogleProfileClaims> for UserClaims {
-    type Error = AuthError;
-
-    fn try_from(value: GoogleProfileClaims) -> Result<Self, Self::Error> {
-        Ok(Self {
-            email: value.email.ok_or(AuthError::MissingClaim("email"))?,
-            name: value.name.ok_or(AuthError::MissingClaim("name"))?,
-            picture: value.picture.ok_or(AuthError::MissingClaim("picture"))?,
-        })
-    }
-}
-
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
     #[error("Auth token not found on the request")]
     TokenNotPresent,
     #[error("Missing claim on id_token: {0}")]
     MissingClaim(&'static str),
-    #[error("OpenID validation failed: {0}")]
-    OpenId(String),
+    #[error(transparent)]
+    Identity(#[from] IdentityError),
     #[error("Invalid token type")]
     InvalidTokenType,
     #[error("Invalid cookie header")]
     InvalidCookie,
     #[error("Invalid token: ({0})")]
     JwtValidation(#[from] jwt::errors::Error),
+    #[error("Admin access required")]
+    NotAdmin,
+    #[error("Wrong email or password")]
+    InvalidCredentials,
+    #[error("Email not verified yet, follow the link sent to it")]
+    EmailNotVerified,
+    #[error("Session expired or revoked")]
+    SessionRevoked,
+    #[error("Refresh token already used, the session was revoked")]
+    TokenReused,
+    #[error("API token is invalid or revoked")]
+    InvalidApiToken,
+    #[error("API token doesn't have the scope this request needs")]
+    InsufficientScope,
+    #[error(transparent)]
+    Storage(#[from] AppError),
 }
 
 impl IntoResponse for AuthError {
@@ -299,6 +504,13 @@ impl IntoResponse for AuthError {
         let err = self.to_string();
         tracing::error!("{err}");
         let body = Json(json!({"error":  err}));
-        (StatusCode::UNAUTHORIZED, body).into_response()
+        let status = match self {
+            AuthError::NotAdmin | AuthError::EmailNotVerified | AuthError::InsufficientScope => {
+                StatusCode::FORBIDDEN
+            }
+            AuthError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::UNAUTHORIZED,
+        };
+        (status, body).into_response()
     }
 }
@@ -1,22 +1,66 @@
 use axum::{Extension, Json, Router, routing};
 use lib::infra::UserClaims;
 
-use crate::application::ApiState;
+use crate::application::{
+    ApiState,
+    rate_limit::{self, RouteGroup},
+};
 
+mod account;
 mod auth;
+mod sessions;
+mod tokens;
 
-pub use auth::auth;
+pub use auth::{admin, auth};
 
 pub fn router(state: ApiState) -> Router {
     let auth = axum::middleware::from_fn_with_state(state.clone(), auth::auth);
+    let limit = axum::middleware::from_fn_with_state(
+        state.rate_limiter.group(RouteGroup::Auth),
+        rate_limit::limit,
+    );
 
-    Router::new()
+    let mut router = Router::new()
         .route("/me", routing::get(handler))
+        .route("/sessions", routing::get(sessions::list))
+        .route("/sessions", routing::delete(auth::logout_all))
+        .route("/sessions/{id}", routing::delete(sessions::revoke))
+        .route("/tokens", routing::get(tokens::list))
+        .route("/tokens", routing::post(tokens::create))
+        .route("/tokens/{id}", routing::delete(tokens::revoke))
         .route_layer(auth)
-        .route("/session/refresh", routing::post(auth::refresh))
-        .route("/session", routing::post(auth::login))
-        .route("/session", routing::delete(auth::logout))
-        .with_state(state)
+        .route(
+            "/session/refresh",
+            routing::post(auth::refresh).layer(limit.clone()),
+        )
+        .route("/session", routing::post(auth::login).layer(limit.clone()))
+        .route("/session", routing::delete(auth::logout));
+
+    if state.local_auth {
+        router = router
+            .route(
+                "/account",
+                routing::post(account::sign_up).layer(limit.clone()),
+            )
+            .route(
+                "/account/verify",
+                routing::post(account::verify).layer(limit.clone()),
+            )
+            .route(
+                "/account/session",
+                routing::post(account::login).layer(limit.clone()),
+            )
+            .route(
+                "/account/password/forgot",
+                routing::post(account::forgot_password).layer(limit.clone()),
+            )
+            .route(
+                "/account/password/reset",
+                routing::post(account::reset_password).layer(limit),
+            );
+    }
+
+    router.with_state(state)
 }
 
 pub async fn handler(Extension(user_claims): Extension<UserClaims>) -> Json<UserClaims> {
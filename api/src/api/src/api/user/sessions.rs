@@ -0,0 +1,65 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use lib::{
+    AppError, AppResult, Json, Response,
+    infra::{Device, UserClaims},
+};
+
+use crate::application::ApiState;
+
+use super::auth::SessionId;
+
+/// A session as shown to its user, without its refresh token id.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionView {
+    id: String,
+    #[serde(flatten)]
+    device: Device,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    /// Whether this is the session making the request.
+    current: bool,
+}
+
+pub async fn list(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<UserClaims>,
+    Extension(SessionId(current)): Extension<SessionId>,
+) -> Response<Vec<SessionView>> {
+    let sessions = state.sessions.list(&claims.id).await?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| SessionView {
+                current: session.id == current,
+                id: session.id,
+                device: session.device,
+                created_at: session.created_at,
+                last_seen_at: session.last_seen_at,
+                expires_at: session.expires_at,
+            })
+            .collect(),
+    ))
+}
+
+pub async fn revoke(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<UserClaims>,
+    Path(id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    if !state.sessions.revoke(&claims.id, &id).await? {
+        return Err(AppError::Validation("Session not found".to_string()));
+    }
+
+    state.session_cache.remove(&id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
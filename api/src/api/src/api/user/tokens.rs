@@ -0,0 +1,335 @@
+//! Personal access tokens, so scripts such as cron imports can call the API without a browser.
+//!
+//! Tokens are sent as `Authorization: Bearer <token>` and accepted by [`super::auth::auth`]
+//! alongside the session cookies. Each one is limited to its scopes and can't manage sessions or
+//! other tokens, which still need a login.
+
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::{Method, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use lib::{
+    AppError, AppResult, Json, Response,
+    infra::{ApiToken, CreateApiToken, TokenScope, UserClaims},
+};
+use uuid::Uuid;
+
+use crate::application::ApiState;
+
+use super::{
+    account::{random_token, token_hash},
+    auth::{AuthError, user_claims},
+};
+
+/// Marks the tokens, so they are easy to spot in scripts and secret scanners.
+const TOKEN_PREFIX: &str = "pp_";
+
+/// A token as shown to its user, without its hash.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenView {
+    id: String,
+    name: String,
+    scopes: Vec<TokenScope>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiToken> for TokenView {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            scopes: token.scopes,
+            created_at: token.created_at,
+            last_used_at: token.last_used_at,
+        }
+    }
+}
+
+/// Only returned when the token is created, it can't be shown again.
+#[derive(serde::Serialize)]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    details: TokenView,
+    token: String,
+}
+
+pub async fn list(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<UserClaims>,
+) -> Response<Vec<TokenView>> {
+    let tokens = state.api_tokens.list(&claims.id).await?;
+
+    Ok(Json(tokens.into_iter().map(TokenView::from).collect()))
+}
+
+pub async fn create(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<UserClaims>,
+    Json(input): Json<CreateApiToken>,
+) -> AppResult<impl IntoResponse> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Token name is required".to_string()));
+    }
+    if input.scopes.is_empty() {
+        return Err(AppError::Validation(
+            "A token needs at least one scope".to_string(),
+        ));
+    }
+
+    let token = format!("{TOKEN_PREFIX}{}", random_token());
+    let mut scopes = input.scopes;
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    let api_token = ApiToken {
+        id: Uuid::now_v7().to_string(),
+        user_id: claims.id,
+        name: name.to_string(),
+        token_hash: token_hash(&token),
+        scopes,
+        created_at: Utc::now(),
+        last_used_at: None,
+        revoked_at: None,
+    };
+    state.api_tokens.create(&api_token).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedToken {
+            details: api_token.into(),
+            token,
+        }),
+    ))
+}
+
+pub async fn revoke(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<UserClaims>,
+    Path(id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    if !state.api_tokens.revoke(&claims.id, &id, Utc::now()).await? {
+        return Err(AppError::Validation("Token not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The user behind a bearer token, as long as the token is active and has the scope the request
+/// needs.
+pub(super) async fn authenticate(
+    state: &ApiState,
+    token: &str,
+    method: &Method,
+    path: &str,
+) -> Result<UserClaims, AuthError> {
+    let api_token = state
+        .api_tokens
+        .find(&token_hash(token))
+        .await?
+        .filter(|t| t.revoked_at.is_none())
+        .ok_or(AuthError::InvalidApiToken)?;
+
+    let allowed = required_scope(method, path).is_some_and(|s| api_token.scopes.contains(&s));
+    if !allowed {
+        return Err(AuthError::InsufficientScope);
+    }
+
+    state.api_tokens.used(&api_token.id, Utc::now()).await?;
+    let user = state
+        .users
+        .get(&api_token.user_id)
+        .await?
+        .ok_or(AuthError::InvalidApiToken)?;
+
+    Ok(user_claims(user))
+}
+
+/// The scope a request needs, or `None` when tokens can't make it at all.
+fn required_scope(method: &Method, path: &str) -> Option<TokenScope> {
+    // Sessions and tokens are only managed after logging in.
+    if path.starts_with("/user/") && path != "/user/me" {
+        return None;
+    }
+    if *method == Method::GET || *method == Method::HEAD {
+        return Some(TokenScope::Read);
+    }
+
+    match path {
+        "/transaction/import" => Some(TokenScope::Import),
+        path if path == "/transaction" || path.starts_with("/transaction/") => {
+            Some(TokenScope::Transactions)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, header::AUTHORIZATION},
+    };
+    use lib::infra::{Card, CardType, DbState, Identity, LimitPolicy};
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    use crate::application::mailer::MemoryMailer;
+
+    use super::*;
+
+    async fn send(app: &Router, token: &str, method: &str, uri: &str, body: Value) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    /// Creates a token through the handler, returning its plaintext.
+    async fn token(state: &ApiState, claims: &UserClaims, scopes: Vec<TokenScope>) -> String {
+        let response = create(
+            State(state.clone()),
+            Extension(claims.clone()),
+            Json(CreateApiToken {
+                name: "cron".to_string(),
+                scopes,
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let tokens = state.api_tokens.list(&claims.id).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let created: Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(tokens.iter().any(|t| created["id"] == t.id.as_str()));
+
+        created["token"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn tokens_are_limited_to_their_scopes() {
+        let db = DbState::in_memory();
+        let state = ApiState::for_tests(&db, Arc::new(MemoryMailer::default()));
+        let app = lib::router(db.clone()).layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            super::super::auth,
+        ));
+
+        let user = state
+            .users
+            .resolve(&Identity {
+                issuer: "test".to_string(),
+                subject: "ana".to_string(),
+                email: "ana@example.com".to_string(),
+                trusted_email: true,
+                name: "Ana".to_string(),
+                picture: String::new(),
+            })
+            .await
+            .unwrap();
+        let claims = user_claims(user);
+        db.cards
+            .create(&Card {
+                id: "nubank".to_string(),
+                ledger_id: claims.id.clone(),
+                name: "Nubank".to_string(),
+                card_type: CardType::Credit,
+                credit_limit: Some(100),
+                current_balance: 0,
+                limit_policy: LimitPolicy::Reject,
+            })
+            .await
+            .unwrap();
+        let import = json!({"transactions": [{
+            "cardId": "nubank",
+            "categoryId": "1",
+            "amount": 5000,
+            "description": "Statement",
+            "transactionType": "expense",
+            "date": "2026-01-10T12:00:00Z",
+        }]});
+
+        let read = token(&state, &claims, vec![TokenScope::Read]).await;
+        assert_eq!(
+            send(&app, &read, "GET", "/card", json!({})).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&app, &read, "POST", "/transaction/import", import.clone()).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send(&app, "pp_unknown", "GET", "/card", json!({})).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Imports skip the limit policy, statements record what was already spent.
+        let importer = token(&state, &claims, vec![TokenScope::Import]).await;
+        assert_eq!(
+            send(
+                &app,
+                &importer,
+                "POST",
+                "/transaction/import",
+                import.clone()
+            )
+            .await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            send(&app, &importer, "POST", "/transaction", json!({})).await,
+            StatusCode::FORBIDDEN
+        );
+        let transactions = db.transactions.list(&claims.id, 10, 0).await.unwrap();
+        assert_eq!(transactions.len(), 1);
+
+        let tokens = state.api_tokens.list(&claims.id).await.unwrap();
+        let used = tokens.iter().find(|t| t.scopes == [TokenScope::Import]);
+        assert!(used.unwrap().last_used_at.is_some());
+
+        revoke(
+            State(state.clone()),
+            Extension(claims.clone()),
+            Path(used.unwrap().id.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            send(&app, &importer, "POST", "/transaction/import", import).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(state.api_tokens.list(&claims.id).await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn token_management_needs_a_session() {
+        assert_eq!(
+            required_scope(&Method::GET, "/user/me"),
+            Some(TokenScope::Read)
+        );
+        assert_eq!(required_scope(&Method::GET, "/user/tokens"), None);
+        assert_eq!(required_scope(&Method::DELETE, "/user/sessions"), None);
+        assert_eq!(
+            required_scope(&Method::DELETE, "/transaction/123"),
+            Some(TokenScope::Transactions)
+        );
+        assert_eq!(required_scope(&Method::POST, "/card"), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! The address requests come from, for rate limits and the session list.
+//!
+//! `X-Forwarded-For` is only read with `TRUST_PROXY=true`, when the API is only reachable through
+//! the nginx proxy of `app/nginx.conf`. Otherwise any client could pick its own address.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{extract::ConnectInfo, http::HeaderMap};
+
+/// Behind the proxy, the last `X-Forwarded-For` entry is the one it added. The others were sent
+/// by the client, so they can't be trusted to tell clients apart.
+pub fn client_ip(
+    headers: &HeaderMap,
+    connect_info: Option<&ConnectInfo<SocketAddr>>,
+    trust_proxy: bool,
+) -> Option<IpAddr> {
+    let forwarded = headers
+        .get("x-forwarded-for")
+        .filter(|_| trust_proxy)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+
+    forwarded.or(connect_info.map(|ConnectInfo(addr)| addr.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_trusts_the_proxy_when_told_to() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.9, 203.0.113.7".parse().unwrap());
+        let peer = ConnectInfo(SocketAddr::from(([192, 168, 1, 2], 41000)));
+        let ip = |ip: &str| Some(ip.parse::<IpAddr>().unwrap());
+
+        assert_eq!(client_ip(&headers, Some(&peer), true), ip("203.0.113.7"));
+        assert_eq!(client_ip(&headers, Some(&peer), false), ip("192.168.1.2"));
+        assert_eq!(
+            client_ip(&HeaderMap::new(), Some(&peer), true),
+            ip("192.168.1.2")
+        );
+    }
+}
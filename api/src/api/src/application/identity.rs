@@ -0,0 +1,411 @@
+//! OpenID Connect identity providers users can log in with, such as Google or a company SSO.
+//!
+//! `OIDC_PROVIDERS` lists the provider names, each configured with `OIDC_<NAME>_ISSUER` and
+//! `OIDC_<NAME>_CLIENT_ID`. `G_CLIENT_ID` alone still configures Google.
+//!
+//! A first login is only linked to the user with the same email if the provider is trusted to
+//! vouch for it, with `OIDC_<NAME>_LINK_BY_EMAIL=true`. Google is trusted unless set to `false`.
+
+use lib::infra::Identity;
+use openidconnect::{
+    ClientId, EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl, Nonce,
+    core::{CoreClient, CoreIdToken, CoreProviderMetadata},
+};
+
+const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+
+pub type OidcClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderConfig {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    /// Whether the verified emails of the provider link its logins to existing users.
+    pub link_by_email: bool,
+}
+
+#[derive(Clone)]
+pub struct IdentityProvider {
+    pub name: String,
+    link_by_email: bool,
+    client: OidcClient,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum IdentityError {
+    #[error("Identity provider {0} is missing {1}")]
+    MissingConfig(String, String),
+    #[error("No identity provider configured, set OIDC_PROVIDERS or G_CLIENT_ID")]
+    NoProviders,
+    #[error("OpenID discovery for {provider} failed: {reason}")]
+    Discovery { provider: String, reason: String },
+    #[error("Unknown identity provider {0}")]
+    UnknownProvider(String),
+    #[error("OpenID validation failed: {0}")]
+    Validation(String),
+    #[error("Missing claim on id_token: {0}")]
+    MissingClaim(&'static str),
+    #[error("Email not verified by the identity provider")]
+    UnverifiedEmail,
+}
+
+/// Reads the provider list from `var`, so it can be tested without touching the environment.
+pub fn configs_from(
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<ProviderConfig>, IdentityError> {
+    let mut configs = var("OIDC_PROVIDERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let setting = |key: &str| {
+                let key = format!("OIDC_{}_{key}", name.to_uppercase().replace('-', "_"));
+                var(&key).ok_or_else(|| IdentityError::MissingConfig(name.to_string(), key))
+            };
+            let issuer = setting("ISSUER")?;
+
+            Ok(ProviderConfig {
+                name: name.to_string(),
+                client_id: setting("CLIENT_ID")?,
+                link_by_email: setting("LINK_BY_EMAIL")
+                    .map_or(issuer == GOOGLE_ISSUER, |link| link == "true"),
+                issuer,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(client_id) = var("G_CLIENT_ID")
+        && !configs.iter().any(|c| c.name == "google")
+    {
+        configs.push(ProviderConfig {
+            name: "google".to_string(),
+            issuer: GOOGLE_ISSUER.to_string(),
+            client_id,
+            link_by_email: var("OIDC_GOOGLE_LINK_BY_EMAIL").is_none_or(|link| link == "true"),
+        });
+    }
+
+    if configs.is_empty() {
+        return Err(IdentityError::NoProviders);
+    }
+
+    Ok(configs)
+}
+
+impl IdentityProvider {
+    pub async fn discover(config: &ProviderConfig) -> Result<Self, IdentityError> {
+        let discovery_error = |reason: String| IdentityError::Discovery {
+            provider: config.name.clone(),
+            reason,
+        };
+
+        let issuer =
+            IssuerUrl::new(config.issuer.clone()).map_err(|e| discovery_error(e.to_string()))?;
+
+        let http_client = openidconnect::reqwest::ClientBuilder::new()
+            .redirect(openidconnect::reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| discovery_error(e.to_string()))?;
+
+        let metadata = CoreProviderMetadata::discover_async(issuer, &http_client)
+            .await
+            .map_err(|e| discovery_error(e.to_string()))?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            link_by_email: config.link_by_email,
+            client: CoreClient::from_provider_metadata(
+                metadata,
+                ClientId::new(config.client_id.clone()),
+                None,
+            ),
+        })
+    }
+
+    /// Checks the ID token signature, issuer, audience and expiry, then reads the user from it.
+    pub fn verify(&self, token: &str) -> Result<Identity, IdentityError> {
+        let id_token: CoreIdToken = serde_json::from_value(serde_json::Value::String(token.into()))
+            .map_err(|e| IdentityError::Validation(e.to_string()))?;
+
+        let verifier = self.client.id_token_verifier();
+        let claims = id_token
+            .claims(&verifier, |_: Option<&Nonce>| Ok(()))
+            .map_err(|e| IdentityError::Validation(e.to_string()))?;
+
+        let email = claims
+            .email()
+            .ok_or(IdentityError::MissingClaim("email"))?
+            .to_string();
+
+        if claims.email_verified() == Some(false) {
+            return Err(IdentityError::UnverifiedEmail);
+        }
+
+        // Not every provider shares a name or a picture.
+        let name = claims
+            .name()
+            .and_then(|name| name.get(None))
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| email.clone());
+        let picture = claims
+            .picture()
+            .and_then(|picture| picture.get(None))
+            .map(|picture| picture.to_string())
+            .unwrap_or_default();
+
+        Ok(Identity {
+            issuer: claims.issuer().to_string(),
+            subject: claims.subject().to_string(),
+            email,
+            // Many providers leave the claim out, which doesn't vouch for the email either.
+            trusted_email: self.link_by_email && claims.email_verified() == Some(true),
+            name,
+            picture,
+        })
+    }
+}
+
+/// Configured providers, in the order they were listed.
+#[derive(Clone)]
+pub struct IdentityProviders(Vec<IdentityProvider>);
+
+impl IdentityProviders {
+    pub fn new(providers: Vec<IdentityProvider>) -> Self {
+        Self(providers)
+    }
+
+    pub async fn discover(configs: &[ProviderConfig]) -> Result<Self, IdentityError> {
+        let mut providers = Vec::with_capacity(configs.len());
+        for config in configs {
+            providers.push(IdentityProvider::discover(config).await?);
+        }
+
+        Ok(Self(providers))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|p| p.name.as_str())
+    }
+
+    /// Verifies the token with the named provider, or with each one until one accepts it.
+    pub fn verify(&self, provider: Option<&str>, token: &str) -> Result<Identity, IdentityError> {
+        if let Some(name) = provider {
+            return self
+                .0
+                .iter()
+                .find(|p| p.name == name)
+                .ok_or_else(|| IdentityError::UnknownProvider(name.to_string()))?
+                .verify(token);
+        }
+
+        let mut last_error = IdentityError::NoProviders;
+        for provider in &self.0 {
+            match provider.verify(token) {
+                Ok(claims) => return Ok(claims),
+                Err(err) => last_error = err,
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+/// A local OpenID issuer serving discovery and keys, and signing ID tokens for tests.
+#[cfg(test)]
+pub mod testing {
+    use axum::{Json, Router, routing};
+    use jsonwebtoken::{self as jwt, jwk::Jwk};
+    use serde_json::{Value, json};
+
+    use super::{IdentityProvider, ProviderConfig};
+
+    const KEY: &str = include_str!("../../tests/data/oidc_issuer_key.pem");
+    const KEY_ID: &str = "test-key";
+    pub const CLIENT_ID: &str = "pocket-planner-test";
+
+    pub struct MockIssuer {
+        pub url: String,
+    }
+
+    impl MockIssuer {
+        pub async fn start() -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+
+            let key = jwt::EncodingKey::from_rsa_pem(KEY.as_bytes()).unwrap();
+            let mut jwk = Jwk::from_encoding_key(&key, jwt::Algorithm::RS256).unwrap();
+            jwk.common.key_id = Some(KEY_ID.to_string());
+            jwk.common.public_key_use = Some(jwt::jwk::PublicKeyUse::Signature);
+
+            let metadata = json!({
+                "issuer": url,
+                "authorization_endpoint": format!("{url}/authorize"),
+                "jwks_uri": format!("{url}/jwks"),
+                "response_types_supported": ["id_token"],
+                "subject_types_supported": ["public"],
+                "id_token_signing_alg_values_supported": ["RS256"],
+            });
+            let jwks = json!({ "keys": [jwk] });
+
+            let app = Router::new()
+                .route(
+                    "/.well-known/openid-configuration",
+                    routing::get(move || async move { Json(metadata) }),
+                )
+                .route("/jwks", routing::get(move || async move { Json(jwks) }));
+
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            Self { url }
+        }
+
+        pub fn config(&self, name: &str) -> ProviderConfig {
+            ProviderConfig {
+                name: name.to_string(),
+                issuer: self.url.clone(),
+                client_id: CLIENT_ID.to_string(),
+                link_by_email: true,
+            }
+        }
+
+        pub async fn provider(&self, name: &str) -> IdentityProvider {
+            IdentityProvider::discover(&self.config(name))
+                .await
+                .unwrap()
+        }
+
+        /// ID token for `email`, with `extra` claims overriding the defaults.
+        pub fn id_token(&self, email: &str, extra: Value) -> String {
+            let now = chrono::Utc::now().timestamp();
+            let mut claims = json!({
+                "iss": self.url,
+                "aud": CLIENT_ID,
+                "sub": email,
+                "email": email,
+                "email_verified": true,
+                "name": "Test User",
+                "iat": now,
+                "exp": now + 300,
+            });
+            claims
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().cloned().unwrap_or_default());
+
+            let mut header = jwt::Header::new(jwt::Algorithm::RS256);
+            header.kid = Some(KEY_ID.to_string());
+
+            jwt::encode(
+                &header,
+                &claims,
+                &jwt::EncodingKey::from_rsa_pem(KEY.as_bytes()).unwrap(),
+            )
+            .unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{testing::MockIssuer, *};
+
+    #[test]
+    fn reads_providers_from_the_environment() {
+        let env = |pairs: &'static [(&str, &str)]| {
+            move |key: &str| {
+                pairs
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+
+        let configs = configs_from(env(&[
+            ("OIDC_PROVIDERS", "company-sso"),
+            (
+                "OIDC_COMPANY_SSO_ISSUER",
+                "https://sso.example.com/realms/team",
+            ),
+            ("OIDC_COMPANY_SSO_CLIENT_ID", "planner"),
+            ("G_CLIENT_ID", "google-client"),
+        ]))
+        .unwrap();
+
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].issuer, "https://sso.example.com/realms/team");
+        assert!(!configs[0].link_by_email);
+        assert_eq!(configs[1].name, "google");
+        assert_eq!(configs[1].issuer, GOOGLE_ISSUER);
+        assert!(configs[1].link_by_email);
+
+        let trusted = configs_from(env(&[
+            ("OIDC_PROVIDERS", "sso"),
+            ("OIDC_SSO_ISSUER", "https://sso.example.com"),
+            ("OIDC_SSO_CLIENT_ID", "planner"),
+            ("OIDC_SSO_LINK_BY_EMAIL", "true"),
+        ]))
+        .unwrap();
+        assert!(trusted[0].link_by_email);
+
+        let missing = configs_from(env(&[("OIDC_PROVIDERS", "keycloak")]));
+        assert!(matches!(missing, Err(IdentityError::MissingConfig(..))));
+        assert!(matches!(
+            configs_from(env(&[])),
+            Err(IdentityError::NoProviders)
+        ));
+    }
+
+    #[tokio::test]
+    async fn verifies_tokens_from_a_discovered_issuer() {
+        let issuer = MockIssuer::start().await;
+        let providers = IdentityProviders::new(vec![issuer.provider("sso").await]);
+
+        let claims = providers
+            .verify(None, &issuer.id_token("ana@example.com", json!({})))
+            .unwrap();
+        assert_eq!(claims.email, "ana@example.com");
+        assert_eq!(claims.issuer, issuer.url);
+        assert_eq!(claims.subject, "ana@example.com");
+        assert_eq!(claims.name, "Test User");
+        assert_eq!(claims.picture, "");
+        assert!(claims.trusted_email);
+
+        let unclaimed = issuer.id_token("ana@example.com", json!({"email_verified": null}));
+        let claims = providers.verify(None, &unclaimed).unwrap();
+        assert!(!claims.trusted_email);
+
+        let other_client = issuer.id_token("ana@example.com", json!({"aud": "someone-else"}));
+        assert!(matches!(
+            providers.verify(Some("sso"), &other_client),
+            Err(IdentityError::Validation(_))
+        ));
+
+        let expired = issuer.id_token("ana@example.com", json!({"exp": 1_000_000}));
+        assert!(providers.verify(None, &expired).is_err());
+
+        let unverified = issuer.id_token("ana@example.com", json!({"email_verified": false}));
+        assert!(matches!(
+            providers.verify(None, &unverified),
+            Err(IdentityError::UnverifiedEmail)
+        ));
+
+        assert!(matches!(
+            providers.verify(
+                Some("google"),
+                &issuer.id_token("ana@example.com", json!({}))
+            ),
+            Err(IdentityError::UnknownProvider(_))
+        ));
+    }
+}
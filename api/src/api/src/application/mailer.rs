@@ -0,0 +1,41 @@
+//! Account emails, such as verification and password reset links.
+
+/// Delivers account emails. Without an SMTP relay the links are read from the server log.
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: Mail);
+}
+
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Writes every email to the log instead of sending it.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, mail: Mail) {
+        tracing::info!(to = mail.to, subject = mail.subject, "{}", mail.body);
+    }
+}
+
+/// Keeps the sent emails, so tests can follow their links.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryMailer(std::sync::Mutex<Vec<Mail>>);
+
+#[cfg(test)]
+impl MemoryMailer {
+    pub fn sent(&self) -> Vec<Mail> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Mailer for MemoryMailer {
+    fn send(&self, mail: Mail) {
+        self.0.lock().unwrap().push(mail);
+    }
+}
@@ -1,30 +1,84 @@
-use openidconnect::{
-    ClientId, EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl,
-    core::{CoreClient, CoreProviderMetadata},
+use std::{sync::Arc, time::Duration};
+
+use lib::{
+    infra::DbState,
+    repository::{AccountRepository, ApiTokenRepository, SessionRepository, UserRepository},
 };
 
 use crate::expect_env;
 
+pub mod client_ip;
 pub mod extractors;
+pub mod identity;
+pub mod mailer;
 pub mod model;
+pub mod rate_limit;
+pub mod session_cache;
+pub mod sgs;
+pub mod webhooks;
+
+use identity::{IdentityError, IdentityProviders};
+use mailer::{LogMailer, Mailer};
+use rate_limit::RateLimiter;
+use session_cache::SessionCache;
 
 #[derive(Clone)]
 pub struct ApiState {
-    pub google_client: GoogleClient,
+    pub identity_providers: Arc<IdentityProviders>,
     pub secure_env: bool,
+    /// Whether the API is only reachable through the proxy, see [`client_ip::client_ip`].
+    pub trust_proxy: bool,
     pub jwt_access_secret: String,
     pub jwt_refresh_secret: String,
     pub jwt_issuer: String,
     pub jwt_audience: String,
     pub access_ttl: u64,
     pub refresh_ttl: u64,
+    /// Ids of the users allowed to change shared data, such as the calculator rates. Not their
+    /// emails, as anyone can claim an email with a provider that doesn't verify it.
+    pub admin_ids: Vec<String>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub session_cache: SessionCache,
+    /// Whether users can sign up with an email and password, see `api::user::account`.
+    pub local_auth: bool,
+    /// Where the frontend is served, for the links in account emails.
+    pub public_url: String,
+    pub accounts: Arc<dyn AccountRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub api_tokens: Arc<dyn ApiTokenRepository>,
+    pub rate_limiter: RateLimiter,
+    pub mailer: Arc<dyn Mailer>,
 }
 
 impl ApiState {
-    pub async fn from_env() -> Self {
-        let google_client = load_google_auth().await;
+    pub async fn from_env(db: &DbState) -> Self {
+        let local_auth = std::env::var("LOCAL_AUTH").is_ok_and(|v| v == "true");
+
+        // Local accounts alone don't need any provider, nor network access at startup.
+        let configs = match identity::configs_from(|key| std::env::var(key).ok()) {
+            Ok(configs) => configs,
+            Err(IdentityError::NoProviders) if local_auth => vec![],
+            Err(err) => panic!("{err}"),
+        };
+        // An unreachable issuer shouldn't keep local accounts from signing in.
+        let identity_providers = match IdentityProviders::discover(&configs).await {
+            Ok(providers) => providers,
+            Err(err) if local_auth => {
+                tracing::error!(
+                    ?err,
+                    "failed to discover the identity providers, only local login is available"
+                );
+                IdentityProviders::new(vec![])
+            }
+            Err(err) => panic!("{err}"),
+        };
+        tracing::info!(
+            "Identity providers: {}",
+            identity_providers.names().collect::<Vec<_>>().join(", ")
+        );
 
         let secure_env = expect_env!("SECURE_ENV") == "true";
+        let trust_proxy = std::env::var("TRUST_PROXY").is_ok_and(|v| v == "true");
 
         let jwt_access_secret = expect_env!("JWT_ACCESS_SECRET");
         let jwt_refresh_secret = expect_env!("JWT_REFRESH_SECRET");
@@ -36,42 +90,75 @@ impl ApiState {
             .parse()
             .expect("numeric value");
 
+        let admin_ids = std::env::var("ADMIN_USER_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect();
+
+        let public_url =
+            std::env::var("PUBLIC_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+
         ApiState {
-            google_client,
+            identity_providers: Arc::new(identity_providers),
             secure_env,
+            trust_proxy,
             jwt_access_secret,
             jwt_refresh_secret,
             jwt_issuer: "pocket-planner-api".to_string(),
             jwt_audience: "pocket-planner-clients".to_string(),
             access_ttl,
             refresh_ttl,
+            admin_ids,
+            sessions: db.sessions.clone(),
+            session_cache: SessionCache::new(Duration::from_secs(30), 10_000),
+            local_auth,
+            public_url,
+            accounts: db.accounts.clone(),
+            users: db.users.clone(),
+            api_tokens: db.api_tokens.clone(),
+            rate_limiter: RateLimiter::from_env(db, trust_proxy),
+            mailer: Arc::new(LogMailer),
         }
     }
 }
 
-pub type GoogleClient = CoreClient<
-    EndpointSet,
-    EndpointNotSet,
-    EndpointNotSet,
-    EndpointNotSet,
-    EndpointMaybeSet,
-    EndpointMaybeSet,
->;
-
-async fn load_google_auth() -> GoogleClient {
-    let audience = expect_env!("G_CLIENT_ID");
-
-    let issuer =
-        IssuerUrl::new("https://accounts.google.com".to_string()).expect("valid issuer url");
-
-    let oidc_http_client = openidconnect::reqwest::ClientBuilder::new()
-        .redirect(openidconnect::reqwest::redirect::Policy::none())
-        .build()
-        .expect("OIDC client should build");
-
-    let google_provider = CoreProviderMetadata::discover_async(issuer, &oidc_http_client)
-        .await
-        .expect("Failed to discover OpenID configuration");
+#[cfg(test)]
+impl ApiState {
+    /// Local accounts only, backed by `db`.
+    pub fn for_tests(db: &DbState, mailer: Arc<dyn Mailer>) -> Self {
+        let off = rate_limit::GroupLimits {
+            per_ip: None,
+            per_user: None,
+        };
 
-    CoreClient::from_provider_metadata(google_provider, ClientId::new(audience), None)
+        ApiState {
+            identity_providers: Arc::new(IdentityProviders::new(vec![])),
+            secure_env: false,
+            trust_proxy: false,
+            jwt_access_secret: "access-secret".to_string(),
+            jwt_refresh_secret: "refresh-secret".to_string(),
+            jwt_issuer: "pocket-planner-api".to_string(),
+            jwt_audience: "pocket-planner-clients".to_string(),
+            access_ttl: 900,
+            refresh_ttl: 3600,
+            admin_ids: vec![],
+            sessions: db.sessions.clone(),
+            session_cache: SessionCache::new(Duration::from_secs(30), 100),
+            local_auth: true,
+            public_url: "http://localhost:8080".to_string(),
+            accounts: db.accounts.clone(),
+            users: db.users.clone(),
+            api_tokens: db.api_tokens.clone(),
+            // Off, so tests can log in as many times as they need.
+            rate_limiter: RateLimiter::new(
+                Arc::new(lib::repository::MemoryStore::default()),
+                off,
+                off,
+                false,
+            ),
+            mailer,
+        }
+    }
 }
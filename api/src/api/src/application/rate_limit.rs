@@ -0,0 +1,346 @@
+//! Token-bucket rate limits per client IP and per user, for groups of routes.
+//!
+//! Buckets are kept in memory by default. With `RATE_LIMIT_STORE=sqlite` they are kept in the
+//! database instead, so every instance sharing it enforces the same limits. Each limit is set as
+//! `<requests>/<seconds>`, or `off`:
+//!
+//! - `RATE_LIMIT_AUTH_IP`, logins and refreshes per IP, 10/60 by default.
+//! - `RATE_LIMIT_WRITES_IP`, changes to the ledger data per IP, 300/60 by default.
+//! - `RATE_LIMIT_WRITES_USER`, the same per user, 120/60 by default.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use lib::{
+    Json,
+    infra::{DbState, RateLimit, UserClaims},
+    repository::{MemoryStore, RateLimitRepository},
+};
+use serde_json::json;
+
+use super::client_ip::client_ip;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteGroup {
+    /// Logging in and refreshing sessions, where passwords and tokens could be guessed.
+    Auth,
+    /// Requests changing the ledger data.
+    Writes,
+}
+
+/// The limits of a route group. Either one can be turned off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupLimits {
+    pub per_ip: Option<RateLimit>,
+    pub per_user: Option<RateLimit>,
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    buckets: Arc<dyn RateLimitRepository>,
+    auth: GroupLimits,
+    writes: GroupLimits,
+    /// Whether clients are told apart by `X-Forwarded-For`, see [`client_ip`].
+    trust_proxy: bool,
+}
+
+/// State of the [`limit`] middleware for one route group.
+#[derive(Clone)]
+pub struct GroupLimiter {
+    group: RouteGroup,
+    limits: GroupLimits,
+    buckets: Arc<dyn RateLimitRepository>,
+    trust_proxy: bool,
+}
+
+impl RateLimiter {
+    pub fn new(
+        buckets: Arc<dyn RateLimitRepository>,
+        auth: GroupLimits,
+        writes: GroupLimits,
+        trust_proxy: bool,
+    ) -> Self {
+        Self {
+            buckets,
+            auth,
+            writes,
+            trust_proxy,
+        }
+    }
+
+    pub fn from_env(db: &DbState, trust_proxy: bool) -> Self {
+        let env = |key| std::env::var(key).ok();
+
+        let buckets: Arc<dyn RateLimitRepository> = match env("RATE_LIMIT_STORE").as_deref() {
+            Some("sqlite") => db.rate_limits.clone(),
+            None | Some("memory") => Arc::new(MemoryStore::default()),
+            Some(other) => panic!("unknown RATE_LIMIT_STORE: {other}"),
+        };
+        let (auth, writes) = configs_from(env).unwrap_or_else(|err| panic!("{err}"));
+
+        Self::new(buckets, auth, writes, trust_proxy)
+    }
+
+    pub fn group(&self, group: RouteGroup) -> GroupLimiter {
+        let limits = match group {
+            RouteGroup::Auth => self.auth,
+            RouteGroup::Writes => self.writes,
+        };
+
+        GroupLimiter {
+            group,
+            limits,
+            buckets: self.buckets.clone(),
+            trust_proxy: self.trust_proxy,
+        }
+    }
+}
+
+/// Limits of the auth and writes groups, from variables read with `get`.
+pub fn configs_from(
+    get: impl Fn(&'static str) -> Option<String>,
+) -> Result<(GroupLimits, GroupLimits), String> {
+    let limit = |key, default: (u32, i64)| match get(key) {
+        None => Ok(Some(RateLimit {
+            burst: default.0,
+            period: Duration::seconds(default.1),
+        })),
+        Some(value) => parse_limit(&value)
+            .ok_or_else(|| format!("{key} must be <requests>/<seconds> or off, not {value:?}")),
+    };
+
+    let auth = GroupLimits {
+        per_ip: limit("RATE_LIMIT_AUTH_IP", (10, 60))?,
+        // Nobody is logged in yet.
+        per_user: None,
+    };
+    let writes = GroupLimits {
+        per_ip: limit("RATE_LIMIT_WRITES_IP", (300, 60))?,
+        per_user: limit("RATE_LIMIT_WRITES_USER", (120, 60))?,
+    };
+
+    Ok((auth, writes))
+}
+
+fn parse_limit(value: &str) -> Option<Option<RateLimit>> {
+    if value.trim() == "off" {
+        return Some(None);
+    }
+
+    let (burst, seconds) = value.split_once('/')?;
+    let burst: u32 = burst.trim().parse().ok()?;
+    let seconds: i64 = seconds.trim().parse().ok()?;
+    if burst == 0 || seconds <= 0 {
+        return None;
+    }
+
+    Some(Some(RateLimit {
+        burst,
+        period: Duration::seconds(seconds),
+    }))
+}
+
+/// Rejects the requests of a client or user over the limits of the group with `429 Too Many
+/// Requests`. Reads aren't limited. Runs after `auth` to limit users too.
+pub async fn limit(
+    State(limiter): State<GroupLimiter>,
+    req: Request,
+    next: Next,
+) -> Result<Response, RateLimited> {
+    if req.method().is_safe() {
+        return Ok(next.run(req).await);
+    }
+
+    let ip = client_ip(req.headers(), req.extensions().get(), limiter.trust_proxy);
+    let user = req.extensions().get::<UserClaims>().map(|c| c.id.as_str());
+    let keys = [
+        limiter.limits.per_ip.zip(ip.map(|ip| format!("ip:{ip}"))),
+        limiter
+            .limits
+            .per_user
+            .zip(user.map(|id| format!("user:{id}"))),
+    ];
+
+    let now = Utc::now();
+    for (limit, key) in keys.into_iter().flatten() {
+        let key = format!("{:?}:{key}", limiter.group).to_lowercase();
+
+        // Better to let requests through than to fail them all while the store is down.
+        match limiter.buckets.take(&key, &limit, now).await {
+            Ok(Some(retry_after)) => return Err(RateLimited { retry_after }),
+            Ok(None) => {}
+            Err(err) => tracing::error!(?err, key, "failed to check the rate limit"),
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+#[derive(Debug)]
+pub struct RateLimited {
+    retry_after: Duration,
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        let seconds = (self.retry_after.num_milliseconds().max(1) + 999) / 1000;
+        let body = Json(json!({
+            "error": format!("Too many requests, try again in {seconds} seconds")
+        }));
+
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, body).into_response();
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::{Router, body::Body, extract::ConnectInfo, routing};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn limiter(per_ip: u32, per_user: u32, trust_proxy: bool) -> GroupLimiter {
+        let limit = |burst| {
+            Some(RateLimit {
+                burst,
+                period: Duration::seconds(60),
+            })
+        };
+        let limits = GroupLimits {
+            per_ip: limit(per_ip),
+            per_user: limit(per_user),
+        };
+
+        RateLimiter::new(
+            Arc::new(MemoryStore::default()),
+            limits,
+            limits,
+            trust_proxy,
+        )
+        .group(RouteGroup::Writes)
+    }
+
+    /// Sent by the proxy at 192.0.2.1 for the client at `ip`.
+    async fn send(app: &Router, method: &str, ip: &str, user: &str) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri("/")
+            .header("x-forwarded-for", format!("203.0.113.9, {ip}"))
+            .extension(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 41000))))
+            .extension(UserClaims {
+                id: user.to_string(),
+                email: String::new(),
+                name: String::new(),
+                picture: String::new(),
+            })
+            .body(Body::empty())
+            .unwrap();
+
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    fn app(limiter: GroupLimiter) -> Router {
+        Router::new()
+            .route("/", routing::get(|| async {}).post(|| async {}))
+            .layer(axum::middleware::from_fn_with_state(limiter, limit))
+    }
+
+    #[tokio::test]
+    async fn limits_each_client_ip() {
+        let app = app(limiter(2, 100, true));
+
+        for user in ["ana", "bia"] {
+            assert_eq!(
+                send(&app, "POST", "10.0.0.1", user).await.status(),
+                StatusCode::OK
+            );
+        }
+        let limited = send(&app, "POST", "10.0.0.1", "carol").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.headers()[RETRY_AFTER], "30");
+
+        // Reads, and other clients, still go through.
+        assert_eq!(
+            send(&app, "GET", "10.0.0.1", "ana").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&app, "POST", "10.0.0.2", "ana").await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_each_user_across_ips() {
+        let app = app(limiter(100, 1, true));
+
+        assert_eq!(
+            send(&app, "POST", "10.0.0.1", "ana").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&app, "POST", "10.0.0.2", "ana").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            send(&app, "POST", "10.0.0.2", "bia").await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_forwarded_ips_without_a_trusted_proxy() {
+        let app = app(limiter(1, 100, false));
+
+        assert_eq!(
+            send(&app, "POST", "10.0.0.1", "ana").await.status(),
+            StatusCode::OK
+        );
+        // Picking another address doesn't get another bucket.
+        assert_eq!(
+            send(&app, "POST", "10.0.0.2", "bia").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn reads_limits_from_the_environment() {
+        let env = |values: &'static [(&str, &str)]| {
+            move |key: &str| {
+                values
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+
+        let (auth, writes) = configs_from(env(&[
+            ("RATE_LIMIT_AUTH_IP", "5/300"),
+            ("RATE_LIMIT_WRITES_USER", "off"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            auth.per_ip,
+            Some(RateLimit {
+                burst: 5,
+                period: Duration::seconds(300),
+            })
+        );
+        assert_eq!(writes.per_ip.unwrap().burst, 300);
+        assert_eq!(writes.per_user, None);
+
+        assert!(configs_from(env(&[("RATE_LIMIT_WRITES_IP", "0/60")])).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Sessions recently confirmed active, so the `auth` middleware only reads the database once in a
+/// while per session. A revocation from another process takes up to `ttl` to be noticed.
+#[derive(Clone)]
+pub struct SessionCache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+struct Entry {
+    user_id: String,
+    checked_at: Instant,
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Arc::default(),
+            ttl,
+            capacity,
+        }
+    }
+
+    pub fn is_fresh(&self, session_id: &str) -> bool {
+        self.entries()
+            .get(session_id)
+            .is_some_and(|entry| entry.checked_at.elapsed() < self.ttl)
+    }
+
+    pub fn insert(&self, session_id: &str, user_id: &str) {
+        let mut entries = self.entries();
+
+        if entries.len() >= self.capacity {
+            entries.retain(|_, entry| entry.checked_at.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity {
+            entries.clear();
+        }
+
+        entries.insert(
+            session_id.to_string(),
+            Entry {
+                user_id: user_id.to_string(),
+                checked_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn remove(&self, session_id: &str) {
+        self.entries().remove(session_id);
+    }
+
+    pub fn remove_user(&self, user_id: &str) {
+        self.entries().retain(|_, entry| entry.user_id != user_id);
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        // Losing the cache only costs a few database reads.
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgets_revoked_and_stale_sessions() {
+        let cache = SessionCache::new(Duration::from_secs(60), 2);
+
+        cache.insert("laptop", "user@test.com");
+        cache.insert("phone", "user@test.com");
+        assert!(cache.is_fresh("laptop"));
+
+        cache.remove("laptop");
+        assert!(!cache.is_fresh("laptop"));
+
+        cache.insert("other", "other@test.com");
+        cache.insert("tablet", "other@test.com");
+        // Full of fresh entries, so it starts over.
+        assert!(!cache.is_fresh("phone"));
+        assert!(cache.is_fresh("tablet"));
+
+        cache.remove_user("other@test.com");
+        assert!(!cache.is_fresh("tablet"));
+
+        let expired = SessionCache::new(Duration::ZERO, 10);
+        expired.insert("laptop", "user@test.com");
+        assert!(!expired.is_fresh("laptop"));
+    }
+}
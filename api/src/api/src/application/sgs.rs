@@ -0,0 +1,101 @@
+//! Parses the files exported by the Banco Central SGS service
+//! (`api.bcb.gov.br/dados/serie/bcdata.sgs.{code}/dados?formato=json|csv`).
+
+use chrono::{Datelike, NaiveDate};
+use lib::infra::{Periodicity, Series, SeriesPoint};
+use rust_decimal::Decimal;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SgsError {
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Line {line}: {reason}")]
+    InvalidLine { line: usize, reason: String },
+}
+
+#[derive(serde::Deserialize)]
+struct JsonPoint {
+    data: String,
+    valor: String,
+}
+
+/// Accepts either export format: a JSON array of `{"data", "valor"}`, or the `;` separated CSV
+/// with a `data;valor` header and decimal commas.
+pub fn parse(series: Series, contents: &str) -> Result<Vec<SeriesPoint>, SgsError> {
+    let contents = contents.trim_start_matches('\u{feff}').trim();
+
+    let rows: Vec<(usize, String, String)> = if contents.starts_with('[') {
+        serde_json::from_str::<Vec<JsonPoint>>(contents)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (i + 1, p.data, p.valor))
+            .collect()
+    } else {
+        contents
+            .lines()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let mut fields = line.split(';').map(|f| f.trim().trim_matches('"'));
+                let date = fields.next().unwrap_or_default().to_string();
+                let value = fields.next().unwrap_or_default().to_string();
+                (i + 1, date, value)
+            })
+            .collect()
+    };
+
+    rows.into_iter()
+        .map(|(line, date, value)| point(series, line, &date, &value))
+        .collect()
+}
+
+fn point(series: Series, line: usize, date: &str, value: &str) -> Result<SeriesPoint, SgsError> {
+    let invalid = |reason: String| SgsError::InvalidLine { line, reason };
+
+    let mut date = NaiveDate::parse_from_str(date, "%d/%m/%Y")
+        .map_err(|err| invalid(format!("invalid date {date:?}: {err}")))?;
+    let value: Decimal = value
+        .replace(',', ".")
+        .parse()
+        .map_err(|err| invalid(format!("invalid value {value:?}: {err}")))?;
+
+    if series.periodicity() == Periodicity::Monthly {
+        date = date.with_day(1).expect("first day of the month");
+    }
+
+    Ok(SeriesPoint {
+        series,
+        date,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::infra::Series;
+    use rust_decimal_macros::dec;
+
+    use super::parse;
+
+    #[test]
+    fn parses_both_formats() {
+        let json = r#"[{"data":"02/01/2020","valor":"0.017089"},{"data":"03/01/2020","valor":"0.017089"}]"#;
+        let csv = "data;valor\n\"02/01/2020\";\"0,017089\"\n\"03/01/2020\";\"0,017089\"\n";
+
+        for contents in [json, csv] {
+            let points = parse(Series::Cdi, contents).unwrap();
+
+            assert_eq!(points.len(), 2);
+            assert_eq!(points[0].date.to_string(), "2020-01-02");
+            assert_eq!(points[1].value, dec!(0.017089));
+        }
+    }
+
+    #[test]
+    fn reports_the_invalid_line() {
+        let err = parse(Series::Ipca, "data;valor\n01/01/2020;0,21\n01/13/2020;0,25").unwrap_err();
+
+        assert!(err.to_string().starts_with("Line 3"));
+    }
+}
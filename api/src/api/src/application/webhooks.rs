@@ -0,0 +1,436 @@
+//! Delivers the webhook events queued by the `lib` handlers.
+//!
+//! The queue lives in SQLite, so deliveries survive restarts. Each request carries the headers
+//! `X-Webhook-Event`, `X-Webhook-Delivery` and `X-Webhook-Signature: t=<unix time>,v1=<hex>`,
+//! where the signature is the HMAC-SHA256 of `<unix time>.<body>` keyed with the webhook secret.
+//! Failed deliveries are retried with an exponential backoff, see [`Delivery::record`].
+//!
+//! Webhooks are only sent to public addresses, unless `WEBHOOK_ALLOW_PRIVATE=true`, so they can't
+//! be used to reach the services next to the API or the cloud metadata endpoints.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use lib::{
+    AppError, AppResult,
+    infra::{Delivery, DeliveryAttempt, Webhook, WebhookTargets},
+    repository::WebhookRepository,
+};
+use reqwest::{
+    Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+};
+use sha2::Sha256;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Long enough for a whole batch to time out before another worker can claim it again.
+const LEASE: chrono::Duration = chrono::Duration::minutes(5);
+const BATCH_SIZE: u32 = 20;
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+pub struct WebhookWorker {
+    webhooks: Arc<dyn WebhookRepository>,
+    targets: Arc<PublicTargets>,
+    client: reqwest::Client,
+}
+
+impl WebhookWorker {
+    pub fn new(webhooks: Arc<dyn WebhookRepository>, targets: Arc<PublicTargets>) -> Self {
+        // Resolving through the targets too, a host can't point somewhere else once checked.
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .dns_resolver(targets.clone())
+            .build()
+            .expect("webhook HTTP client");
+
+        Self {
+            webhooks,
+            targets,
+            client,
+        }
+    }
+
+    /// Delivers the queue in the background for as long as the server runs.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = self.deliver_due(Utc::now()).await {
+                    tracing::error!(?err, "failed to deliver webhooks");
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Sends the deliveries due by `now`, returning how many were attempted.
+    pub async fn deliver_due(&self, now: DateTime<Utc>) -> AppResult<usize> {
+        let mut attempted = 0;
+
+        loop {
+            let due = self
+                .webhooks
+                .claim_due(now, now + LEASE, BATCH_SIZE)
+                .await?;
+            if due.is_empty() {
+                return Ok(attempted);
+            }
+
+            for (webhook, mut delivery) in due {
+                let attempt = self.send(&webhook, &delivery).await;
+                delivery.record(attempt);
+                self.webhooks.update_delivery(&delivery).await?;
+
+                attempted += 1;
+            }
+        }
+    }
+
+    async fn send(&self, webhook: &Webhook, delivery: &Delivery) -> DeliveryAttempt {
+        // The URL was checked when the webhook was created, but its host may resolve elsewhere
+        // by now. Addresses in the URL don't go through the resolver either.
+        if let Err(err) = self.targets.check(&webhook.url).await {
+            return DeliveryAttempt {
+                at: Utc::now(),
+                response_status: None,
+                error: Some(err.to_string()),
+            };
+        }
+
+        let timestamp = Utc::now().timestamp();
+        let signature = signature(&webhook.secret, timestamp, &delivery.payload);
+
+        let result = self
+            .client
+            .post(&webhook.url)
+            .header("content-type", "application/json")
+            .header("x-webhook-event", delivery.event.as_str())
+            .header("x-webhook-delivery", &delivery.id)
+            .header(SIGNATURE_HEADER, format!("t={timestamp},v1={signature}"))
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+
+        let (response_status, error) = match result {
+            Ok(response) => (Some(response.status().as_u16()), None),
+            Err(err) => {
+                tracing::warn!(?err, webhook = webhook.id, "webhook delivery failed");
+                (None, Some(err.to_string()))
+            }
+        };
+
+        DeliveryAttempt {
+            at: Utc::now(),
+            response_status,
+            error,
+        }
+    }
+}
+
+/// Only lets webhooks reach public addresses, unless `allow_private` is set.
+pub struct PublicTargets {
+    allow_private: bool,
+}
+
+impl PublicTargets {
+    pub fn new(allow_private: bool) -> Self {
+        Self { allow_private }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("WEBHOOK_ALLOW_PRIVATE").is_ok_and(|v| v == "true"))
+    }
+
+    fn allows(&self, ip: IpAddr) -> bool {
+        self.allow_private || is_public(ip)
+    }
+
+    async fn lookup(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+
+        // Any private answer could be the one connected to, so none is used.
+        if addrs.is_empty() || addrs.iter().any(|addr| !self.allows(addr.ip())) {
+            return Err(std::io::Error::other(format!(
+                "{host} doesn't resolve to public addresses only"
+            )));
+        }
+
+        Ok(addrs)
+    }
+}
+
+#[async_trait]
+impl WebhookTargets for PublicTargets {
+    async fn check(&self, url: &str) -> AppResult<()> {
+        let invalid = |reason: &str| AppError::Validation(format!("Invalid webhook URL: {reason}"));
+
+        let url = Url::parse(url).map_err(|err| invalid(&err.to_string()))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let host = url.host_str().ok_or_else(|| invalid("missing host"))?;
+        // IPv6 addresses are written in brackets.
+        let public = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            Ok(ip) => self.allows(ip),
+            Err(_) => self.lookup(host, port).await.is_ok(),
+        };
+
+        if !public {
+            return Err(invalid("webhooks can only be sent to public addresses"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Resolve for PublicTargets {
+    fn resolve(&self, name: Name) -> Resolving {
+        let targets = Self::new(self.allow_private);
+
+        Box::pin(async move {
+            let addrs = targets.lookup(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether the address is reachable on the internet, rather than on the host or its network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // "This network", carrier-grade NAT, benchmarking and reserved ranges.
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (18..20).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    // Documentation.
+                    || ip.segments()[..2] == [0x2001, 0xdb8])
+            }
+        },
+    }
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<payload>`, what receivers compare the signature header with.
+pub fn signature(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{timestamp}.{payload}").as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::{
+        Extension, Router,
+        body::{Body, Bytes},
+        http::{HeaderMap, Request, StatusCode},
+        routing,
+    };
+    use lib::infra::{DbState, DeliveryStatus, UserClaims, init_db};
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    /// A local stand-in for the receiver, failing the first request it gets.
+    async fn receiver() -> (String, Received) {
+        let received = Received::default();
+        let app = Router::new().route(
+            "/hook",
+            routing::post({
+                let received = received.clone();
+                move |headers: HeaderMap, body: Bytes| async move {
+                    let mut received = received.lock().unwrap();
+                    received.push((headers, String::from_utf8(body.to_vec()).unwrap()));
+                    if received.len() == 1 {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    } else {
+                        StatusCode::NO_CONTENT
+                    }
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (url, received)
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str, body: Value) -> Value {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert!(
+            response.status().is_success(),
+            "{method} {uri}: {}",
+            response.status()
+        );
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_events_with_retries() {
+        let db = DbState::new(init_db(":memory:").await.unwrap());
+        let app = lib::router(db.clone()).layer(Extension(UserClaims {
+            id: "ana".to_string(),
+            email: "ana@example.com".to_string(),
+            name: "Ana".to_string(),
+            picture: String::new(),
+        }));
+        let (url, received) = receiver().await;
+
+        let webhook = send(
+            &app,
+            "POST",
+            "/webhook",
+            json!({"url": url, "events": ["transaction.created", "credit_limit.exceeded"]}),
+        )
+        .await;
+        let card = send(
+            &app,
+            "POST",
+            "/card",
+            json!({"name": "Nubank", "cardType": "credit", "creditLimit": 10000}),
+        )
+        .await;
+        send(
+            &app,
+            "POST",
+            "/transaction",
+            json!({
+                "cardId": card["id"],
+                "categoryId": "1",
+                "amount": 2500,
+                "description": "Groceries",
+                "transactionType": "expense",
+                "date": "2026-01-10T12:00:00Z",
+            }),
+        )
+        .await;
+
+        // The receiver is local.
+        let worker = WebhookWorker::new(db.webhooks.clone(), Arc::new(PublicTargets::new(true)));
+        let now = Utc::now();
+        assert_eq!(worker.deliver_due(now).await.unwrap(), 1);
+        // The receiver failed, so the delivery waits for its backoff.
+        assert_eq!(worker.deliver_due(now).await.unwrap(), 0);
+        let later = now + chrono::Duration::seconds(31);
+        assert_eq!(worker.deliver_due(later).await.unwrap(), 1);
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        let (headers, body) = &received[1];
+        assert_eq!(headers["x-webhook-event"], "transaction.created");
+        let event: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(event["data"]["description"], "Groceries");
+        assert_eq!(&received[0].1, body);
+
+        let header = headers[SIGNATURE_HEADER].to_str().unwrap();
+        let (timestamp, signed) = header
+            .strip_prefix("t=")
+            .and_then(|rest| rest.split_once(",v1="))
+            .unwrap();
+        let secret = webhook["secret"].as_str().unwrap();
+        assert_eq!(signed, signature(secret, timestamp.parse().unwrap(), body));
+
+        let log = send(
+            &app,
+            "GET",
+            &format!("/webhook/{}/deliveries", webhook["id"].as_str().unwrap()),
+            json!({}),
+        )
+        .await;
+        let log: Vec<Delivery> = serde_json::from_value(log).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].status, DeliveryStatus::Delivered);
+        assert_eq!(log[0].attempts, 2);
+        assert_eq!(log[0].response_status, Some(204));
+    }
+
+    #[tokio::test]
+    async fn only_sends_to_public_addresses() {
+        let targets = PublicTargets::new(false);
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.5/hook",
+            "http://[::1]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "http://[fd00::1]/hook",
+        ] {
+            assert!(targets.check(url).await.is_err(), "{url}");
+        }
+        assert!(targets.check("https://93.184.216.34/hook").await.is_ok());
+        assert!(
+            PublicTargets::new(true)
+                .check("http://127.0.0.1:8080/hook")
+                .await
+                .is_ok()
+        );
+
+        let db = DbState::new(init_db(":memory:").await.unwrap())
+            .with_webhook_targets(Arc::new(targets));
+        let app = lib::router(db).layer(Extension(UserClaims {
+            id: "ana".to_string(),
+            email: "ana@example.com".to_string(),
+            name: "Ana".to_string(),
+            picture: String::new(),
+        }));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/webhook")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({"url": "http://127.0.0.1:9000/hook", "events": ["transaction.created"]})
+                    .to_string(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
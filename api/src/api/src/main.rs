@@ -3,7 +3,11 @@ use std::{
     sync::Arc,
 };
 
-use application::ApiState;
+use application::{
+    ApiState,
+    rate_limit::{self, RouteGroup},
+    webhooks::{PublicTargets, WebhookWorker},
+};
 use axum::{
     Router,
     extract::Request,
@@ -12,7 +16,7 @@ use axum::{
 };
 use lib::{
     AppError,
-    infra::{DbState, init_db},
+    infra::{DbState, Series, init_db, migrations, open_db},
 };
 use tower_http::services::ServeDir;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -41,10 +45,21 @@ async fn main() {
     dotenvy::dotenv().ok();
 
     let db_path = expect_env!("DATABASE_PATH");
+
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        return match command.as_str() {
+            "import-series" => import_series_command(&db_path, &args[1..]).await,
+            _ => migrations_command(&db_path, command).await,
+        };
+    }
+
     let conn = init_db(&db_path).await.expect("Initialize database");
-    let state = DbState::new(conn);
+    let webhook_targets = Arc::new(PublicTargets::from_env());
+    let state = DbState::new(conn).with_webhook_targets(webhook_targets.clone());
 
-    let api_state = ApiState::from_env().await;
+    let api_state = ApiState::from_env(&state).await;
+    WebhookWorker::new(state.webhooks.clone(), webhook_targets).spawn();
 
     let router = router(state, api_state)
         .layer(tower_http::trace::TraceLayer::new_for_http())
@@ -56,7 +71,10 @@ async fn main() {
         .await
         .expect("To bind to {addr:?}");
 
-    let server = axum::serve(listener, router);
+    let server = axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    );
 
     tracing::info!("Starting API in: {}", addr);
 
@@ -65,6 +83,78 @@ async fn main() {
     }
 }
 
+/// `api migrations-status` lists pending migrations, `api migrations-dry-run` also runs them
+/// inside a rolled back transaction. Neither starts the server.
+async fn migrations_command(db_path: &str, command: &str) {
+    let dry_run = match command {
+        "migrations-status" => false,
+        "migrations-dry-run" => true,
+        _ => panic!("unknown command: {command}"),
+    };
+
+    let conn = open_db(db_path).await.expect("Open database");
+
+    let result = conn
+        .call(move |conn| {
+            let status = migrations::status(conn)?;
+            if dry_run {
+                migrations::dry_run(conn)?;
+            }
+            Ok::<_, migrations::MigrationError>(status)
+        })
+        .await;
+
+    match result {
+        Ok(status) => {
+            println!("current version: {}", status.current);
+            println!("latest version: {}", status.latest);
+            for migration in status.pending {
+                println!("pending: {} ({})", migration.version, migration.name);
+            }
+            if dry_run {
+                println!("dry run succeeded, no changes were committed");
+            }
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `api import-series <selic|cdi|ipca|tr> <file>` stores a series exported by the Banco Central
+/// SGS service, in either its JSON or CSV format.
+async fn import_series_command(db_path: &str, args: &[String]) {
+    let [series, path] = args else {
+        panic!("usage: api import-series <selic|cdi|ipca|tr> <file>");
+    };
+
+    let series: Series = serde_json::from_value(serde_json::Value::String(series.clone()))
+        .unwrap_or_else(|_| panic!("unknown series: {series}"));
+
+    let contents = std::fs::read_to_string(path).expect("Read series file");
+
+    let points = match application::sgs::parse(series, &contents) {
+        Ok(points) => points,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let conn = init_db(db_path).await.expect("Initialize database");
+    let imported = DbState::new(conn)
+        .series
+        .import(&points)
+        .await
+        .expect("Import series");
+
+    println!(
+        "imported {imported} points of SGS series {}",
+        series.sgs_code()
+    );
+}
+
 async fn log_app_errors(request: Request, next: Next) -> Response {
     let response = next.run(request).await;
 
@@ -78,8 +168,15 @@ async fn log_app_errors(request: Request, next: Next) -> Response {
 pub fn router(state: DbState, api_state: ApiState) -> Router {
     let auth_layer = axum::middleware::from_fn_with_state(api_state.clone(), api::auth);
 
-    lib::router(state)
+    let writes_limit = axum::middleware::from_fn_with_state(
+        api_state.rate_limiter.group(RouteGroup::Writes),
+        rate_limit::limit,
+    );
+
+    // The last layer runs first, so the limits know the user.
+    lib::router(state.clone())
+        .layer(writes_limit)
         .layer(auth_layer)
-        .merge(api::router(api_state))
+        .merge(api::router(api_state, state))
         .fallback_service(ServeDir::new("public"))
 }
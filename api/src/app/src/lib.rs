@@ -1,27 +1,47 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
 
 use axum::{
-    Extension,
+    Extension, Router,
     body::Body,
     http::{HeaderMap, HeaderValue, Method, Request as AxumRequest, response::Parts},
 };
 use http_body_util::BodyExt;
-use lib::infra::{DbState, UserClaims};
+use lib::{
+    infra::{DbState, UserClaims},
+    repository::MemoryStore,
+};
 use tower::ServiceExt;
 use wasm_bindgen::{JsError, JsValue, prelude::wasm_bindgen};
 use web_sys::{Response, ResponseInit};
 
+mod storage;
+
+/// Rows live in memory and are written back to IndexedDB after every successful change.
+static STORE: OnceLock<Arc<MemoryStore>> = OnceLock::new();
+
 #[wasm_bindgen]
 pub async fn render(req: JsValue) -> JsResult<Response> {
     let req = serde_wasm_bindgen::from_value(req)?;
 
     let req = build_request(req).await?;
+    let mutates = !matches!(*req.method(), Method::GET | Method::HEAD);
+
+    let store = store().await?;
 
-    let res = lib::router(DbState::new())
+    // Same paths as the server behind the nginx `/api/` proxy.
+    let res = Router::new()
+        .nest("/api", lib::router(DbState::from_store(store.clone())))
         .layer(auth())
         .oneshot(req)
         .await?;
 
+    if mutates && res.status().is_success() {
+        storage::save(&store.snapshot()).await?;
+    }
+
     let (parts, body) = res.into_parts();
 
     let mut body = body.collect().await?.to_bytes().to_vec();
@@ -31,10 +51,23 @@ pub async fn render(req: JsValue) -> JsResult<Response> {
     Response::new_with_opt_u8_array_and_init(Some(body.as_mut()), &options?).map_err(to_err)
 }
 
+async fn store() -> JsResult<Arc<MemoryStore>> {
+    if let Some(store) = STORE.get() {
+        return Ok(store.clone());
+    }
+
+    let data = storage::load().await?.unwrap_or_default();
+
+    Ok(STORE
+        .get_or_init(|| Arc::new(MemoryStore::new(data)))
+        .clone())
+}
+
 fn auth() -> Extension<UserClaims> {
     let local = "local".to_string();
 
     let local_claims = UserClaims {
+        id: local.clone(),
         name: local.clone(),
         email: local.clone(),
         picture: local,
@@ -48,7 +81,7 @@ struct Request {
     method: String,
     url: String,
     headers: HashMap<String, String>,
-    form: Option<String>,
+    body: Option<String>,
 }
 
 fn build_options(parts: Parts) -> JsResult<ResponseInit> {
@@ -72,8 +105,8 @@ fn get_headers(headers: &HeaderMap<HeaderValue>) -> JsResult<JsValue> {
 async fn build_request(req: Request) -> JsResult<AxumRequest<Body>> {
     let method = Method::from_bytes(req.method.as_bytes())?;
 
-    let body = if let Some(form) = req.form {
-        Body::from(form)
+    let body = if let Some(body) = req.body {
+        Body::from(body)
     } else {
         Body::empty()
     };
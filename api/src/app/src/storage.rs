@@ -0,0 +1,144 @@
+use js_sys::Promise;
+use lib::repository::MemoryData;
+use serde_json::Value;
+use wasm_bindgen::{JsCast, JsError, JsValue, closure::Closure};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode, WorkerGlobalScope};
+
+use crate::{JsResult, to_err};
+
+const DB_NAME: &str = "pocket-planner";
+const DB_VERSION: u32 = 1;
+const STORE_NAME: &str = "snapshots";
+const SNAPSHOT_KEY: &str = "ledger";
+/// Saved along with the data. Snapshots without a version are from before ledgers, when rows
+/// were owned by the user id, or earlier by the email.
+const SNAPSHOT_VERSION: u64 = 2;
+
+#[derive(serde::Serialize)]
+struct Snapshot<'a> {
+    version: u64,
+    data: &'a MemoryData,
+}
+
+/// Reads the last saved snapshot, if the browser has one.
+pub async fn load() -> JsResult<Option<MemoryData>> {
+    let db = open().await?;
+
+    let store = db
+        .transaction_with_str(STORE_NAME)
+        .and_then(|tx| tx.object_store(STORE_NAME))
+        .map_err(to_err)?;
+
+    let value = request(&store.get(&SNAPSHOT_KEY.into()).map_err(to_err)?).await?;
+
+    let Some(json) = value.as_string() else {
+        return Ok(None);
+    };
+    let mut snapshot: Value = serde_json::from_str(&json)?;
+
+    let data = match snapshot.get("version").and_then(Value::as_u64) {
+        Some(SNAPSHOT_VERSION) => snapshot["data"].take(),
+        Some(version) => {
+            return Err(JsError::new(&format!("Unknown snapshot version {version}")));
+        }
+        None => {
+            move_to_ledgers(&mut snapshot);
+            snapshot
+        }
+    };
+
+    Ok(Some(serde_json::from_value(data)?))
+}
+
+/// Gives the rows of an unversioned snapshot to the ledger with the id of their owner.
+fn move_to_ledgers(snapshot: &mut Value) {
+    let tables = [
+        ("cards", ["userId", "userEmail"], "ledgerId"),
+        ("goals", ["userId", "userEmail"], "ledgerId"),
+        ("transactions", ["userId", "userEmail"], "ledgerId"),
+        ("categories", ["user_id", "user_email"], "ledger_id"),
+    ];
+
+    for (table, owners, ledger) in tables {
+        let rows = snapshot.get_mut(table).and_then(Value::as_array_mut);
+        for row in rows.into_iter().flatten().filter_map(Value::as_object_mut) {
+            for owner in owners {
+                if let Some(id) = row.remove(owner) {
+                    row.entry(ledger).or_insert(id);
+                }
+            }
+        }
+    }
+}
+
+pub async fn save(data: &MemoryData) -> JsResult<()> {
+    let json = serde_json::to_string(&Snapshot {
+        version: SNAPSHOT_VERSION,
+        data,
+    })?;
+    let db = open().await?;
+
+    let store = db
+        .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)
+        .and_then(|tx| tx.object_store(STORE_NAME))
+        .map_err(to_err)?;
+
+    request(
+        &store
+            .put_with_key(&json.into(), &SNAPSHOT_KEY.into())
+            .map_err(to_err)?,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn open() -> JsResult<IdbDatabase> {
+    let factory = js_sys::global()
+        .unchecked_into::<WorkerGlobalScope>()
+        .indexed_db()
+        .map_err(to_err)?
+        .ok_or_else(|| JsError::new("IndexedDB is not available"))?;
+
+    let open_request = factory.open_with_u32(DB_NAME, DB_VERSION).map_err(to_err)?;
+
+    let upgrade_request = open_request.clone();
+    let on_upgrade = Closure::once_into_js(move || {
+        if let Ok(db) = upgrade_request.result() {
+            let _ = db
+                .unchecked_into::<IdbDatabase>()
+                .create_object_store(STORE_NAME);
+        }
+    });
+    open_request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    Ok(request(&open_request).await?.unchecked_into())
+}
+
+/// Resolves with the request result once IndexedDB fires `success`.
+async fn request(request: &IdbRequest) -> JsResult<JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let success_request = request.clone();
+        let on_success = Closure::once_into_js(move || {
+            let result = success_request.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::NULL, &result);
+        });
+
+        let error_request = request.clone();
+        let on_error = Closure::once_into_js(move || {
+            let error = error_request
+                .error()
+                .ok()
+                .flatten()
+                .map(JsValue::from)
+                .unwrap_or(JsValue::UNDEFINED);
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+
+    JsFuture::from(promise).await.map_err(to_err)
+}
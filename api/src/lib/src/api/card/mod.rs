@@ -1,5 +1,5 @@
 use axum::{
-    Extension, Router,
+    Router,
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
@@ -9,7 +9,8 @@ use uuid::Uuid;
 
 use crate::{
     AppError, AppResult, Json, Response,
-    infra::{Card, CardType, CreateCard, DbState, UpdateCard, UserClaims},
+    api::ledger::ActiveLedger,
+    infra::{Card, CardDetails, CreateCard, DbState, UpdateCard},
 };
 
 pub fn router(state: DbState) -> Router {
@@ -22,192 +23,79 @@ pub fn router(state: DbState) -> Router {
         .with_state(state)
 }
 
-async fn list(
-    State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
-) -> Response<Vec<Card>> {
-    let email = claims.email;
-    let cards = state
-        .conn
-        .call(move |conn| {
-            let mut stmt = conn.prepare(
-                "SELECT id, user_email, name, card_type, credit_limit, current_balance
-                 FROM cards WHERE user_email = ?1",
-            )?;
-            let cards = stmt
-                .query_map([&email], |row| {
-                    Ok(Card {
-                        id: row.get(0)?,
-                        user_email: row.get(1)?,
-                        name: row.get(2)?,
-                        card_type: parse_card_type(row.get::<_, String>(3)?),
-                        credit_limit: row.get(4)?,
-                        current_balance: row.get(5)?,
-                    })
-                })?
-                .collect::<Result<Vec<_>, _>>()?;
-            Ok(cards)
-        })
-        .await?;
+async fn list(State(state): State<DbState>, ledger: ActiveLedger) -> Response<Vec<CardDetails>> {
+    let cards = state.cards.list(&ledger.id).await?;
 
     Ok(Json(cards))
 }
 
 async fn get(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Path(id): Path<String>,
-) -> Response<Card> {
-    let email = claims.email;
+) -> Response<CardDetails> {
     let card = state
-        .conn
-        .call(move |conn| {
-            conn.query_row(
-                "SELECT id, user_email, name, card_type, credit_limit, current_balance
-                 FROM cards WHERE id = ?1 AND user_email = ?2",
-                [&id, &email],
-                |row| {
-                    Ok(Card {
-                        id: row.get(0)?,
-                        user_email: row.get(1)?,
-                        name: row.get(2)?,
-                        card_type: parse_card_type(row.get::<_, String>(3)?),
-                        credit_limit: row.get(4)?,
-                        current_balance: row.get(5)?,
-                    })
-                },
-            )
-        })
-        .await?;
+        .cards
+        .get(&ledger.id, &id)
+        .await?
+        .ok_or_else(not_found)?;
 
     Ok(Json(card))
 }
 
 async fn create(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Json(input): Json<CreateCard>,
 ) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
     let card = Card {
         id: Uuid::now_v7().to_string(),
-        user_email: claims.email,
+        ledger_id: ledger.id,
         name: input.name,
         card_type: input.card_type,
         credit_limit: input.credit_limit,
         current_balance: 0,
+        limit_policy: input.limit_policy,
     };
 
-    let card_clone = card.clone();
-    state
-        .conn
-        .call(move |conn| {
-            conn.execute(
-                "INSERT INTO cards (id, user_email, name, card_type, credit_limit, current_balance)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                (
-                    &card_clone.id,
-                    &card_clone.user_email,
-                    &card_clone.name,
-                    card_type_to_str(&card_clone.card_type),
-                    &card_clone.credit_limit,
-                    &card_clone.current_balance,
-                ),
-            )?;
-            Ok(())
-        })
-        .await?;
-
-    Ok((StatusCode::CREATED, Json(card)))
+    state.cards.create(&card).await?;
+
+    Ok((StatusCode::CREATED, Json(card.with_usage(0))))
 }
 
 async fn update(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Path(id): Path<String>,
     Json(input): Json<UpdateCard>,
-) -> Response<Card> {
-    let email = claims.email;
+) -> Response<CardDetails> {
+    let ledger = ledger.editable()?;
+
     let card = state
-        .conn
-        .call(move |conn| {
-            // Update fields if provided
-            if let Some(name) = &input.name {
-                conn.execute(
-                    "UPDATE cards SET name = ?1 WHERE id = ?2 AND user_email = ?3",
-                    (&name, &id, &email),
-                )?;
-            }
-            if let Some(credit_limit) = &input.credit_limit {
-                conn.execute(
-                    "UPDATE cards SET credit_limit = ?1 WHERE id = ?2 AND user_email = ?3",
-                    (credit_limit, &id, &email),
-                )?;
-            }
-
-            // Fetch updated card
-            conn.query_row(
-                "SELECT id, user_email, name, card_type, credit_limit, current_balance
-                 FROM cards WHERE id = ?1 AND user_email = ?2",
-                [&id, &email],
-                |row| {
-                    Ok(Card {
-                        id: row.get(0)?,
-                        user_email: row.get(1)?,
-                        name: row.get(2)?,
-                        card_type: parse_card_type(row.get::<_, String>(3)?),
-                        credit_limit: row.get(4)?,
-                        current_balance: row.get(5)?,
-                    })
-                },
-            )
-        })
-        .await?;
+        .cards
+        .update(&ledger.id, &id, &input)
+        .await?
+        .ok_or_else(not_found)?;
 
     Ok(Json(card))
 }
 
 async fn delete(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Path(id): Path<String>,
 ) -> AppResult<impl IntoResponse> {
-    let email = claims.email;
-    let deleted = state
-        .conn
-        .call(move |conn| {
-            // Delete related transactions first
-            conn.execute(
-                "DELETE FROM transactions WHERE card_id = ?1 AND user_email = ?2",
-                [&id, &email],
-            )?;
-
-            // Delete the card
-            let rows = conn.execute(
-                "DELETE FROM cards WHERE id = ?1 AND user_email = ?2",
-                [&id, &email],
-            )?;
-            Ok(rows > 0)
-        })
-        .await?;
-
-    if !deleted {
-        return Err(AppError::Validation("Card not found".to_string()));
+    let ledger = ledger.editable()?;
+
+    if !state.cards.delete(&ledger.id, &id).await? {
+        return Err(not_found());
     }
 
     Ok(StatusCode::NO_CONTENT)
 }
 
-fn parse_card_type(s: String) -> CardType {
-    match s.as_str() {
-        "credit" => CardType::Credit,
-        "debit" => CardType::Debit,
-        _ => CardType::Debit,
-    }
-}
-
-fn card_type_to_str(t: &CardType) -> &'static str {
-    match t {
-        CardType::Credit => "credit",
-        CardType::Debit => "debit",
-    }
+fn not_found() -> AppError {
+    AppError::Validation("Card not found".to_string())
 }
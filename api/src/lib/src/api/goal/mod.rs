@@ -0,0 +1,203 @@
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing,
+};
+use uuid::Uuid;
+
+use crate::{
+    AppError, AppResult, Json, Response,
+    api::ledger::ActiveLedger,
+    infra::{CreateGoal, DbState, Goal, UpdateGoal},
+};
+
+pub fn router(state: DbState) -> Router {
+    Router::new()
+        .route("/", routing::get(list))
+        .route("/", routing::post(create))
+        .route("/{id}", routing::get(get))
+        .route("/{id}", routing::put(update))
+        .route("/{id}", routing::delete(delete))
+        .with_state(state)
+}
+
+async fn list(State(state): State<DbState>, ledger: ActiveLedger) -> Response<Vec<Goal>> {
+    Ok(Json(state.goals.list(&ledger.id).await?))
+}
+
+async fn get(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+) -> Response<Goal> {
+    let goal = state
+        .goals
+        .get(&ledger.id, &id)
+        .await?
+        .ok_or_else(not_found)?;
+
+    Ok(Json(goal))
+}
+
+async fn create(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Json(input): Json<CreateGoal>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
+    validate(
+        &state,
+        &ledger.id,
+        input.card_id.as_deref(),
+        input.emergency_months,
+    )
+    .await?;
+
+    if input.emergency_months.is_none() && input.target <= 0 {
+        return Err(AppError::Validation(
+            "A goal needs a positive target or a number of emergency months".to_string(),
+        ));
+    }
+
+    let goal = Goal {
+        id: Uuid::now_v7().to_string(),
+        ledger_id: ledger.id,
+        name: input.name,
+        target: input.target,
+        deadline: input.deadline,
+        card_id: input.card_id,
+        saved: input.saved,
+        monthly_contribution: input.monthly_contribution,
+        emergency_months: input.emergency_months,
+    };
+
+    state.goals.create(&goal).await?;
+
+    Ok((StatusCode::CREATED, Json(goal)))
+}
+
+async fn update(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateGoal>,
+) -> Response<Goal> {
+    let ledger = ledger.editable()?;
+
+    validate(
+        &state,
+        &ledger.id,
+        input.card_id.as_deref(),
+        input.emergency_months,
+    )
+    .await?;
+
+    let goal = state
+        .goals
+        .update(&ledger.id, &id, &input)
+        .await?
+        .ok_or_else(not_found)?;
+
+    Ok(Json(goal))
+}
+
+async fn delete(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
+    if !state.goals.delete(&ledger.id, &id).await? {
+        return Err(not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn validate(
+    state: &DbState,
+    ledger_id: &str,
+    card_id: Option<&str>,
+    emergency_months: Option<u8>,
+) -> AppResult<()> {
+    if emergency_months == Some(0) {
+        return Err(AppError::Validation(
+            "An emergency fund covers at least one month".to_string(),
+        ));
+    }
+
+    if let Some(card_id) = card_id
+        && state.cards.get(ledger_id, card_id).await?.is_none()
+    {
+        return Err(AppError::Validation("Card not found".to_string()));
+    }
+
+    Ok(())
+}
+
+fn not_found() -> AppError {
+    AppError::Validation("Goal not found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::{Value, json};
+
+    use crate::{
+        api::testing::{app, send},
+        repository::stores,
+    };
+
+    #[tokio::test]
+    async fn goals_follow_their_card() {
+        for state in stores().await {
+            let app = app(state);
+
+            let (_, card) = send(
+                &app,
+                "POST",
+                "/card",
+                json!({"name": "Savings", "cardType": "debit"}),
+            )
+            .await;
+            let card_id = card["id"].as_str().unwrap();
+
+            let (status, _) = send(
+                &app,
+                "POST",
+                "/goal",
+                json!({"name": "Trip", "deadline": "2027-01-01", "cardId": "missing", "target": 100}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (status, goal) = send(
+                &app,
+                "POST",
+                "/goal",
+                json!({"name": "Trip", "deadline": "2027-01-01", "cardId": card_id, "target": 500000}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+            let uri = format!("/goal/{}", goal["id"].as_str().unwrap());
+
+            let (_, goal) = send(&app, "PUT", &uri, json!({"saved": 120000})).await;
+            assert_eq!(goal["saved"], 120000);
+            assert_eq!(goal["target"], 500000);
+            assert_eq!(goal["deadline"], "2027-01-01");
+
+            send(&app, "DELETE", &format!("/card/{card_id}"), Value::Null).await;
+
+            let (_, goal) = send(&app, "GET", &uri, Value::Null).await;
+            assert_eq!(goal["cardId"], Value::Null);
+
+            let (status, _) = send(&app, "DELETE", &uri, Value::Null).await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+        }
+    }
+}
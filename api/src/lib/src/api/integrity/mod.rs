@@ -0,0 +1,22 @@
+use axum::{Router, extract::State, routing};
+
+use crate::{
+    Json, Response,
+    api::ledger::ActiveLedger,
+    infra::{DbState, IntegrityIssue},
+};
+
+pub fn router(state: DbState) -> Router {
+    Router::new()
+        .route("/", routing::get(check))
+        .with_state(state)
+}
+
+async fn check(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+) -> Response<Vec<IntegrityIssue>> {
+    let issues = state.integrity.check(&ledger.id).await?;
+
+    Ok(Json(issues))
+}
@@ -0,0 +1,363 @@
+use axum::{
+    Extension, Router,
+    extract::{FromRequestParts, Path, State},
+    http::{StatusCode, request::Parts},
+    response::IntoResponse,
+    routing,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::{
+    AppError, AppResult, Json, Response,
+    infra::{
+        CreateInvite, CreateLedger, DbState, Ledger, LedgerInvite, LedgerRole, MemberDetails,
+        Membership, PERSONAL_LEDGER, UpdateMember, UserClaims,
+    },
+};
+
+/// Header picking the ledger a request works on.
+pub const LEDGER_HEADER: &str = "x-ledger";
+
+const INVITE_TTL: Duration = Duration::days(7);
+
+pub fn router(state: DbState) -> Router {
+    Router::new()
+        .route("/", routing::get(list))
+        .route("/", routing::post(create))
+        .route("/{id}/members", routing::get(members))
+        .route("/{id}/members/{user_id}", routing::put(update_member))
+        .route("/{id}/members/{user_id}", routing::delete(remove_member))
+        .route("/{id}/invites", routing::post(invite))
+        .route("/invites/{token}", routing::post(accept_invite))
+        .with_state(state)
+}
+
+/// The ledger a request works on, picked with the `X-Ledger` header. Without it, the user's
+/// personal ledger, which has the same id as the user.
+#[derive(Debug, Clone)]
+pub struct ActiveLedger {
+    pub id: String,
+    pub role: LedgerRole,
+}
+
+impl ActiveLedger {
+    /// Rejects viewers, who can only read the ledger.
+    pub fn editable(self) -> AppResult<Self> {
+        if !self.role.can_edit() {
+            return Err(AppError::Forbidden(
+                "Viewers can't change the ledger".to_string(),
+            ));
+        }
+
+        Ok(self)
+    }
+}
+
+impl FromRequestParts<DbState> for ActiveLedger {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &DbState) -> AppResult<Self> {
+        let claims = parts
+            .extensions
+            .get::<UserClaims>()
+            .ok_or_else(|| AppError::Forbidden("Not logged in".to_string()))?;
+
+        let id = match parts.headers.get(LEDGER_HEADER) {
+            Some(value) => value
+                .to_str()
+                .map_err(|_| AppError::Validation("Invalid ledger header".to_string()))?
+                .to_string(),
+            None => claims.id.clone(),
+        };
+        let role = role_in(state, claims, &id).await?;
+
+        Ok(Self { id, role })
+    }
+}
+
+/// Everyone owns their personal ledger, other ledgers need a membership.
+async fn role_in(state: &DbState, claims: &UserClaims, ledger_id: &str) -> AppResult<LedgerRole> {
+    if ledger_id == claims.id {
+        return Ok(LedgerRole::Owner);
+    }
+
+    state
+        .ledgers
+        .role(ledger_id, &claims.id)
+        .await?
+        .ok_or_else(not_found)
+}
+
+/// Members are only managed on shared ledgers, and only by their owners.
+async fn owned_shared(state: &DbState, claims: &UserClaims, ledger_id: &str) -> AppResult<()> {
+    if ledger_id == claims.id {
+        return Err(not_shared());
+    }
+    if role_in(state, claims, ledger_id).await? != LedgerRole::Owner {
+        return Err(AppError::Forbidden(
+            "Only owners can manage the members".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The personal ledger first, then the shared ones.
+async fn list(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+) -> Response<Vec<Membership>> {
+    let created_at = state
+        .users
+        .get(&claims.id)
+        .await?
+        .map_or_else(Utc::now, |user| user.created_at);
+
+    let personal = Membership {
+        ledger: Ledger {
+            id: claims.id.clone(),
+            name: PERSONAL_LEDGER.to_string(),
+            created_at,
+        },
+        role: LedgerRole::Owner,
+        personal: true,
+    };
+    let shared = state.ledgers.list(&claims.id).await?;
+
+    Ok(Json([personal].into_iter().chain(shared).collect()))
+}
+
+async fn create(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+    Json(input): Json<CreateLedger>,
+) -> AppResult<impl IntoResponse> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Ledger name is required".to_string()));
+    }
+
+    let ledger = Ledger {
+        id: Uuid::now_v7().to_string(),
+        name: name.to_string(),
+        created_at: Utc::now(),
+    };
+
+    state.ledgers.create(&ledger, &claims.id).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(Membership {
+            ledger,
+            role: LedgerRole::Owner,
+            personal: false,
+        }),
+    ))
+}
+
+async fn members(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+    Path(id): Path<String>,
+) -> Response<Vec<MemberDetails>> {
+    if id == claims.id {
+        return Err(not_shared());
+    }
+    role_in(&state, &claims, &id).await?;
+
+    Ok(Json(state.ledgers.members(&id).await?))
+}
+
+async fn update_member(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+    Path((id, user_id)): Path<(String, String)>,
+    Json(input): Json<UpdateMember>,
+) -> AppResult<impl IntoResponse> {
+    owned_shared(&state, &claims, &id).await?;
+
+    if input.role != LedgerRole::Owner {
+        keep_an_owner(&state, &id, &user_id).await?;
+    }
+    if !state.ledgers.set_role(&id, &user_id, input.role).await? {
+        return Err(member_not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Owners remove members, and every member can leave.
+async fn remove_member(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+    Path((id, user_id)): Path<(String, String)>,
+) -> AppResult<impl IntoResponse> {
+    if user_id == claims.id {
+        if id == claims.id {
+            return Err(not_shared());
+        }
+        role_in(&state, &claims, &id).await?;
+    } else {
+        owned_shared(&state, &claims, &id).await?;
+    }
+
+    keep_an_owner(&state, &id, &user_id).await?;
+    if !state.ledgers.remove_member(&id, &user_id).await? {
+        return Err(member_not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Rejects changes that would leave the ledger without an owner.
+async fn keep_an_owner(state: &DbState, ledger_id: &str, user_id: &str) -> AppResult<()> {
+    let owners: Vec<String> = state
+        .ledgers
+        .members(ledger_id)
+        .await?
+        .into_iter()
+        .filter(|m| m.member.role == LedgerRole::Owner)
+        .map(|m| m.member.user_id)
+        .collect();
+
+    if owners == [user_id] {
+        return Err(AppError::Validation(
+            "A ledger needs at least one owner".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn invite(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+    Path(id): Path<String>,
+    Json(input): Json<CreateInvite>,
+) -> AppResult<impl IntoResponse> {
+    owned_shared(&state, &claims, &id).await?;
+
+    let invite = LedgerInvite {
+        token: Uuid::new_v4().simple().to_string(),
+        ledger_id: id,
+        role: input.role,
+        created_by: claims.id,
+        expires_at: Utc::now() + INVITE_TTL,
+        used_at: None,
+    };
+
+    state.ledgers.create_invite(&invite).await?;
+
+    Ok((StatusCode::CREATED, Json(invite)))
+}
+
+async fn accept_invite(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+    Path(token): Path<String>,
+) -> Response<Membership> {
+    let membership = state
+        .ledgers
+        .accept_invite(&token, &claims.id, Utc::now())
+        .await?
+        .ok_or_else(|| AppError::Validation("Invite is invalid or expired".to_string()))?;
+
+    Ok(Json(membership))
+}
+
+fn not_found() -> AppError {
+    AppError::Validation("Ledger not found".to_string())
+}
+
+fn not_shared() -> AppError {
+    AppError::Validation("Personal ledgers can't be shared".to_string())
+}
+
+fn member_not_found() -> AppError {
+    AppError::Validation("Member not found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::{Value, json};
+
+    use crate::{
+        api::testing::{app_as, send, send_in},
+        repository::stores,
+    };
+
+    #[tokio::test]
+    async fn shared_ledgers_follow_member_roles() {
+        for state in stores().await {
+            let ana = app_as(state.clone(), "user-1");
+            let bia = app_as(state.clone(), "user-2");
+            let carol = app_as(state, "user-3");
+            let card = json!({"name": "Groceries", "cardType": "debit"});
+
+            let (status, ledger) = send(&ana, "POST", "/ledger", json!({"name": "Home"})).await;
+            assert_eq!(status, StatusCode::CREATED);
+            let id = ledger["id"].as_str().unwrap();
+            let home = Some(id);
+
+            let (status, invite) = send(
+                &ana,
+                "POST",
+                &format!("/ledger/{id}/invites"),
+                json!({"role": "viewer"}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+            let accept = format!("/ledger/invites/{}", invite["token"].as_str().unwrap());
+
+            let (status, joined) = send(&bia, "POST", &accept, Value::Null).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(joined["role"], "viewer");
+            // Invites work once.
+            let (status, _) = send(&carol, "POST", &accept, Value::Null).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (_, ledgers) = send(&bia, "GET", "/ledger", Value::Null).await;
+            assert_eq!(ledgers[0]["personal"], true);
+            assert_eq!(ledgers[1]["name"], "Home");
+
+            let (status, _) = send_in(&ana, home, "POST", "/card", card.clone()).await;
+            assert_eq!(status, StatusCode::CREATED);
+            let (status, _) = send_in(&bia, home, "POST", "/card", card.clone()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+
+            let (_, cards) = send_in(&bia, home, "GET", "/card", Value::Null).await;
+            assert_eq!(cards.as_array().unwrap().len(), 1);
+            let (_, personal) = send(&bia, "GET", "/card", Value::Null).await;
+            assert!(personal.as_array().unwrap().is_empty());
+            let (status, _) = send_in(&carol, home, "GET", "/card", Value::Null).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let bia_member = format!("/ledger/{id}/members/user-2");
+            let (status, _) = send(&bia, "PUT", &bia_member, json!({"role": "owner"})).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            let (status, _) = send(&ana, "PUT", &bia_member, json!({"role": "editor"})).await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+            let (status, _) = send_in(&bia, home, "POST", "/card", card).await;
+            assert_eq!(status, StatusCode::CREATED);
+
+            // The only owner can neither step down nor leave.
+            let ana_member = format!("/ledger/{id}/members/user-1");
+            let (status, _) = send(&ana, "PUT", &ana_member, json!({"role": "viewer"})).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let (status, _) = send(&ana, "DELETE", &ana_member, Value::Null).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (_, members) =
+                send(&ana, "GET", &format!("/ledger/{id}/members"), Value::Null).await;
+            assert_eq!(members[0]["userId"], "user-1");
+            assert_eq!(members[1]["role"], "editor");
+
+            let (status, _) = send(&bia, "DELETE", &bia_member, Value::Null).await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+            let (status, _) = send_in(&bia, home, "GET", "/card", Value::Null).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+}
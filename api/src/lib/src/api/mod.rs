@@ -4,11 +4,79 @@ use crate::infra::DbState;
 
 pub mod card;
 pub mod category;
+pub mod goal;
+pub mod integrity;
+pub mod ledger;
+pub mod split;
 pub mod transaction;
+pub mod webhook;
 
 pub fn router(state: DbState) -> Router {
     Router::new()
         .nest("/card", card::router(state.clone()))
         .nest("/category", category::router(state.clone()))
-        .nest("/transaction", transaction::router(state))
+        .nest("/goal", goal::router(state.clone()))
+        .nest("/integrity", integrity::router(state.clone()))
+        .nest("/ledger", ledger::router(state.clone()))
+        .nest("/split", split::router(state.clone()))
+        .nest("/transaction", transaction::router(state.clone()))
+        .nest("/webhook", webhook::router(state))
+}
+
+/// Requests for the tests of each route module, sent through the whole router.
+#[cfg(test)]
+pub(crate) mod testing {
+    use axum::{
+        Extension, Router,
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::infra::{DbState, UserClaims};
+
+    pub fn app(state: DbState) -> Router {
+        app_as(state, "user-1")
+    }
+
+    pub fn app_as(state: DbState, user_id: &str) -> Router {
+        super::router(state).layer(Extension(UserClaims {
+            id: user_id.to_string(),
+            email: format!("{user_id}@test.com"),
+            name: "user".to_string(),
+            picture: String::new(),
+        }))
+    }
+
+    pub async fn send(app: &Router, method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        send_in(app, None, method, uri, body).await
+    }
+
+    pub async fn send_in(
+        app: &Router,
+        ledger: Option<&str>,
+        method: &str,
+        uri: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(ledger) = ledger {
+            request = request.header(super::ledger::LEDGER_HEADER, ledger);
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
 }
@@ -0,0 +1,330 @@
+use axum::{
+    Extension, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing,
+};
+use chrono::Utc;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    AppError, AppResult, Json, Response,
+    api::{ledger::ActiveLedger, webhook},
+    infra::{
+        Balances, Contact, CreateContact, CreateSettlement, CreateSplit, DbState, Participant,
+        Settlement, Split, TransactionType, UserClaims, WebhookEvent, split,
+    },
+};
+
+pub fn router(state: DbState) -> Router {
+    Router::new()
+        .route("/contacts", routing::get(contacts))
+        .route("/contacts", routing::post(create_contact))
+        .route("/transaction/{id}", routing::get(get))
+        .route("/transaction/{id}", routing::put(save))
+        .route("/transaction/{id}", routing::delete(delete))
+        .route("/settlements", routing::get(settlements))
+        .route("/settlements", routing::post(settle))
+        .route("/balances", routing::get(balances))
+        .with_state(state)
+}
+
+async fn contacts(State(state): State<DbState>, ledger: ActiveLedger) -> Response<Vec<Contact>> {
+    Ok(Json(state.splits.contacts(&ledger.id).await?))
+}
+
+async fn create_contact(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Json(input): Json<CreateContact>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Contact name is required".to_string()));
+    }
+
+    let contact = Contact {
+        id: Uuid::now_v7().to_string(),
+        ledger_id: ledger.id,
+        name: name.to_string(),
+        created_at: Utc::now(),
+    };
+
+    state.splits.create_contact(&contact).await?;
+
+    Ok((StatusCode::CREATED, Json(contact)))
+}
+
+async fn get(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+) -> Response<Split> {
+    let split = state
+        .splits
+        .get(&ledger.id, &id)
+        .await?
+        .ok_or_else(not_found)?;
+
+    Ok(Json(split))
+}
+
+/// Splits an expense, or changes how it is split.
+async fn save(
+    State(state): State<DbState>,
+    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+    Json(input): Json<CreateSplit>,
+) -> Response<Split> {
+    let ledger = ledger.editable()?;
+
+    let transaction = state
+        .transactions
+        .get(&ledger.id, &id)
+        .await?
+        .ok_or_else(|| AppError::Validation("Transaction not found".to_string()))?;
+    if transaction.transaction_type != TransactionType::Expense {
+        return Err(AppError::Validation(
+            "Only expenses can be split".to_string(),
+        ));
+    }
+
+    let paid_by = input.paid_by.unwrap_or(Participant::User(claims.id));
+    let mut participants = input.method.participants();
+    participants.push(&paid_by);
+    check_participants(&state, &ledger, &participants).await?;
+
+    let split = Split {
+        transaction_id: transaction.id.clone(),
+        ledger_id: ledger.id.clone(),
+        shares: input.method.shares(transaction.amount)?,
+        paid_by,
+    };
+
+    state.splits.save(&split).await?;
+
+    let data = json!({ "transaction": transaction, "split": split });
+    webhook::emit(&state, &ledger.id, WebhookEvent::TransactionUpdated, data).await;
+
+    Ok(Json(split))
+}
+
+async fn delete(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
+    if !state.splits.delete(&ledger.id, &id).await? {
+        return Err(not_found());
+    }
+
+    if let Some(transaction) = state.transactions.get(&ledger.id, &id).await? {
+        let data = json!({ "transaction": transaction, "split": null });
+        webhook::emit(&state, &ledger.id, WebhookEvent::TransactionUpdated, data).await;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn settlements(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+) -> Response<Vec<Settlement>> {
+    Ok(Json(state.splits.settlements(&ledger.id).await?))
+}
+
+/// Records that `from` paid `to` back.
+async fn settle(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Json(input): Json<CreateSettlement>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
+    if input.amount <= 0 {
+        return Err(AppError::Validation(
+            "Settlements need a positive amount".to_string(),
+        ));
+    }
+    if input.from == input.to {
+        return Err(AppError::Validation(
+            "Participants can't settle with themselves".to_string(),
+        ));
+    }
+    check_participants(&state, &ledger, &[&input.from, &input.to]).await?;
+
+    let settlement = Settlement {
+        id: Uuid::now_v7().to_string(),
+        ledger_id: ledger.id,
+        from: input.from,
+        to: input.to,
+        amount: input.amount,
+        date: input.date.unwrap_or_else(Utc::now),
+    };
+
+    state.splits.settle(&settlement).await?;
+
+    Ok((StatusCode::CREATED, Json(settlement)))
+}
+
+/// Who owes whom across every split and settlement of the ledger.
+async fn balances(State(state): State<DbState>, ledger: ActiveLedger) -> Response<Balances> {
+    let splits = state.splits.list(&ledger.id).await?;
+    let settlements = state.splits.settlements(&ledger.id).await?;
+
+    let balances = split::balances(&splits, &settlements);
+    let debts = split::simplify(&balances);
+
+    Ok(Json(Balances { balances, debts }))
+}
+
+/// Participants are members of the ledger, or its contacts.
+async fn check_participants(
+    state: &DbState,
+    ledger: &ActiveLedger,
+    participants: &[&Participant],
+) -> AppResult<()> {
+    let contacts = state.splits.contacts(&ledger.id).await?;
+
+    for participant in participants {
+        let known = match participant {
+            // The personal ledger shares its id with its only member.
+            Participant::User(id) => {
+                *id == ledger.id || state.ledgers.role(&ledger.id, id).await?.is_some()
+            }
+            Participant::Contact(id) => contacts.iter().any(|c| c.id == *id),
+        };
+
+        if !known {
+            return Err(AppError::Validation(format!(
+                "Unknown {} {}",
+                participant.kind(),
+                participant.id()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn not_found() -> AppError {
+    AppError::Validation("Split not found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::{Value, json};
+
+    use crate::{
+        api::testing::{app_as, send, send_in},
+        repository::stores,
+    };
+
+    #[tokio::test]
+    async fn split_expenses_balance_out() {
+        for state in stores().await {
+            let ana = app_as(state.clone(), "user-1");
+            let bia = app_as(state.clone(), "user-2");
+
+            let (_, ledger) = send(&ana, "POST", "/ledger", json!({"name": "Trip"})).await;
+            let id = ledger["id"].as_str().unwrap();
+            let trip = Some(id);
+            let (_, invite) = send(
+                &ana,
+                "POST",
+                &format!("/ledger/{id}/invites"),
+                json!({"role": "editor"}),
+            )
+            .await;
+            let accept = format!("/ledger/invites/{}", invite["token"].as_str().unwrap());
+            send(&bia, "POST", &accept, Value::Null).await;
+
+            let (status, carol) = send_in(
+                &ana,
+                trip,
+                "POST",
+                "/split/contacts",
+                json!({"name": "Carol"}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+            let carol = carol["id"].as_str().unwrap();
+
+            let (_, card) = send_in(
+                &ana,
+                trip,
+                "POST",
+                "/card",
+                json!({"name": "Visa", "cardType": "credit"}),
+            )
+            .await;
+            let (_, dinner) = send_in(
+                &ana,
+                trip,
+                "POST",
+                "/transaction",
+                json!({
+                    "cardId": card["id"],
+                    "categoryId": "1",
+                    "amount": 4000,
+                    "description": "dinner",
+                    "transactionType": "expense",
+                    "date": "2026-01-01T20:00:00Z",
+                }),
+            )
+            .await;
+            let uri = format!("/split/transaction/{}", dinner["id"].as_str().unwrap());
+
+            let equally = |contact: &str| {
+                json!({
+                    "method": "equal",
+                    "participants": [{"user": "user-1"}, {"user": "user-2"}, {"contact": contact}],
+                })
+            };
+            let (status, _) = send_in(&ana, trip, "PUT", &uri, equally("stranger")).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let (status, split) = send_in(&ana, trip, "PUT", &uri, equally(carol)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(split["paidBy"], json!({"user": "user-1"}));
+            assert_eq!(split["shares"][0]["amount"], 1334);
+
+            let (_, owed) = send_in(&bia, trip, "GET", "/split/balances", Value::Null).await;
+            assert_eq!(owed["balances"][0]["net"], 2666);
+            assert_eq!(
+                owed["debts"][0],
+                json!({"from": {"user": "user-2"}, "to": {"user": "user-1"}, "amount": 1333})
+            );
+
+            let (status, _) = send_in(
+                &bia,
+                trip,
+                "POST",
+                "/split/settlements",
+                json!({"from": {"user": "user-2"}, "to": {"user": "user-1"}, "amount": 1333}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+
+            let (_, owed) = send_in(&ana, trip, "GET", "/split/balances", Value::Null).await;
+            assert_eq!(
+                owed["debts"],
+                json!([{"from": {"contact": carol}, "to": {"user": "user-1"}, "amount": 1333}])
+            );
+
+            // The split goes away with its transaction.
+            let transaction = format!("/transaction/{}", dinner["id"].as_str().unwrap());
+            send_in(&ana, trip, "DELETE", &transaction, Value::Null).await;
+            let (status, _) = send_in(&ana, trip, "GET", &uri, Value::Null).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(state.splits.list(id).await.unwrap().is_empty());
+        }
+    }
+}
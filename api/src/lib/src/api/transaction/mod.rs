@@ -1,23 +1,25 @@
 use axum::{
-    Extension, Router,
+    Router,
     extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     routing,
 };
-use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 
 use crate::{
     AppError, AppResult, Json, Response,
-    infra::{CreateTransaction, DbState, Transaction, TransactionType, UserClaims},
+    api::{ledger::ActiveLedger, webhook},
+    infra::{Charge, CreateTransaction, DbState, LimitPolicy, Transaction, WebhookEvent},
 };
 
 pub fn router(state: DbState) -> Router {
     Router::new()
         .route("/", routing::get(list))
         .route("/", routing::post(create))
+        .route("/import", routing::post(import))
         .route("/{id}", routing::get(get))
         .route("/{id}", routing::delete(delete))
         .with_state(state)
@@ -31,199 +33,443 @@ struct TransactionListQuery {
 
 async fn list(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Query(query): Query<TransactionListQuery>,
 ) -> Response<Vec<Transaction>> {
-    let limit = query.limit.unwrap_or(50).clamp(1, 200) as i64;
-    let offset = query.offset.unwrap_or(0) as i64;
-    let email = claims.email;
-    let transactions = state
-        .conn
-        .call(move |conn| {
-            let mut stmt = conn.prepare(
-                "SELECT id, user_email, card_id, category_id, amount, description, transaction_type, date
-                 FROM transactions WHERE user_email = ?1 ORDER BY date DESC LIMIT ?2 OFFSET ?3",
-            )?;
-            let transactions = stmt
-                .query_map((&email, limit, offset), |row| {
-                    Ok(Transaction {
-                        id: row.get(0)?,
-                        user_email: row.get(1)?,
-                        card_id: row.get(2)?,
-                        category_id: row.get(3)?,
-                        amount: row.get(4)?,
-                        description: row.get(5)?,
-                        transaction_type: parse_transaction_type(row.get::<_, String>(6)?),
-                        date: parse_date(row.get::<_, String>(7)?),
-                    })
-                })?
-                .collect::<Result<Vec<_>, _>>()?;
-            Ok(transactions)
-        })
-        .await?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0);
+    let transactions = state.transactions.list(&ledger.id, limit, offset).await?;
 
     Ok(Json(transactions))
 }
 
 async fn get(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Path(id): Path<String>,
 ) -> Response<Transaction> {
-    let email = claims.email;
     let transaction = state
-        .conn
-        .call(move |conn| {
-            conn.query_row(
-                "SELECT id, user_email, card_id, category_id, amount, description, transaction_type, date
-                 FROM transactions WHERE id = ?1 AND user_email = ?2",
-                [&id, &email],
-                |row| {
-                    Ok(Transaction {
-                        id: row.get(0)?,
-                        user_email: row.get(1)?,
-                        card_id: row.get(2)?,
-                        category_id: row.get(3)?,
-                        amount: row.get(4)?,
-                        description: row.get(5)?,
-                        transaction_type: parse_transaction_type(row.get::<_, String>(6)?),
-                        date: parse_date(row.get::<_, String>(7)?),
-                    })
-                },
-            )
-        })
-        .await?;
+        .transactions
+        .get(&ledger.id, &id)
+        .await?
+        .ok_or_else(not_found)?;
 
     Ok(Json(transaction))
 }
 
+#[derive(Serialize)]
+struct CreatedTransaction {
+    #[serde(flatten)]
+    transaction: Transaction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+}
+
 async fn create(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Json(input): Json<CreateTransaction>,
 ) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
     let transaction = Transaction {
         id: Uuid::now_v7().to_string(),
-        user_email: claims.email.clone(),
-        card_id: input.card_id.clone(),
+        ledger_id: ledger.id.clone(),
+        card_id: input.card_id,
         category_id: input.category_id,
         amount: input.amount,
         description: input.description,
-        transaction_type: input.transaction_type.clone(),
+        transaction_type: input.transaction_type,
         date: input.date,
     };
 
-    let tx_clone = transaction.clone();
-    let email = claims.email;
-    let card_id = input.card_id;
-    let amount = input.amount;
-    let _tx_type = input.transaction_type;
-
-    state
-        .conn
-        .call(move |conn| {
-            // Verify the card belongs to the user
-            let card_exists: bool = conn.query_row(
-                "SELECT 1 FROM cards WHERE id = ?1 AND user_email = ?2",
-                [&card_id, &email],
-                |_| Ok(true),
-            ).unwrap_or(false);
-
-            if !card_exists {
-                return Err(rusqlite::Error::QueryReturnedNoRows);
-            }
+    // Verifies the card belongs to the ledger, and checks its limit, along with the insert.
+    let Charge {
+        card,
+        exceeds_limit,
+        inserted,
+    } = state
+        .transactions
+        .charge(&transaction)
+        .await?
+        .ok_or_else(|| AppError::Validation("Card not found".to_string()))?;
+
+    if !inserted {
+        return Err(AppError::Validation(
+            "Transaction exceeds the card credit limit".to_string(),
+        ));
+    }
+    let warning = (exceeds_limit && card.limit_policy == LimitPolicy::Warn)
+        .then(|| "Transaction exceeds the card credit limit".to_string());
+
+    webhook::emit(
+        &state,
+        &ledger.id,
+        WebhookEvent::TransactionCreated,
+        &transaction,
+    )
+    .await;
+    if exceeds_limit {
+        let data = json!({
+            "cardId": card.id,
+            "creditLimit": card.credit_limit,
+            "balance": card.current_balance + transaction.amount,
+            "transactionId": transaction.id,
+        });
+        webhook::emit(&state, &ledger.id, WebhookEvent::CreditLimitExceeded, data).await;
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedTransaction {
+            transaction,
+            warning,
+        }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ImportRequest {
+    transactions: Vec<CreateTransaction>,
+}
+
+#[derive(Serialize)]
+struct ImportResult {
+    imported: usize,
+}
 
-            // Insert transaction
-            conn.execute(
-                "INSERT INTO transactions (id, user_email, card_id, category_id, amount, description, transaction_type, date)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                (
-                    &tx_clone.id,
-                    &tx_clone.user_email,
-                    &tx_clone.card_id,
-                    &tx_clone.category_id,
-                    &tx_clone.amount,
-                    &tx_clone.description,
-                    transaction_type_to_str(&tx_clone.transaction_type),
-                    &tx_clone.date.to_rfc3339(),
-                ),
-            )?;
-
-            // Update card balance
-            let balance_change = amount;
-
-            conn.execute(
-                "UPDATE cards SET current_balance = current_balance + ?1 WHERE id = ?2",
-                (balance_change, &card_id),
-            )?;
-
-            Ok(())
+/// Records transactions exported from elsewhere, such as a bank statement. They already
+/// happened, so the cards' limit policies don't apply. Webhooks get a single `import.completed`
+/// event instead of one per transaction.
+async fn import(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Json(input): Json<ImportRequest>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = ledger.editable()?;
+
+    // Checks every card first, so an unknown one doesn't leave the import half done.
+    let mut cards: Vec<&str> = input
+        .transactions
+        .iter()
+        .map(|t| t.card_id.as_str())
+        .collect();
+    cards.sort_unstable();
+    cards.dedup();
+    for card_id in cards {
+        if state.cards.get(&ledger.id, card_id).await?.is_none() {
+            return Err(AppError::Validation(format!("Card {card_id} not found")));
+        }
+    }
+
+    let transactions: Vec<_> = input
+        .transactions
+        .into_iter()
+        .map(|input| Transaction {
+            id: Uuid::now_v7().to_string(),
+            ledger_id: ledger.id.clone(),
+            card_id: input.card_id,
+            category_id: input.category_id,
+            amount: input.amount,
+            description: input.description,
+            transaction_type: input.transaction_type,
+            date: input.date,
         })
-        .await?;
+        .collect();
 
-    Ok((StatusCode::CREATED, Json(transaction)))
+    state.transactions.create_many(&transactions).await?;
+
+    let result = ImportResult {
+        imported: transactions.len(),
+    };
+    webhook::emit(&state, &ledger.id, WebhookEvent::ImportCompleted, &result).await;
+
+    Ok((StatusCode::CREATED, Json(result)))
 }
 
 async fn delete(
     State(state): State<DbState>,
-    Extension(claims): Extension<UserClaims>,
+    ledger: ActiveLedger,
     Path(id): Path<String>,
 ) -> AppResult<impl IntoResponse> {
-    let email = claims.email;
-    let deleted = state
-        .conn
-        .call(move |conn| {
-            // Get transaction details to reverse balance
-            let (card_id, amount): (String, i64) = conn.query_row(
-                "SELECT card_id, amount FROM transactions WHERE id = ?1 AND user_email = ?2",
-                [&id, &email],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )?;
-
-            // Reverse balance change
-            let balance_change = -amount;
-
-            conn.execute(
-                "UPDATE cards SET current_balance = current_balance + ?1 WHERE id = ?2",
-                (balance_change, &card_id),
-            )?;
-
-            // Delete transaction
-            let rows = conn.execute(
-                "DELETE FROM transactions WHERE id = ?1 AND user_email = ?2",
-                [&id, &email],
-            )?;
-
-            Ok(rows > 0)
-        })
-        .await?;
+    let ledger = ledger.editable()?;
 
-    if !deleted {
-        return Err(AppError::Validation("Transaction not found".to_string()));
+    let transaction = state
+        .transactions
+        .get(&ledger.id, &id)
+        .await?
+        .ok_or_else(not_found)?;
+    if !state.transactions.delete(&ledger.id, &id).await? {
+        return Err(not_found());
     }
 
+    webhook::emit(
+        &state,
+        &ledger.id,
+        WebhookEvent::TransactionDeleted,
+        &transaction,
+    )
+    .await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
-fn parse_transaction_type(s: String) -> TransactionType {
-    match s.as_str() {
-        "expense" => TransactionType::Expense,
-        "income" => TransactionType::Income,
-        "payment" => TransactionType::Payment,
-        _ => TransactionType::Expense,
-    }
+fn not_found() -> AppError {
+    AppError::Validation("Transaction not found".to_string())
 }
 
-fn transaction_type_to_str(t: &TransactionType) -> &'static str {
-    match t {
-        TransactionType::Expense => "expense",
-        TransactionType::Income => "income",
-        TransactionType::Payment => "payment",
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::{Value, json};
+
+    use crate::infra::DbState;
+    use crate::{
+        api::testing::{app, send},
+        repository::stores,
+    };
+
+    #[tokio::test]
+    async fn transactions_respect_the_card_limit_policy() {
+        for state in stores().await {
+            let app = app(state);
+
+            let (status, card) = send(
+                &app,
+                "POST",
+                "/card",
+                json!({"name": "Visa", "cardType": "credit", "creditLimit": 1000, "limitPolicy": "reject"}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+            let card_id = card["id"].as_str().unwrap();
+
+            let transaction = |amount: i64| {
+                json!({
+                    "cardId": card_id,
+                    "categoryId": "1",
+                    "amount": amount,
+                    "description": "groceries",
+                    "transactionType": "expense",
+                    "date": "2024-01-01T12:00:00Z",
+                })
+            };
+
+            let (status, created) = send(&app, "POST", "/transaction", transaction(800)).await;
+            assert_eq!(status, StatusCode::CREATED);
+
+            let (status, _) = send(&app, "POST", "/transaction", transaction(300)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (_, card) = send(&app, "GET", &format!("/card/{card_id}"), Value::Null).await;
+            assert_eq!(card["currentBalance"], 800);
+            assert_eq!(card["creditUsage"]["available"], 200);
+            assert_eq!(card["creditUsage"]["utilizationPercent"], 80.0);
+
+            let uri = format!("/transaction/{}", created["id"].as_str().unwrap());
+            let (status, _) = send(&app, "DELETE", &uri, Value::Null).await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+
+            let (_, card) = send(&app, "GET", &format!("/card/{card_id}"), Value::Null).await;
+            assert_eq!(card["currentBalance"], 0);
+        }
     }
-}
 
-fn parse_date(s: String) -> DateTime<Utc> {
-    DateTime::parse_from_rfc3339(&s)
-        .map(|d| d.with_timezone(&Utc))
-        .unwrap_or_else(|_| Utc::now())
+    #[tokio::test]
+    async fn categories_in_use_are_kept() {
+        for state in stores().await {
+            let app = app(state);
+
+            let (_, card) = send(
+                &app,
+                "POST",
+                "/card",
+                json!({"name": "Visa", "cardType": "debit"}),
+            )
+            .await;
+            let (_, category) = send(&app, "POST", "/category", json!({"name": "Pets"})).await;
+            let (_, created) = send(
+                &app,
+                "POST",
+                "/transaction",
+                json!({
+                    "cardId": card["id"],
+                    "categoryId": category["id"],
+                    "amount": 300,
+                    "description": "vet",
+                    "transactionType": "expense",
+                    "date": "2024-01-01T12:00:00Z",
+                }),
+            )
+            .await;
+
+            let category = format!("/category/{}", category["id"].as_str().unwrap());
+            let (status, _) = send(&app, "DELETE", &category, Value::Null).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let transaction = format!("/transaction/{}", created["id"].as_str().unwrap());
+            send(&app, "DELETE", &transaction, Value::Null).await;
+            let (status, _) = send(&app, "DELETE", &category, Value::Null).await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+        }
+    }
+
+    #[tokio::test]
+    async fn other_policies_let_transactions_over_the_limit() {
+        for state in stores().await {
+            let app = app(state);
+
+            for (policy, warning) in [
+                ("warn", json!("Transaction exceeds the card credit limit")),
+                ("ignore", Value::Null),
+            ] {
+                let (_, card) = send(
+                    &app,
+                    "POST",
+                    "/card",
+                    json!({"name": "Visa", "cardType": "credit", "creditLimit": 1000, "limitPolicy": policy}),
+                )
+                .await;
+                let card_id = card["id"].as_str().unwrap();
+
+                let (status, created) = send(
+                    &app,
+                    "POST",
+                    "/transaction",
+                    json!({
+                        "cardId": card_id,
+                        "categoryId": "1",
+                        "amount": 1500,
+                        "description": "new phone",
+                        "transactionType": "expense",
+                        "date": "2024-01-01T12:00:00Z",
+                    }),
+                )
+                .await;
+                assert_eq!(status, StatusCode::CREATED, "{policy}");
+                assert_eq!(created["warning"], warning, "{policy}");
+
+                let (_, card) = send(&app, "GET", &format!("/card/{card_id}"), Value::Null).await;
+                assert_eq!(card["currentBalance"], 1500);
+                assert_eq!(card["creditUsage"]["available"], -500);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_transactions_stay_under_a_rejecting_limit() {
+        for state in stores().await {
+            let app = app(state);
+
+            let (_, card) = send(
+                &app,
+                "POST",
+                "/card",
+                json!({"name": "Visa", "cardType": "credit", "creditLimit": 1000, "limitPolicy": "reject"}),
+            )
+            .await;
+            let card_id = card["id"].as_str().unwrap();
+
+            let transaction = json!({
+                "cardId": card_id,
+                "categoryId": "1",
+                "amount": 600,
+                "description": "groceries",
+                "transactionType": "expense",
+                "date": "2024-01-01T12:00:00Z",
+            });
+            let (first, second) = tokio::join!(
+                send(&app, "POST", "/transaction", transaction.clone()),
+                send(&app, "POST", "/transaction", transaction),
+            );
+
+            let mut statuses = [first.0, second.0];
+            statuses.sort();
+            assert_eq!(statuses, [StatusCode::CREATED, StatusCode::BAD_REQUEST]);
+
+            let (_, card) = send(&app, "GET", &format!("/card/{card_id}"), Value::Null).await;
+            assert_eq!(card["currentBalance"], 600);
+        }
+    }
+
+    #[tokio::test]
+    async fn imports_insert_every_transaction() {
+        for state in stores().await {
+            let app = app(state);
+
+            let (_, card) = send(
+                &app,
+                "POST",
+                "/card",
+                json!({"name": "Visa", "cardType": "credit", "creditLimit": 1000, "limitPolicy": "reject"}),
+            )
+            .await;
+            let card_id = card["id"].as_str().unwrap();
+
+            let transactions: Vec<_> = (1..=3)
+                .map(|day| {
+                    json!({
+                        "cardId": card_id,
+                        "categoryId": "1",
+                        "amount": 500,
+                        "description": "statement",
+                        "transactionType": "expense",
+                        "date": format!("2024-01-0{day}T12:00:00Z"),
+                    })
+                })
+                .collect();
+            let (status, result) = send(
+                &app,
+                "POST",
+                "/transaction/import",
+                json!({"transactions": transactions}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+            assert_eq!(result["imported"], 3);
+
+            // Past the limit, which imports don't apply.
+            let (_, card) = send(&app, "GET", &format!("/card/{card_id}"), Value::Null).await;
+            assert_eq!(card["currentBalance"], 1500);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn failed_batches_insert_nothing() {
+        use crate::infra::{Transaction, TransactionType};
+
+        let state = DbState::new(crate::infra::init_db(":memory:").await.unwrap());
+        let app = app(state.clone());
+
+        let (_, card) = send(
+            &app,
+            "POST",
+            "/card",
+            json!({"name": "Visa", "cardType": "debit"}),
+        )
+        .await;
+        let card_id = card["id"].as_str().unwrap();
+
+        let transaction = |id: &str, card_id: &str| Transaction {
+            id: id.to_string(),
+            ledger_id: card["ledgerId"].as_str().unwrap().to_string(),
+            card_id: card_id.to_string(),
+            category_id: "1".to_string(),
+            amount: 500,
+            description: "statement".to_string(),
+            transaction_type: TransactionType::Expense,
+            date: chrono::Utc::now(),
+        };
+        let batch = [
+            transaction("first", card_id),
+            transaction("second", "missing"),
+        ];
+        assert!(state.transactions.create_many(&batch).await.is_err());
+
+        let (_, card) = send(&app, "GET", &format!("/card/{card_id}"), Value::Null).await;
+        assert_eq!(card["currentBalance"], 0);
+        let (_, transactions) = send(&app, "GET", "/transaction", Value::Null).await;
+        assert_eq!(transactions, json!([]));
+    }
 }
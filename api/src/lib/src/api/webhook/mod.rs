@@ -0,0 +1,225 @@
+use axum::{
+    Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    AppError, AppResult, Json, Response,
+    api::ledger::ActiveLedger,
+    infra::{CreateWebhook, DbState, Delivery, DeliveryStatus, LedgerRole, Webhook, WebhookEvent},
+};
+
+pub fn router(state: DbState) -> Router {
+    Router::new()
+        .route("/", routing::get(list))
+        .route("/", routing::post(create))
+        .route("/{id}", routing::delete(delete))
+        .route("/{id}/deliveries", routing::get(deliveries))
+        .with_state(state)
+}
+
+/// Queues `event` for every webhook of the ledger subscribed to it. The `api` binary delivers
+/// the queue, see its `application::webhooks`. The change that raised the event is already
+/// saved, so a failure here is logged instead of failing the request.
+pub(crate) async fn emit(
+    state: &DbState,
+    ledger_id: &str,
+    event: WebhookEvent,
+    data: impl Serialize,
+) {
+    if let Err(err) = enqueue(state, ledger_id, event, data).await {
+        tracing::error!(
+            ?err,
+            ledger = ledger_id,
+            ?event,
+            "failed to queue webhook event"
+        );
+    }
+}
+
+async fn enqueue(
+    state: &DbState,
+    ledger_id: &str,
+    event: WebhookEvent,
+    data: impl Serialize,
+) -> AppResult<()> {
+    let webhooks: Vec<Webhook> = state
+        .webhooks
+        .list(ledger_id)
+        .await?
+        .into_iter()
+        .filter(|w| w.events.contains(&event))
+        .collect();
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    // Retries resend the same payload, so receivers can drop the events they already got by id.
+    let payload = json!({
+        "id": Uuid::now_v7().to_string(),
+        "type": event,
+        "ledgerId": ledger_id,
+        "createdAt": now,
+        "data": data,
+    })
+    .to_string();
+
+    let deliveries: Vec<Delivery> = webhooks
+        .into_iter()
+        .map(|webhook| Delivery {
+            id: Uuid::now_v7().to_string(),
+            webhook_id: webhook.id,
+            event,
+            payload: payload.clone(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_attempt_at: None,
+            response_status: None,
+            error: None,
+            created_at: now,
+        })
+        .collect();
+
+    state.webhooks.enqueue(&deliveries).await
+}
+
+/// A webhook as listed, without its secret.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookView {
+    id: String,
+    url: String,
+    events: Vec<WebhookEvent>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<Webhook> for WebhookView {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            events: webhook.events,
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+/// Only returned when the webhook is created, the secret can't be shown again.
+#[derive(Serialize)]
+struct CreatedWebhook {
+    #[serde(flatten)]
+    details: WebhookView,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct DeliveriesQuery {
+    limit: Option<u32>,
+}
+
+async fn list(State(state): State<DbState>, ledger: ActiveLedger) -> Response<Vec<WebhookView>> {
+    let ledger = owned(ledger)?;
+    let webhooks = state.webhooks.list(&ledger.id).await?;
+
+    Ok(Json(webhooks.into_iter().map(WebhookView::from).collect()))
+}
+
+async fn create(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Json(input): Json<CreateWebhook>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = owned(ledger)?;
+
+    let url = input.url.trim();
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(AppError::Validation(
+            "Webhook URLs must be http or https".to_string(),
+        ));
+    }
+    state.webhook_targets.check(url).await?;
+    if input.events.is_empty() {
+        return Err(AppError::Validation(
+            "A webhook needs at least one event".to_string(),
+        ));
+    }
+
+    let mut events = input.events;
+    events.sort_unstable();
+    events.dedup();
+
+    let webhook = Webhook {
+        id: Uuid::now_v7().to_string(),
+        ledger_id: ledger.id,
+        url: url.to_string(),
+        secret: format!("whsec_{}", Uuid::new_v4().simple()),
+        events,
+        created_at: Utc::now(),
+    };
+
+    state.webhooks.create(&webhook).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedWebhook {
+            secret: webhook.secret.clone(),
+            details: webhook.into(),
+        }),
+    ))
+}
+
+async fn delete(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let ledger = owned(ledger)?;
+
+    if !state.webhooks.delete(&ledger.id, &id).await? {
+        return Err(not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The delivery log, newest first.
+async fn deliveries(
+    State(state): State<DbState>,
+    ledger: ActiveLedger,
+    Path(id): Path<String>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Response<Vec<Delivery>> {
+    let ledger = owned(ledger)?;
+
+    let webhooks = state.webhooks.list(&ledger.id).await?;
+    if !webhooks.iter().any(|w| w.id == id) {
+        return Err(not_found());
+    }
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    Ok(Json(state.webhooks.deliveries(&id, limit).await?))
+}
+
+/// Webhooks send the ledger's data elsewhere, so only its owners manage them.
+fn owned(ledger: ActiveLedger) -> AppResult<ActiveLedger> {
+    if ledger.role != LedgerRole::Owner {
+        return Err(AppError::Forbidden(
+            "Only owners can manage the webhooks".to_string(),
+        ));
+    }
+
+    Ok(ledger)
+}
+
+fn not_found() -> AppError {
+    AppError::Validation("Webhook not found".to_string())
+}
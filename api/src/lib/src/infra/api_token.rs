@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A personal access token for scripts, sent as a `Bearer` header. Only the SHA-256 hash of the
+/// token is stored, the token itself is shown once when it is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// What a token may do. None of them manage sessions or other tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    /// Every read-only request.
+    Read,
+    /// Creating and deleting transactions one by one.
+    Transactions,
+    /// Importing transactions in bulk.
+    Import,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiToken {
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+}
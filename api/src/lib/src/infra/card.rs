@@ -7,15 +7,26 @@ pub enum CardType {
     Debit,
 }
 
+/// What happens when a new transaction would push a credit card over its limit.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitPolicy {
+    #[default]
+    Ignore,
+    Warn,
+    Reject,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
     pub id: String,
-    pub user_email: String,
+    pub ledger_id: String,
     pub name: String,
     pub card_type: CardType,
     pub credit_limit: Option<i64>,
     pub current_balance: i64,
+    pub limit_policy: LimitPolicy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,6 +35,8 @@ pub struct CreateCard {
     pub name: String,
     pub card_type: CardType,
     pub credit_limit: Option<i64>,
+    #[serde(default)]
+    pub limit_policy: LimitPolicy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,4 +44,53 @@ pub struct CreateCard {
 pub struct UpdateCard {
     pub name: Option<String>,
     pub credit_limit: Option<i64>,
+    pub limit_policy: Option<LimitPolicy>,
+}
+
+/// Card as returned by the API, with the credit limit usage when it applies.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardDetails {
+    #[serde(flatten)]
+    pub card: Card,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credit_usage: Option<CreditUsage>,
+}
+
+/// All values in cents, except `utilization_percent`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreditUsage {
+    /// Amount already committed by transactions dated in the future (installments).
+    pub reserved: i64,
+    pub available: i64,
+    pub utilization_percent: f64,
+}
+
+impl Card {
+    /// `reserved` is the part of `current_balance` that comes from future-dated transactions.
+    pub fn with_usage(self, reserved: i64) -> CardDetails {
+        let credit_usage = match (&self.card_type, self.credit_limit) {
+            (CardType::Credit, Some(limit)) if limit > 0 => Some(CreditUsage {
+                reserved,
+                available: limit - self.current_balance,
+                utilization_percent: (self.current_balance as f64 / limit as f64 * 10000.).round()
+                    / 100.,
+            }),
+            _ => None,
+        };
+
+        CardDetails {
+            card: self,
+            credit_usage,
+        }
+    }
+
+    /// Whether adding `amount` to the balance would go over the credit limit.
+    pub fn exceeds_limit(&self, amount: i64) -> bool {
+        match (&self.card_type, self.credit_limit) {
+            (CardType::Credit, Some(limit)) => amount > 0 && self.current_balance + amount > limit,
+            _ => false,
+        }
+    }
 }
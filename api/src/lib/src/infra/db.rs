@@ -1,11 +1,27 @@
-use rusqlite::Connection;
 use tokio_rusqlite::Connection as AsyncConnection;
 
-pub async fn init_db(path: &str) -> Result<AsyncConnection, tokio_rusqlite::Error> {
+use super::migrations::{self, MigrationError};
+
+pub async fn init_db(path: &str) -> Result<AsyncConnection, InitDbError> {
+    let conn = open_db(path).await?;
+
+    conn.call(|conn| migrations::apply(conn).map(|_| ()))
+        .await?;
+
+    Ok(conn)
+}
+
+/// Opens the database without touching its schema.
+pub async fn open_db(path: &str) -> Result<AsyncConnection, tokio_rusqlite::Error> {
     let conn = AsyncConnection::open(path).await?;
 
     conn.call(|conn| {
-        run_migrations(conn)?;
+        // Litestream replicates from the WAL, and needs writers to wait on its checkpoint locks.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "busy_timeout", 5000)?;
+        // The bundled SQLite enforces foreign keys by default, but a system one may not, and the
+        // schema relies on them to cascade splits and keep categories in use.
+        conn.pragma_update(None, "foreign_keys", "ON")?;
         Ok(())
     })
     .await?;
@@ -13,57 +29,10 @@ pub async fn init_db(path: &str) -> Result<AsyncConnection, tokio_rusqlite::Erro
     Ok(conn)
 }
 
-fn run_migrations(conn: &Connection) -> rusqlite::Result<()> {
-    conn.execute_batch(
-        r#"
-        CREATE TABLE IF NOT EXISTS cards (
-            id TEXT PRIMARY KEY,
-            user_email TEXT NOT NULL,
-            name TEXT NOT NULL,
-            card_type TEXT NOT NULL CHECK (card_type IN ('credit', 'debit')),
-            credit_limit INTEGER,
-            current_balance INTEGER NOT NULL DEFAULT 0
-        );
-
-        CREATE INDEX IF NOT EXISTS idx_cards_user_email ON cards(user_email);
-
-        CREATE TABLE IF NOT EXISTS categories (
-            id TEXT PRIMARY KEY,
-            user_email TEXT,
-            name TEXT NOT NULL,
-            color TEXT
-        );
-
-        CREATE INDEX IF NOT EXISTS idx_categories_user_email ON categories(user_email);
-
-        CREATE TABLE IF NOT EXISTS transactions (
-            id TEXT PRIMARY KEY,
-            user_email TEXT NOT NULL,
-            card_id TEXT NOT NULL,
-            category_id TEXT NOT NULL,
-            amount INTEGER NOT NULL,
-            description TEXT NOT NULL,
-            transaction_type TEXT NOT NULL CHECK (transaction_type IN ('expense', 'income', 'payment')),
-            date TEXT NOT NULL,
-            FOREIGN KEY (card_id) REFERENCES cards(id),
-            FOREIGN KEY (category_id) REFERENCES categories(id)
-        );
-
-        CREATE INDEX IF NOT EXISTS idx_transactions_user_email ON transactions(user_email);
-        CREATE INDEX IF NOT EXISTS idx_transactions_card_id ON transactions(card_id);
-        CREATE INDEX IF NOT EXISTS idx_transactions_date ON transactions(date);
-
-        -- Insert default categories if they don't exist
-        INSERT OR IGNORE INTO categories (id, user_email, name, color) VALUES
-            ('1', NULL, 'Food & Dining', '#ef4444'),
-            ('2', NULL, 'Transportation', '#f97316'),
-            ('3', NULL, 'Shopping', '#eab308'),
-            ('4', NULL, 'Entertainment', '#22c55e'),
-            ('5', NULL, 'Bills & Utilities', '#3b82f6'),
-            ('6', NULL, 'Health', '#8b5cf6'),
-            ('7', NULL, 'Other', '#6b7280');
-        "#,
-    )?;
-
-    Ok(())
+#[derive(thiserror::Error, Debug)]
+pub enum InitDbError {
+    #[error("Failed to open database: {0}")]
+    Open(#[from] tokio_rusqlite::Error),
+    #[error("Failed to migrate database: {0}")]
+    Migration(#[from] tokio_rusqlite::Error<MigrationError>),
 }
@@ -0,0 +1,48 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Amount the user is saving towards by a deadline. Amounts in cents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Goal {
+    pub id: String,
+    pub ledger_id: String,
+    pub name: String,
+    /// Ignored when `emergency_months` is set, the target then follows the user's expenses.
+    pub target: i64,
+    pub deadline: NaiveDate,
+    /// Card or account the money is kept in.
+    pub card_id: Option<String>,
+    /// Already saved towards the goal.
+    pub saved: i64,
+    pub monthly_contribution: i64,
+    /// Makes this an emergency fund worth this many months of average expenses.
+    pub emergency_months: Option<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateGoal {
+    pub name: String,
+    #[serde(default)]
+    pub target: i64,
+    pub deadline: NaiveDate,
+    pub card_id: Option<String>,
+    #[serde(default)]
+    pub saved: i64,
+    #[serde(default)]
+    pub monthly_contribution: i64,
+    pub emergency_months: Option<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateGoal {
+    pub name: Option<String>,
+    pub target: Option<i64>,
+    pub deadline: Option<NaiveDate>,
+    pub card_id: Option<String>,
+    pub saved: Option<i64>,
+    pub monthly_contribution: Option<i64>,
+    pub emergency_months: Option<u8>,
+}
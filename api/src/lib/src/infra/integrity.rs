@@ -0,0 +1,29 @@
+use serde::Serialize;
+
+/// A stored value that the API would fail to read, or a reference to a missing row.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityIssue {
+    pub table: &'static str,
+    pub id: String,
+    pub column: &'static str,
+    pub value: Option<String>,
+    pub reason: String,
+}
+
+impl IntegrityIssue {
+    pub fn missing_reference(
+        table: &'static str,
+        id: &str,
+        column: &'static str,
+        value: String,
+    ) -> Self {
+        Self {
+            table,
+            id: id.to_string(),
+            column,
+            value: Some(value),
+            reason: "references a missing row".to_string(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Name of the ledger everyone has to themselves, which shares its id with their user.
+pub const PERSONAL_LEDGER: &str = "Personal";
+
+/// Owns cards, categories, transactions and goals, shared by its members.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ledger {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl LedgerRole {
+    pub fn can_edit(self) -> bool {
+        self >= LedgerRole::Editor
+    }
+}
+
+/// A ledger as one of its members sees it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Membership {
+    #[serde(flatten)]
+    pub ledger: Ledger,
+    pub role: LedgerRole,
+    pub personal: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerMember {
+    pub ledger_id: String,
+    pub user_id: String,
+    pub role: LedgerRole,
+    pub joined_at: DateTime<Utc>,
+}
+
+/// Member with the profile of their user, as listed to the other members.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberDetails {
+    #[serde(flatten)]
+    pub member: LedgerMember,
+    pub email: String,
+    pub name: String,
+    pub picture: String,
+}
+
+/// A single-use link joining whoever opens it to the ledger with `role`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerInvite {
+    pub token: String,
+    pub ledger_id: String,
+    pub role: LedgerRole,
+    pub created_by: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLedger {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInvite {
+    pub role: LedgerRole,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMember {
+    pub role: LedgerRole,
+}
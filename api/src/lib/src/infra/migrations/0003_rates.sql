@@ -0,0 +1,13 @@
+-- Annual rates used by the calculators, kept with their history. Values are decimal strings.
+CREATE TABLE rates (
+    kind TEXT NOT NULL CHECK (kind IN ('selic', 'cdi', 'treasury_spread', 'fgts')),
+    effective_from TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (kind, effective_from)
+);
+
+INSERT INTO rates (kind, effective_from, value) VALUES
+    ('selic', '2025-06-19', '15.0'),
+    ('cdi', '2025-06-19', '14.9'),
+    ('treasury_spread', '2025-06-19', '0.1736'),
+    ('fgts', '2025-06-19', '3.0');
@@ -0,0 +1,7 @@
+-- Historical Banco Central SGS series, imported from the files the SGS service exports.
+CREATE TABLE series_points (
+    series TEXT NOT NULL CHECK (series IN ('selic', 'cdi', 'ipca', 'tr')),
+    date TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (series, date)
+);
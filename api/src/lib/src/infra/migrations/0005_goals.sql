@@ -0,0 +1,15 @@
+-- Savings goals, amounts in cents like the rest of the user data.
+CREATE TABLE goals (
+    id TEXT PRIMARY KEY,
+    user_email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    target INTEGER NOT NULL,
+    deadline TEXT NOT NULL,
+    card_id TEXT,
+    saved INTEGER NOT NULL DEFAULT 0,
+    monthly_contribution INTEGER NOT NULL DEFAULT 0,
+    emergency_months INTEGER,
+    FOREIGN KEY (card_id) REFERENCES cards(id)
+);
+
+CREATE INDEX idx_goals_user_email ON goals(user_email);
@@ -0,0 +1,12 @@
+-- One row per login. Refreshing replaces refresh_jti, so an older refresh token showing up again
+-- means it was stolen and the whole session gets revoked.
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    user_email TEXT NOT NULL,
+    refresh_jti TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    revoked_at TEXT
+);
+
+CREATE INDEX idx_sessions_user_email ON sessions(user_email);
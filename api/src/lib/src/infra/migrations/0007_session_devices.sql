@@ -0,0 +1,6 @@
+-- Shown to the user so they can tell their sessions apart.
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
+ALTER TABLE sessions ADD COLUMN ip TEXT;
+ALTER TABLE sessions ADD COLUMN last_seen_at TEXT;
+
+UPDATE sessions SET last_seen_at = created_at;
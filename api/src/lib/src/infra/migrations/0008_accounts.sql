@@ -0,0 +1,19 @@
+-- Local logins for self-hosted instances without an identity provider.
+CREATE TABLE accounts (
+    email TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    password_hash TEXT NOT NULL,
+    verified_at TEXT,
+    created_at TEXT NOT NULL
+);
+
+-- Email verification and password reset links. Only a hash of the mailed token is kept.
+CREATE TABLE account_tokens (
+    token_hash TEXT PRIMARY KEY,
+    email TEXT NOT NULL REFERENCES accounts(email) ON DELETE CASCADE,
+    purpose TEXT NOT NULL CHECK (purpose IN ('verify', 'reset')),
+    expires_at TEXT NOT NULL,
+    used_at TEXT
+);
+
+CREATE INDEX idx_account_tokens_email ON account_tokens(email);
@@ -0,0 +1,68 @@
+-- Data was owned by the user's email, lost when the email changed. Users now get a stable id,
+-- and each login (issuer and subject) is linked to one user.
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    picture TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE user_identities (
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
+
+-- Every email owning something becomes a user with a random (version 4) UUID. Their identity
+-- provider logins get linked by email on the next login, local accounts right away.
+INSERT INTO users (id, email, name, created_at)
+SELECT
+    lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' ||
+        substr(lower(hex(randomblob(2))), 2) || '-' ||
+        substr('89ab', 1 + (abs(random()) % 4), 1) || substr(lower(hex(randomblob(2))), 2) || '-' ||
+        lower(hex(randomblob(6))),
+    email,
+    COALESCE((SELECT name FROM accounts WHERE accounts.email = owners.email), email),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+FROM (
+    SELECT user_email AS email FROM cards
+    UNION SELECT user_email FROM categories WHERE user_email IS NOT NULL
+    UNION SELECT user_email FROM transactions
+    UNION SELECT user_email FROM goals
+    UNION SELECT user_email FROM sessions
+    UNION SELECT email FROM accounts
+) AS owners;
+
+INSERT INTO user_identities (issuer, subject, user_id)
+SELECT 'local', email, id FROM users WHERE email IN (SELECT email FROM accounts);
+
+ALTER TABLE cards RENAME COLUMN user_email TO user_id;
+UPDATE cards SET user_id = (SELECT id FROM users WHERE email = cards.user_id);
+DROP INDEX IF EXISTS idx_cards_user_email;
+CREATE INDEX idx_cards_user_id ON cards(user_id);
+
+-- Default categories have no owner and stay that way.
+ALTER TABLE categories RENAME COLUMN user_email TO user_id;
+UPDATE categories SET user_id = (SELECT id FROM users WHERE email = categories.user_id)
+WHERE user_id IS NOT NULL;
+DROP INDEX IF EXISTS idx_categories_user_email;
+CREATE INDEX idx_categories_user_id ON categories(user_id);
+
+ALTER TABLE transactions RENAME COLUMN user_email TO user_id;
+UPDATE transactions SET user_id = (SELECT id FROM users WHERE email = transactions.user_id);
+DROP INDEX IF EXISTS idx_transactions_user_email;
+CREATE INDEX idx_transactions_user_id ON transactions(user_id);
+
+ALTER TABLE goals RENAME COLUMN user_email TO user_id;
+UPDATE goals SET user_id = (SELECT id FROM users WHERE email = goals.user_id);
+DROP INDEX IF EXISTS idx_goals_user_email;
+CREATE INDEX idx_goals_user_id ON goals(user_id);
+
+ALTER TABLE sessions RENAME COLUMN user_email TO user_id;
+UPDATE sessions SET user_id = (SELECT id FROM users WHERE email = sessions.user_id);
+DROP INDEX IF EXISTS idx_sessions_user_email;
+CREATE INDEX idx_sessions_user_id ON sessions(user_id);
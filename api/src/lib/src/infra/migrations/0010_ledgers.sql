@@ -0,0 +1,43 @@
+-- Cards, categories, transactions and goals belong to a ledger instead of a single user, so a
+-- household can share them. Everyone has an implicit personal ledger with the same id as their
+-- user, which is what every existing row already points to.
+CREATE TABLE ledgers (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE ledger_members (
+    ledger_id TEXT NOT NULL REFERENCES ledgers(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    joined_at TEXT NOT NULL,
+    PRIMARY KEY (ledger_id, user_id)
+);
+
+CREATE INDEX idx_ledger_members_user_id ON ledger_members(user_id);
+
+CREATE TABLE ledger_invites (
+    token TEXT PRIMARY KEY,
+    ledger_id TEXT NOT NULL REFERENCES ledgers(id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    created_by TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT
+);
+
+ALTER TABLE cards RENAME COLUMN user_id TO ledger_id;
+DROP INDEX IF EXISTS idx_cards_user_id;
+CREATE INDEX idx_cards_ledger_id ON cards(ledger_id);
+
+ALTER TABLE categories RENAME COLUMN user_id TO ledger_id;
+DROP INDEX IF EXISTS idx_categories_user_id;
+CREATE INDEX idx_categories_ledger_id ON categories(ledger_id);
+
+ALTER TABLE transactions RENAME COLUMN user_id TO ledger_id;
+DROP INDEX IF EXISTS idx_transactions_user_id;
+CREATE INDEX idx_transactions_ledger_id ON transactions(ledger_id);
+
+ALTER TABLE goals RENAME COLUMN user_id TO ledger_id;
+DROP INDEX IF EXISTS idx_goals_user_id;
+CREATE INDEX idx_goals_ledger_id ON goals(ledger_id);
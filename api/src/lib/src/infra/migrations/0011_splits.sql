@@ -0,0 +1,42 @@
+-- Expenses shared between the members of a ledger and contacts without an account.
+-- Participants are stored as a kind ('user' or 'contact') and an id.
+CREATE TABLE contacts (
+    id TEXT PRIMARY KEY,
+    ledger_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_contacts_ledger_id ON contacts(ledger_id);
+
+CREATE TABLE transaction_splits (
+    transaction_id TEXT PRIMARY KEY REFERENCES transactions(id) ON DELETE CASCADE,
+    ledger_id TEXT NOT NULL,
+    paid_by_kind TEXT NOT NULL CHECK (paid_by_kind IN ('user', 'contact')),
+    paid_by_id TEXT NOT NULL
+);
+
+CREATE INDEX idx_transaction_splits_ledger_id ON transaction_splits(ledger_id);
+
+-- Shares add up to the transaction amount, in cents.
+CREATE TABLE split_shares (
+    transaction_id TEXT NOT NULL REFERENCES transaction_splits(transaction_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    participant_kind TEXT NOT NULL CHECK (participant_kind IN ('user', 'contact')),
+    participant_id TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    PRIMARY KEY (transaction_id, position)
+);
+
+CREATE TABLE settlements (
+    id TEXT PRIMARY KEY,
+    ledger_id TEXT NOT NULL,
+    from_kind TEXT NOT NULL CHECK (from_kind IN ('user', 'contact')),
+    from_id TEXT NOT NULL,
+    to_kind TEXT NOT NULL CHECK (to_kind IN ('user', 'contact')),
+    to_id TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    date TEXT NOT NULL
+);
+
+CREATE INDEX idx_settlements_ledger_id ON settlements(ledger_id);
@@ -0,0 +1,14 @@
+-- Personal access tokens for scripts. Only a hash of each token is kept.
+CREATE TABLE api_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- Space separated `read`, `transactions` and `import`.
+    scopes TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    last_used_at TEXT,
+    revoked_at TEXT
+);
+
+CREATE INDEX idx_api_tokens_user ON api_tokens(user_id);
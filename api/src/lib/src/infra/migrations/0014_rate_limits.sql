@@ -0,0 +1,8 @@
+-- Token buckets of the rate limiter, shared by every instance when it is backed by SQLite.
+CREATE TABLE rate_limit_buckets (
+    key TEXT PRIMARY KEY,
+    tokens REAL NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_rate_limit_buckets_updated ON rate_limit_buckets(updated_at);
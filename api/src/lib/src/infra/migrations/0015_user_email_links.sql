@@ -0,0 +1,41 @@
+-- Identities used to be linked to whoever had their email, so anyone able to present an email
+-- got its owner's data. Only verified emails from trusted issuers link now, and the others get a
+-- user of their own, so emails are no longer unique. Only one user can have a verified one.
+--
+-- Dropping `users` would cascade to `user_identities`, so the identities are set aside first.
+CREATE TABLE users_copy AS SELECT * FROM users;
+CREATE TABLE user_identities_copy AS SELECT * FROM user_identities;
+
+DROP TABLE user_identities;
+DROP TABLE users;
+
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    email TEXT NOT NULL,
+    email_verified INTEGER NOT NULL DEFAULT 0,
+    name TEXT NOT NULL,
+    picture TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_users_email ON users(email);
+CREATE UNIQUE INDEX idx_users_verified_email ON users(email) WHERE email_verified;
+
+-- Existing emails were all trusted to link, keep it that way.
+INSERT INTO users (id, email, email_verified, name, picture, created_at)
+SELECT id, email, 1, name, picture, created_at FROM users_copy;
+
+CREATE TABLE user_identities (
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
+
+INSERT INTO user_identities (issuer, subject, user_id)
+SELECT issuer, subject, user_id FROM user_identities_copy;
+
+DROP TABLE user_identities_copy;
+DROP TABLE users_copy;
@@ -0,0 +1,319 @@
+//! Forward-only, numbered schema migrations.
+//!
+//! The applied version is tracked in SQLite's `user_version` header field, and each migration runs
+//! inside its own `BEGIN IMMEDIATE` transaction together with the `user_version` bump. That keeps
+//! every step atomic in the WAL, so Litestream only ever replicates fully applied versions.
+//!
+//! To change the schema, append a new [`Migration`] to [`MIGRATIONS`]; never edit an applied one.
+
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        up: |tx| tx.execute_batch(include_str!("0001_initial_schema.sql")),
+    },
+    Migration {
+        version: 2,
+        name: "card_limit_policy",
+        // Databases created before versioned migrations may already have this column.
+        up: |tx| {
+            add_column_if_missing(
+                tx,
+                "cards",
+                "limit_policy",
+                "TEXT NOT NULL DEFAULT 'ignore' CHECK (limit_policy IN ('ignore', 'warn', 'reject'))",
+            )
+        },
+    },
+    Migration {
+        version: 3,
+        name: "rates",
+        up: |tx| tx.execute_batch(include_str!("0003_rates.sql")),
+    },
+    Migration {
+        version: 4,
+        name: "series_points",
+        up: |tx| tx.execute_batch(include_str!("0004_series.sql")),
+    },
+    Migration {
+        version: 5,
+        name: "goals",
+        up: |tx| tx.execute_batch(include_str!("0005_goals.sql")),
+    },
+    Migration {
+        version: 6,
+        name: "sessions",
+        up: |tx| tx.execute_batch(include_str!("0006_sessions.sql")),
+    },
+    Migration {
+        version: 7,
+        name: "session_devices",
+        up: |tx| tx.execute_batch(include_str!("0007_session_devices.sql")),
+    },
+    Migration {
+        version: 8,
+        name: "accounts",
+        up: |tx| tx.execute_batch(include_str!("0008_accounts.sql")),
+    },
+    Migration {
+        version: 9,
+        name: "users",
+        up: |tx| tx.execute_batch(include_str!("0009_users.sql")),
+    },
+    Migration {
+        version: 10,
+        name: "ledgers",
+        up: |tx| tx.execute_batch(include_str!("0010_ledgers.sql")),
+    },
+    Migration {
+        version: 11,
+        name: "splits",
+        up: |tx| tx.execute_batch(include_str!("0011_splits.sql")),
+    },
+    Migration {
+        version: 12,
+        name: "api_tokens",
+        up: |tx| tx.execute_batch(include_str!("0012_api_tokens.sql")),
+    },
+    Migration {
+        version: 13,
+        name: "webhooks",
+        up: |tx| tx.execute_batch(include_str!("0013_webhooks.sql")),
+    },
+    Migration {
+        version: 14,
+        name: "rate_limits",
+        up: |tx| tx.execute_batch(include_str!("0014_rate_limits.sql")),
+    },
+    Migration {
+        version: 15,
+        name: "user_email_links",
+        up: |tx| tx.execute_batch(include_str!("0015_user_email_links.sql")),
+    },
+];
+
+pub struct MigrationStatus {
+    pub current: u32,
+    pub latest: u32,
+    pub pending: Vec<&'static Migration>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MigrationError {
+    #[error("Database is at version {found}, newer than the latest known migration {latest}")]
+    UnknownVersion { found: u32, latest: u32 },
+    #[error("Migration {version} ({name}) failed: {source}")]
+    Failed {
+        version: u32,
+        name: &'static str,
+        source: rusqlite::Error,
+    },
+    #[error("rusqlite error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub fn status(conn: &Connection) -> Result<MigrationStatus, MigrationError> {
+    let current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let latest = MIGRATIONS.last().map(|m| m.version).unwrap_or_default();
+
+    if current > latest {
+        return Err(MigrationError::UnknownVersion {
+            found: current,
+            latest,
+        });
+    }
+
+    Ok(MigrationStatus {
+        current,
+        latest,
+        pending: MIGRATIONS.iter().filter(|m| m.version > current).collect(),
+    })
+}
+
+/// Applies every pending migration, returning the ones that ran.
+pub fn apply(conn: &mut Connection) -> Result<Vec<&'static Migration>, MigrationError> {
+    run(conn, true)
+}
+
+/// Runs every pending migration and rolls each one back, to validate them against the current data.
+pub fn dry_run(conn: &mut Connection) -> Result<Vec<&'static Migration>, MigrationError> {
+    run(conn, false)
+}
+
+fn run(conn: &mut Connection, commit: bool) -> Result<Vec<&'static Migration>, MigrationError> {
+    let pending = status(conn)?.pending;
+
+    if !commit {
+        // Later migrations depend on earlier ones, so validate them all in a single transaction.
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        for migration in &pending {
+            migration.run(&tx)?;
+        }
+        tx.rollback()?;
+
+        return Ok(pending);
+    }
+
+    for migration in &pending {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        migration.run(&tx)?;
+        tx.commit()?;
+
+        tracing::info!(
+            "Applied migration {} ({})",
+            migration.version,
+            migration.name
+        );
+    }
+
+    Ok(pending)
+}
+
+impl Migration {
+    fn run(&self, tx: &Transaction) -> Result<(), MigrationError> {
+        (self.up)(tx)
+            .and_then(|_| tx.pragma_update(None, "user_version", self.version))
+            .map_err(|source| MigrationError::Failed {
+                version: self.version,
+                name: self.name,
+                source,
+            })
+    }
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{MIGRATIONS, apply, dry_run, status};
+
+    #[test]
+    fn applies_pending_migrations_once() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let applied = apply(&mut conn).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+
+        let status = status(&conn).unwrap();
+        assert_eq!(status.current, status.latest);
+        assert!(status.pending.is_empty());
+
+        assert!(apply(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn dry_run_leaves_the_database_untouched() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let pending = dry_run(&mut conn).unwrap();
+        assert_eq!(pending.len(), MIGRATIONS.len());
+
+        assert_eq!(status(&conn).unwrap().current, 0);
+    }
+
+    #[test]
+    fn upgrades_a_pre_migrations_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(include_str!("0001_initial_schema.sql"))
+            .unwrap();
+
+        apply(&mut conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO cards (id, ledger_id, name, card_type) VALUES ('c', 'e', 'n', 'credit')",
+            [],
+        )
+        .unwrap();
+        let policy: String = conn
+            .query_row("SELECT limit_policy FROM cards", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(policy, "ignore");
+    }
+
+    #[test]
+    fn moves_ownership_from_emails_to_user_ids() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version < 9) {
+            let tx = conn.transaction().unwrap();
+            migration.run(&tx).unwrap();
+            tx.commit().unwrap();
+        }
+
+        conn.execute_batch(
+            "INSERT INTO cards (id, user_email, name, card_type) VALUES ('c', 'ana@test.com', 'n', 'debit');
+             INSERT INTO categories (id, user_email, name) VALUES ('own', 'ana@test.com', 'Pets');
+             INSERT INTO transactions (id, user_email, card_id, category_id, amount, description, transaction_type, date)
+                 VALUES ('t', 'ana@test.com', 'c', 'own', 100, 'd', 'expense', '2026-01-01T00:00:00Z');
+             INSERT INTO accounts (email, name, password_hash, created_at)
+                 VALUES ('bia@test.com', 'Bia', 'hash', '2026-01-01T00:00:00Z');",
+        )
+        .unwrap();
+
+        apply(&mut conn).unwrap();
+
+        let user = |email: &str| -> String {
+            conn.query_row("SELECT id FROM users WHERE email = ?1", [email], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        let ana = user("ana@test.com");
+        assert_eq!(ana.len(), 36);
+        assert_ne!(ana, user("bia@test.com"));
+
+        for table in ["cards", "transactions"] {
+            let owner: String = conn
+                .query_row(&format!("SELECT ledger_id FROM {table}"), [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(owner, ana);
+        }
+        let default_owner: Option<String> = conn
+            .query_row(
+                "SELECT ledger_id FROM categories WHERE id = '1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(default_owner, None);
+
+        let linked: String = conn
+            .query_row(
+                "SELECT user_id FROM user_identities WHERE issuer = 'local' AND subject = 'bia@test.com'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(linked, user("bia@test.com"));
+    }
+}
@@ -1,31 +1,144 @@
 use std::sync::Arc;
-use tokio_rusqlite::Connection;
 
+use crate::repository::{
+    AccountRepository, ApiTokenRepository, CardRepository, CategoryRepository, GoalRepository,
+    IntegrityRepository, LedgerRepository, MemoryStore, RateLimitRepository, RateRepository,
+    SeriesRepository, SessionRepository, SplitRepository, TransactionRepository, UserRepository,
+    WebhookRepository,
+};
+
+pub mod account;
+pub mod api_token;
 pub mod card;
 pub mod category;
+#[cfg(feature = "sqlite")]
 pub mod db;
+pub mod goal;
+pub mod integrity;
+pub mod ledger;
+#[cfg(feature = "sqlite")]
+pub mod migrations;
+pub mod rate;
+pub mod rate_limit;
+pub mod series;
+pub mod session;
+pub mod split;
+#[cfg(feature = "sqlite")]
+pub mod sql;
 pub mod transaction;
+pub mod user;
+pub mod webhook;
 
-pub use card::{Card, CardType, CreateCard, UpdateCard};
+pub use account::{Account, AccountToken, TokenPurpose};
+pub use api_token::{ApiToken, CreateApiToken, TokenScope};
+pub use card::{Card, CardDetails, CardType, CreateCard, CreditUsage, LimitPolicy, UpdateCard};
 pub use category::{Category, CreateCategory};
-pub use db::init_db;
-pub use transaction::{CreateTransaction, Transaction, TransactionType};
+#[cfg(feature = "sqlite")]
+pub use db::{init_db, open_db};
+pub use goal::{CreateGoal, Goal, UpdateGoal};
+pub use integrity::IntegrityIssue;
+pub use ledger::{
+    CreateInvite, CreateLedger, Ledger, LedgerInvite, LedgerMember, LedgerRole, MemberDetails,
+    Membership, PERSONAL_LEDGER, UpdateMember,
+};
+pub use rate::{Rate, RateKind};
+pub use rate_limit::{Bucket, RateLimit};
+pub use series::{Periodicity, Series, SeriesPoint};
+pub use session::{Device, Session};
+pub use split::{
+    Balance, Balances, Contact, CreateContact, CreateSettlement, CreateSplit, Debt, Participant,
+    PercentShare, Settlement, Share, Split, SplitMethod,
+};
+#[cfg(feature = "sqlite")]
+pub use sql::{Date, DecimalText, Timestamp};
+pub use transaction::{Charge, CreateTransaction, Transaction, TransactionType};
+pub use user::{Identity, LOCAL_ISSUER, User, UserIdentity};
+pub use webhook::{
+    AnyTarget, CreateWebhook, Delivery, DeliveryAttempt, DeliveryStatus, MAX_DELIVERY_ATTEMPTS,
+    Webhook, WebhookEvent, WebhookTargets,
+};
 
 #[derive(Clone)]
 pub struct DbState {
-    pub conn: Arc<Connection>,
+    pub cards: Arc<dyn CardRepository>,
+    pub categories: Arc<dyn CategoryRepository>,
+    pub transactions: Arc<dyn TransactionRepository>,
+    pub integrity: Arc<dyn IntegrityRepository>,
+    pub rates: Arc<dyn RateRepository>,
+    pub series: Arc<dyn SeriesRepository>,
+    pub goals: Arc<dyn GoalRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub accounts: Arc<dyn AccountRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub ledgers: Arc<dyn LedgerRepository>,
+    pub splits: Arc<dyn SplitRepository>,
+    pub api_tokens: Arc<dyn ApiTokenRepository>,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub rate_limits: Arc<dyn RateLimitRepository>,
+    /// Checks the URLs of new webhooks, [`AnyTarget`] unless set with
+    /// [`DbState::with_webhook_targets`].
+    pub webhook_targets: Arc<dyn WebhookTargets>,
 }
 
 impl DbState {
-    pub fn new(conn: Connection) -> Self {
+    #[cfg(feature = "sqlite")]
+    pub fn new(conn: tokio_rusqlite::Connection) -> Self {
+        Self::from_store(Arc::new(crate::repository::SqliteStore::new(conn)))
+    }
+
+    pub fn in_memory() -> Self {
+        Self::from_store(Arc::new(MemoryStore::default()))
+    }
+
+    pub fn from_store<S>(store: Arc<S>) -> Self
+    where
+        S: CardRepository
+            + CategoryRepository
+            + TransactionRepository
+            + IntegrityRepository
+            + RateRepository
+            + SeriesRepository
+            + GoalRepository
+            + SessionRepository
+            + AccountRepository
+            + UserRepository
+            + LedgerRepository
+            + SplitRepository
+            + ApiTokenRepository
+            + WebhookRepository
+            + RateLimitRepository
+            + 'static,
+    {
         Self {
-            conn: Arc::new(conn),
+            cards: store.clone(),
+            categories: store.clone(),
+            transactions: store.clone(),
+            integrity: store.clone(),
+            rates: store.clone(),
+            series: store.clone(),
+            goals: store.clone(),
+            sessions: store.clone(),
+            accounts: store.clone(),
+            users: store.clone(),
+            ledgers: store.clone(),
+            splits: store.clone(),
+            api_tokens: store.clone(),
+            webhooks: store.clone(),
+            rate_limits: store,
+            webhook_targets: Arc::new(AnyTarget),
         }
     }
+
+    pub fn with_webhook_targets(mut self, targets: Arc<dyn WebhookTargets>) -> Self {
+        self.webhook_targets = targets;
+        self
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct UserClaims {
+    /// Stable id of the user, kept when the email changes.
+    pub id: String,
     pub email: String,
     pub name: String,
     pub picture: String,
@@ -0,0 +1,50 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateKind {
+    Selic,
+    Cdi,
+    /// Added to the SELIC for the Tesouro Selic yield.
+    TreasurySpread,
+    Fgts,
+}
+
+/// Annual rate, in percent, in effect from `effective_from` until the next one of the same kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rate {
+    pub kind: RateKind,
+    pub value: Decimal,
+    pub effective_from: NaiveDate,
+}
+
+impl RateKind {
+    pub const ALL: [RateKind; 4] = [
+        RateKind::Selic,
+        RateKind::Cdi,
+        RateKind::TreasurySpread,
+        RateKind::Fgts,
+    ];
+}
+
+/// Same values the `rates` migration seeds.
+pub fn default_rates() -> Vec<Rate> {
+    let effective_from = NaiveDate::from_ymd_opt(2025, 6, 19).expect("valid date");
+
+    [
+        (RateKind::Selic, Decimal::new(150, 1)),
+        (RateKind::Cdi, Decimal::new(149, 1)),
+        (RateKind::TreasurySpread, Decimal::new(1736, 4)),
+        (RateKind::Fgts, Decimal::new(30, 1)),
+    ]
+    .into_iter()
+    .map(|(kind, value)| Rate {
+        kind,
+        value,
+        effective_from,
+    })
+    .collect()
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Allows `burst` requests at once, refilled evenly over `period`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub period: Duration,
+}
+
+/// A token bucket, full when it isn't stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bucket {
+    pub tokens: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RateLimit {
+    pub fn full(&self, now: DateTime<Utc>) -> Bucket {
+        Bucket {
+            tokens: self.burst as f64,
+            updated_at: now,
+        }
+    }
+
+    fn refill_per_second(&self) -> f64 {
+        self.burst as f64 / self.period.as_seconds_f64()
+    }
+}
+
+impl Bucket {
+    /// Takes a token after refilling the bucket up to `now`. Without one left, returns how long
+    /// until there is.
+    pub fn take(&mut self, limit: &RateLimit, now: DateTime<Utc>) -> Result<(), Duration> {
+        let elapsed = (now - self.updated_at).as_seconds_f64().max(0.);
+        self.tokens = (self.tokens + elapsed * limit.refill_per_second()).min(limit.burst as f64);
+        self.updated_at = now;
+
+        if self.tokens >= 1. {
+            self.tokens -= 1.;
+            return Ok(());
+        }
+
+        let wait = (1. - self.tokens) / limit.refill_per_second();
+        Err(Duration::milliseconds((wait * 1000.).ceil() as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_refill_over_the_period() {
+        let limit = RateLimit {
+            burst: 2,
+            period: Duration::seconds(60),
+        };
+        let now = Utc::now();
+        let mut bucket = limit.full(now);
+
+        assert!(bucket.take(&limit, now).is_ok());
+        assert!(bucket.take(&limit, now).is_ok());
+        assert_eq!(bucket.take(&limit, now), Err(Duration::seconds(30)));
+
+        let later = now + Duration::seconds(30);
+        assert!(bucket.take(&limit, later).is_ok());
+        assert!(bucket.take(&limit, later).is_err());
+
+        // Waiting longer than the period doesn't save more than the burst.
+        let much_later = later + Duration::hours(1);
+        assert!(bucket.take(&limit, much_later).is_ok());
+        assert!(bucket.take(&limit, much_later).is_ok());
+        assert!(bucket.take(&limit, much_later).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Banco Central SGS series the calculators can backtest with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Series {
+    /// SGS 11, percent per business day.
+    Selic,
+    /// SGS 12, percent per business day.
+    Cdi,
+    /// SGS 433, percent per month.
+    Ipca,
+    /// SGS 7811, percent per month.
+    Tr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Periodicity {
+    Daily,
+    Monthly,
+}
+
+impl Series {
+    pub fn sgs_code(self) -> u32 {
+        match self {
+            Series::Selic => 11,
+            Series::Cdi => 12,
+            Series::Ipca => 433,
+            Series::Tr => 7811,
+        }
+    }
+
+    pub fn periodicity(self) -> Periodicity {
+        match self {
+            Series::Selic | Series::Cdi => Periodicity::Daily,
+            Series::Ipca | Series::Tr => Periodicity::Monthly,
+        }
+    }
+}
+
+/// Value of a series on a date, monthly series are dated on the first day of the month.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesPoint {
+    pub series: Series,
+    pub date: NaiveDate,
+    pub value: Decimal,
+}
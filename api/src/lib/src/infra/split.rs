@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppResult};
+
+/// Someone sharing expenses in a ledger: one of its members, or a contact without an account.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Participant {
+    User(String),
+    Contact(String),
+}
+
+impl Participant {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Contact(_) => "contact",
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            Self::User(id) | Self::Contact(id) => id,
+        }
+    }
+
+    pub fn from_parts(kind: &str, id: String) -> Option<Self> {
+        match kind {
+            "user" => Some(Self::User(id)),
+            "contact" => Some(Self::Contact(id)),
+            _ => None,
+        }
+    }
+}
+
+/// A named person in the ledger who doesn't use the app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub id: String,
+    pub ledger_id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateContact {
+    pub name: String,
+}
+
+/// An expense paid by one participant on behalf of several. The shares add up to the
+/// transaction amount, in cents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Split {
+    pub transaction_id: String,
+    pub ledger_id: String,
+    pub paid_by: Participant,
+    pub shares: Vec<Share>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Share {
+    pub participant: Participant,
+    pub amount: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PercentShare {
+    pub participant: Participant,
+    pub percent: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum SplitMethod {
+    Equal { participants: Vec<Participant> },
+    Percent { shares: Vec<PercentShare> },
+    Exact { shares: Vec<Share> },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSplit {
+    /// Whoever is splitting the transaction by default.
+    pub paid_by: Option<Participant>,
+    #[serde(flatten)]
+    pub method: SplitMethod,
+}
+
+/// Money one participant paid back to another, outside of any card.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Settlement {
+    pub id: String,
+    pub ledger_id: String,
+    pub from: Participant,
+    pub to: Participant,
+    pub amount: i64,
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSettlement {
+    pub from: Participant,
+    pub to: Participant,
+    pub amount: i64,
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// Positive when the participant is owed money, negative when they owe it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Balance {
+    pub participant: Participant,
+    pub net: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Debt {
+    pub from: Participant,
+    pub to: Participant,
+    pub amount: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Balances {
+    pub balances: Vec<Balance>,
+    /// The fewest payments that settle every balance.
+    pub debts: Vec<Debt>,
+}
+
+impl SplitMethod {
+    pub fn participants(&self) -> Vec<&Participant> {
+        match self {
+            Self::Equal { participants } => participants.iter().collect(),
+            Self::Percent { shares } => shares.iter().map(|s| &s.participant).collect(),
+            Self::Exact { shares } => shares.iter().map(|s| &s.participant).collect(),
+        }
+    }
+
+    /// Splits `amount` cents so the shares always add up to it. Cents that don't divide evenly
+    /// go to the largest remainders, the first participants on a tie.
+    pub fn shares(&self, amount: i64) -> AppResult<Vec<Share>> {
+        let participants = self.participants();
+        if participants.is_empty() {
+            return Err(invalid("A split needs at least one participant"));
+        }
+        if (1..participants.len()).any(|i| participants[..i].contains(&participants[i])) {
+            return Err(invalid("Each participant can only have one share"));
+        }
+
+        let exact: Vec<Decimal> = match self {
+            Self::Equal { participants } => {
+                let count = Decimal::from(participants.len());
+                vec![Decimal::from(amount) / count; participants.len()]
+            }
+            Self::Percent { shares } => {
+                if shares.iter().any(|s| s.percent.is_sign_negative()) {
+                    return Err(invalid("Percentages can't be negative"));
+                }
+                if shares.iter().map(|s| s.percent).sum::<Decimal>() != Decimal::ONE_HUNDRED {
+                    return Err(invalid("Percentages must add up to 100"));
+                }
+                shares
+                    .iter()
+                    .map(|s| Decimal::from(amount) * s.percent / Decimal::ONE_HUNDRED)
+                    .collect()
+            }
+            Self::Exact { shares } => {
+                if shares.iter().any(|s| s.amount < 0) {
+                    return Err(invalid("Shares can't be negative"));
+                }
+                if shares.iter().map(|s| s.amount).sum::<i64>() != amount {
+                    return Err(invalid("Shares must add up to the transaction amount"));
+                }
+                return Ok(shares.clone());
+            }
+        };
+
+        let mut amounts: Vec<i64> = exact
+            .iter()
+            .map(|share| share.floor().to_i64().unwrap_or_default())
+            .collect();
+        let mut by_remainder: Vec<usize> = (0..exact.len()).collect();
+        by_remainder.sort_by_key(|&i| std::cmp::Reverse(exact[i] - exact[i].floor()));
+
+        let missing = amount - amounts.iter().sum::<i64>();
+        for &i in by_remainder.iter().cycle().take(missing.max(0) as usize) {
+            amounts[i] += 1;
+        }
+
+        Ok(participants
+            .into_iter()
+            .zip(amounts)
+            .map(|(participant, amount)| Share {
+                participant: participant.clone(),
+                amount,
+            })
+            .collect())
+    }
+}
+
+/// Nets what everyone paid against what they owe, leaving out the participants already even.
+pub fn balances(splits: &[Split], settlements: &[Settlement]) -> Vec<Balance> {
+    let mut net: BTreeMap<&Participant, i64> = BTreeMap::new();
+
+    for split in splits {
+        for share in &split.shares {
+            *net.entry(&split.paid_by).or_default() += share.amount;
+            *net.entry(&share.participant).or_default() -= share.amount;
+        }
+    }
+    for settlement in settlements {
+        *net.entry(&settlement.from).or_default() += settlement.amount;
+        *net.entry(&settlement.to).or_default() -= settlement.amount;
+    }
+
+    net.into_iter()
+        .filter(|(_, net)| *net != 0)
+        .map(|(participant, net)| Balance {
+            participant: participant.clone(),
+            net,
+        })
+        .collect()
+}
+
+/// Pays the largest creditor from the largest debtor until everyone is even, which takes at
+/// most one payment less than there are participants.
+pub fn simplify(balances: &[Balance]) -> Vec<Debt> {
+    let mut creditors: Vec<(Participant, i64)> = balances
+        .iter()
+        .filter(|b| b.net > 0)
+        .map(|b| (b.participant.clone(), b.net))
+        .collect();
+    let mut debtors: Vec<(Participant, i64)> = balances
+        .iter()
+        .filter(|b| b.net < 0)
+        .map(|b| (b.participant.clone(), -b.net))
+        .collect();
+
+    let mut debts = vec![];
+    loop {
+        creditors.sort_by_key(|(_, amount)| std::cmp::Reverse(*amount));
+        debtors.sort_by_key(|(_, amount)| std::cmp::Reverse(*amount));
+
+        let (Some(creditor), Some(debtor)) = (creditors.first_mut(), debtors.first_mut()) else {
+            break;
+        };
+        let amount = creditor.1.min(debtor.1);
+        if amount == 0 {
+            break;
+        }
+
+        creditor.1 -= amount;
+        debtor.1 -= amount;
+        debts.push(Debt {
+            from: debtor.0.clone(),
+            to: creditor.0.clone(),
+            amount,
+        });
+    }
+
+    debts
+}
+
+fn invalid(reason: &str) -> AppError {
+    AppError::Validation(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: &str) -> Participant {
+        Participant::User(id.to_string())
+    }
+
+    fn amounts(shares: Vec<Share>) -> Vec<i64> {
+        shares.into_iter().map(|s| s.amount).collect()
+    }
+
+    #[test]
+    fn shares_add_up_to_the_amount() {
+        let equal = SplitMethod::Equal {
+            participants: vec![user("a"), user("b"), user("c")],
+        };
+        assert_eq!(amounts(equal.shares(1000).unwrap()), [334, 333, 333]);
+
+        let percent = |percents: [i64; 3]| SplitMethod::Percent {
+            shares: ["a", "b", "c"]
+                .into_iter()
+                .zip(percents)
+                .map(|(id, percent)| PercentShare {
+                    participant: user(id),
+                    percent: Decimal::from(percent),
+                })
+                .collect(),
+        };
+        assert_eq!(
+            amounts(percent([50, 25, 25]).shares(999).unwrap()),
+            [499, 250, 250]
+        );
+        assert!(percent([50, 25, 20]).shares(999).is_err());
+
+        let duplicated = SplitMethod::Equal {
+            participants: vec![user("a"), user("a")],
+        };
+        assert!(duplicated.shares(1000).is_err());
+    }
+
+    #[test]
+    fn simplifies_debts_across_the_group() {
+        let split = |paid_by: &str, shares: &[(&str, i64)]| Split {
+            transaction_id: String::new(),
+            ledger_id: String::new(),
+            paid_by: user(paid_by),
+            shares: shares
+                .iter()
+                .map(|(id, amount)| Share {
+                    participant: user(id),
+                    amount: *amount,
+                })
+                .collect(),
+        };
+
+        // Bia owes Ana 30 and Carol owes Bia 30, so Carol can pay Ana directly.
+        let splits = [
+            split("ana", &[("ana", 30), ("bia", 30)]),
+            split("bia", &[("bia", 30), ("carol", 30)]),
+        ];
+        let balances = balances(&splits, &[]);
+        assert_eq!(
+            simplify(&balances),
+            [Debt {
+                from: user("carol"),
+                to: user("ana"),
+                amount: 30,
+            }]
+        );
+
+        let settlement = Settlement {
+            id: String::new(),
+            ledger_id: String::new(),
+            from: user("carol"),
+            to: user("ana"),
+            amount: 30,
+            date: Utc::now(),
+        };
+        assert!(super::balances(&splits, &[settlement]).is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rust_decimal::Decimal;
+
+use super::{
+    CardType, DeliveryStatus, LedgerRole, LimitPolicy, RateKind, Series, TokenPurpose, TokenScope,
+    TransactionType, WebhookEvent,
+};
+
+/// Column value that doesn't map to the Rust type it is read as.
+#[derive(thiserror::Error, Debug)]
+#[error("invalid {kind}: {value:?}")]
+pub struct InvalidValue {
+    pub kind: &'static str,
+    pub value: String,
+}
+
+/// Enums stored as text by [`text_enum!`].
+pub trait TextEnum: FromSql {
+    fn as_str(&self) -> &'static str;
+}
+
+/// Stores enums as their lowercase names, matching the `CHECK` constraints on their columns.
+macro_rules! text_enum {
+    ($name:ident { $($variant:ident => $text:literal),+ $(,)? }) => {
+        impl $name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $text),+
+                }
+            }
+        }
+
+        impl TextEnum for $name {
+            fn as_str(&self) -> &'static str {
+                $name::as_str(self)
+            }
+        }
+
+        impl ToSql for $name {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(self.as_str().into())
+            }
+        }
+
+        impl FromSql for $name {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                match value.as_str()? {
+                    $($text => Ok(Self::$variant),)+
+                    other => Err(FromSqlError::other(InvalidValue {
+                        kind: stringify!($name),
+                        value: other.to_string(),
+                    })),
+                }
+            }
+        }
+    };
+}
+
+text_enum!(CardType {
+    Credit => "credit",
+    Debit => "debit",
+});
+
+text_enum!(TransactionType {
+    Expense => "expense",
+    Income => "income",
+    Payment => "payment",
+});
+
+text_enum!(LimitPolicy {
+    Ignore => "ignore",
+    Warn => "warn",
+    Reject => "reject",
+});
+
+text_enum!(RateKind {
+    Selic => "selic",
+    Cdi => "cdi",
+    TreasurySpread => "treasury_spread",
+    Fgts => "fgts",
+});
+
+text_enum!(Series {
+    Selic => "selic",
+    Cdi => "cdi",
+    Ipca => "ipca",
+    Tr => "tr",
+});
+
+text_enum!(LedgerRole {
+    Owner => "owner",
+    Editor => "editor",
+    Viewer => "viewer",
+});
+
+text_enum!(TokenScope {
+    Read => "read",
+    Transactions => "transactions",
+    Import => "import",
+});
+
+text_enum!(WebhookEvent {
+    TransactionCreated => "transaction.created",
+    TransactionUpdated => "transaction.updated",
+    TransactionDeleted => "transaction.deleted",
+    CreditLimitExceeded => "credit_limit.exceeded",
+    ImportCompleted => "import.completed",
+});
+
+text_enum!(DeliveryStatus {
+    Pending => "pending",
+    Delivered => "delivered",
+    Failed => "failed",
+});
+
+text_enum!(TokenPurpose {
+    Verify => "verify",
+    Reset => "reset",
+});
+
+/// Several enum values in one column, space separated.
+pub struct TextList<T>(pub Vec<T>);
+
+impl<T: TextEnum> ToSql for TextList<T> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let values: Vec<&str> = self.0.iter().map(T::as_str).collect();
+        Ok(values.join(" ").into())
+    }
+}
+
+impl<T: TextEnum> FromSql for TextList<T> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .split_whitespace()
+            .map(|text| T::column_result(ValueRef::Text(text.as_bytes())))
+            .collect::<FromSqlResult<_>>()
+            .map(TextList)
+    }
+}
+
+/// RFC 3339 timestamp column. Every date is stored in this format so they sort as text.
+pub struct Timestamp(pub DateTime<Utc>);
+
+impl ToSql for Timestamp {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0.to_rfc3339().into())
+    }
+}
+
+impl FromSql for Timestamp {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let text = value.as_str()?;
+
+        DateTime::parse_from_rfc3339(text)
+            .map(|date| Timestamp(date.with_timezone(&Utc)))
+            .map_err(|_| {
+                FromSqlError::other(InvalidValue {
+                    kind: "Timestamp",
+                    value: text.to_string(),
+                })
+            })
+    }
+}
+
+/// `YYYY-MM-DD` date column.
+pub struct Date(pub NaiveDate);
+
+impl ToSql for Date {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0.to_string().into())
+    }
+}
+
+impl FromSql for Date {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        parse_text(value, "Date").map(Date)
+    }
+}
+
+/// Decimal stored as text, so no precision is lost to SQLite's floating point.
+pub struct DecimalText(pub Decimal);
+
+impl ToSql for DecimalText {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0.to_string().into())
+    }
+}
+
+impl FromSql for DecimalText {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        parse_text(value, "Decimal").map(DecimalText)
+    }
+}
+
+fn parse_text<T: FromStr>(value: ValueRef<'_>, kind: &'static str) -> FromSqlResult<T> {
+    let text = value.as_str()?;
+
+    text.parse().map_err(|_| {
+        FromSqlError::other(InvalidValue {
+            kind,
+            value: text.to_string(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{CardType, Timestamp};
+
+    #[test]
+    fn round_trips_typed_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        let now = chrono::Utc::now();
+
+        let (card_type, date): (CardType, Timestamp) = conn
+            .query_row("SELECT ?1, ?2", (CardType::Credit, Timestamp(now)), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+
+        assert_eq!(card_type, CardType::Credit);
+        assert_eq!(date.0, now);
+    }
+
+    #[test]
+    fn rejects_unknown_values() {
+        let conn = Connection::open_in_memory().unwrap();
+
+        let card_type = conn.query_row("SELECT 'gold'", [], |row| row.get::<_, CardType>(0));
+        let date = conn.query_row("SELECT 'yesterday'", [], |row| row.get::<_, Timestamp>(0));
+
+        assert!(matches!(
+            card_type,
+            Err(rusqlite::Error::FromSqlConversionFailure(0, _, _))
+        ));
+        assert!(date.is_err());
+    }
+}
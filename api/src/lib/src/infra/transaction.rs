@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::Card;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
@@ -13,7 +15,7 @@ pub enum TransactionType {
 #[serde(rename_all = "camelCase")]
 pub struct Transaction {
     pub id: String,
-    pub user_email: String,
+    pub ledger_id: String,
     pub card_id: String,
     pub category_id: String,
     pub amount: i64, // in cents
@@ -22,6 +24,16 @@ pub struct Transaction {
     pub date: DateTime<Utc>,
 }
 
+/// A new transaction checked against the credit limit of its card.
+#[derive(Debug, Clone)]
+pub struct Charge {
+    /// The card as it was before the transaction.
+    pub card: Card,
+    pub exceeds_limit: bool,
+    /// False when the card rejects going over its limit.
+    pub inserted: bool,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateTransaction {
@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Issuer of the identities of local email and password accounts.
+pub const LOCAL_ISSUER: &str = "local";
+
+/// Everyone's data is owned by their user id, which stays the same when their email changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub id: String,
+    pub email: String,
+    /// Set by identities trusted with their email, the only ones linked to the user by it.
+    pub email_verified: bool,
+    pub name: String,
+    pub picture: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Links a login to a user. The subject never changes for the same issuer, unlike the email.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub user_id: String,
+}
+
+/// Who an identity provider, or a local account, says just logged in.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: String,
+    /// Whether the issuer verified the email and is trusted to link it to an existing user.
+    /// Otherwise, anyone able to present an email would get the data of the user who has it.
+    pub trusted_email: bool,
+    pub name: String,
+    pub picture: String,
+}
@@ -0,0 +1,162 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::AppResult;
+
+/// Deliveries that keep failing are given up after this many attempts.
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 8;
+
+/// A URL receiving the events of a ledger, signed with its secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub ledger_id: String,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "transaction.created")]
+    TransactionCreated,
+    /// Transactions can't be edited, but splitting one, or removing its split, changes it.
+    #[serde(rename = "transaction.updated")]
+    TransactionUpdated,
+    #[serde(rename = "transaction.deleted")]
+    TransactionDeleted,
+    /// A transaction took a card over its credit limit.
+    #[serde(rename = "credit_limit.exceeded")]
+    CreditLimitExceeded,
+    #[serde(rename = "import.completed")]
+    ImportCompleted,
+}
+
+/// Decides which URLs webhooks can be created with. The `api` binary, which delivers them, keeps
+/// them away from its own network, see its `application::webhooks::PublicTargets`.
+#[async_trait]
+pub trait WebhookTargets: Send + Sync {
+    async fn check(&self, url: &str) -> AppResult<()>;
+}
+
+/// Accepts any URL, for stores that never deliver the webhooks themselves.
+pub struct AnyTarget;
+
+#[async_trait]
+impl WebhookTargets for AnyTarget {
+    async fn check(&self, _url: &str) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhook {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+/// One event queued for one webhook, with the outcome of its last attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    /// The JSON body, kept as sent so every retry is signed over the same bytes.
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+/// The outcome of sending a delivery once.
+#[derive(Debug, Clone)]
+pub struct DeliveryAttempt {
+    pub at: DateTime<Utc>,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl Delivery {
+    /// Applies an attempt: delivered on success, otherwise retried after twice as long as the
+    /// previous time, starting at 30 seconds, until [`MAX_DELIVERY_ATTEMPTS`].
+    pub fn record(&mut self, attempt: DeliveryAttempt) {
+        let succeeded = attempt.error.is_none()
+            && attempt
+                .response_status
+                .is_some_and(|status| (200..300).contains(&status));
+
+        self.attempts += 1;
+        self.last_attempt_at = Some(attempt.at);
+        self.response_status = attempt.response_status;
+        self.error = attempt.error;
+
+        (self.status, self.next_attempt_at) = if succeeded {
+            (DeliveryStatus::Delivered, None)
+        } else if self.attempts >= MAX_DELIVERY_ATTEMPTS {
+            (DeliveryStatus::Failed, None)
+        } else {
+            let backoff = Duration::seconds(30 << (self.attempts - 1));
+            (DeliveryStatus::Pending, Some(attempt.at + backoff))
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_attempts_back_off_until_given_up() {
+        let now = Utc::now();
+        let mut delivery = Delivery {
+            id: String::new(),
+            webhook_id: String::new(),
+            event: WebhookEvent::TransactionCreated,
+            payload: String::new(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_attempt_at: None,
+            response_status: None,
+            error: None,
+            created_at: now,
+        };
+        let failure = |status| DeliveryAttempt {
+            at: now,
+            response_status: Some(status),
+            error: None,
+        };
+
+        delivery.record(failure(500));
+        assert_eq!(delivery.next_attempt_at, Some(now + Duration::seconds(30)));
+        delivery.record(failure(404));
+        assert_eq!(delivery.next_attempt_at, Some(now + Duration::seconds(60)));
+
+        for _ in 2..MAX_DELIVERY_ATTEMPTS {
+            delivery.record(failure(503));
+        }
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.next_attempt_at, None);
+
+        let mut retried = delivery.clone();
+        retried.attempts = 1;
+        retried.record(failure(204));
+        assert_eq!(retried.status, DeliveryStatus::Delivered);
+    }
+}
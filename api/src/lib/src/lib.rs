@@ -8,11 +8,12 @@ use axum::{
 
 mod api;
 pub mod infra;
+pub mod repository;
 mod util;
 
 use serde_json::json;
 
-pub use api::router;
+pub use api::{ledger::ActiveLedger, router};
 
 impl From<JsonRejection> for AppError {
     fn from(rejection: JsonRejection) -> Self {
@@ -37,19 +38,54 @@ pub type AppResult<T> = Result<T, AppError>;
 pub enum AppError {
     #[error("Validation error: {0}")]
     Validation(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[cfg(feature = "sqlite")]
     #[error("rusqlite error: {0}")]
-    Database(#[from] tokio_rusqlite::Error),
+    Database(tokio_rusqlite::Error),
+    #[error("Stored data could not be read from column {column}: {reason}")]
+    Conversion { column: usize, reason: String },
+}
+
+#[cfg(feature = "sqlite")]
+impl From<tokio_rusqlite::Error> for AppError {
+    fn from(err: tokio_rusqlite::Error) -> Self {
+        use rusqlite::Error as SqlError;
+
+        match err {
+            tokio_rusqlite::Error::Error(SqlError::FromSqlConversionFailure(column, _, source)) => {
+                AppError::Conversion {
+                    column,
+                    reason: source.to_string(),
+                }
+            }
+            tokio_rusqlite::Error::Error(SqlError::InvalidColumnType(column, name, kind)) => {
+                AppError::Conversion {
+                    column,
+                    reason: format!("{name} has unexpected type {kind}"),
+                }
+            }
+            err => AppError::Database(err),
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         let (code, msg, err) = match self {
+            #[cfg(feature = "sqlite")]
             Self::Database(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 self.to_string(),
                 Some(self),
             ),
+            Self::Conversion { .. } => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                self.to_string(),
+                Some(self),
+            ),
             Self::Validation(reason) => (StatusCode::BAD_REQUEST, reason, None),
+            Self::Forbidden(reason) => (StatusCode::FORBIDDEN, reason, None),
         };
 
         let mut response = (code, Json(json!({"error": msg }))).into_response();
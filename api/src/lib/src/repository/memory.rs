@@ -0,0 +1,1217 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    AppResult,
+    infra::{
+        Account, AccountToken, ApiToken, Bucket, Card, CardDetails, Category, Charge, Contact,
+        Delivery, DeliveryStatus, Device, Goal, Identity, IntegrityIssue, Ledger, LedgerInvite,
+        LedgerMember, LedgerRole, LimitPolicy, MemberDetails, Membership, Rate, RateKind,
+        RateLimit, Series, SeriesPoint, Session, Settlement, Split, TokenPurpose, Transaction,
+        TransactionType, UpdateCard, UpdateGoal, User, UserIdentity, Webhook,
+        category::default_categories, rate::default_rates,
+    },
+};
+
+use super::{
+    AccountRepository, ApiTokenRepository, CardRepository, CategoryRepository, GoalRepository,
+    IDLE_BUCKET_TTL, IntegrityRepository, LedgerRepository, RateLimitRepository, RateRepository,
+    SeriesRepository, SessionRepository, SplitRepository, TransactionRepository, UserRepository,
+    WebhookRepository,
+};
+
+/// Keeps every row in memory, for tests and for running without a database file.
+pub struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MemoryData {
+    pub cards: Vec<Card>,
+    pub categories: Vec<Category>,
+    pub transactions: Vec<Transaction>,
+    // Snapshots saved before rates existed don't have them.
+    #[serde(default = "default_rates")]
+    pub rates: Vec<Rate>,
+    #[serde(default)]
+    pub series: Vec<SeriesPoint>,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    #[serde(default)]
+    pub sessions: Vec<Session>,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    #[serde(default)]
+    pub account_tokens: Vec<AccountToken>,
+    #[serde(default)]
+    pub users: Vec<User>,
+    #[serde(default)]
+    pub user_identities: Vec<UserIdentity>,
+    #[serde(default)]
+    pub ledgers: Vec<Ledger>,
+    #[serde(default)]
+    pub ledger_members: Vec<LedgerMember>,
+    #[serde(default)]
+    pub ledger_invites: Vec<LedgerInvite>,
+    #[serde(default)]
+    pub contacts: Vec<Contact>,
+    #[serde(default)]
+    pub splits: Vec<Split>,
+    #[serde(default)]
+    pub settlements: Vec<Settlement>,
+    #[serde(default)]
+    pub api_tokens: Vec<ApiToken>,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub webhook_deliveries: Vec<Delivery>,
+    /// Rate limits only last while the process runs.
+    #[serde(skip)]
+    pub rate_limits: HashMap<String, Bucket>,
+}
+
+impl Default for MemoryData {
+    fn default() -> Self {
+        Self {
+            cards: vec![],
+            categories: default_categories(),
+            transactions: vec![],
+            rates: default_rates(),
+            series: vec![],
+            goals: vec![],
+            sessions: vec![],
+            accounts: vec![],
+            account_tokens: vec![],
+            users: vec![],
+            user_identities: vec![],
+            ledgers: vec![],
+            ledger_members: vec![],
+            ledger_invites: vec![],
+            contacts: vec![],
+            splits: vec![],
+            settlements: vec![],
+            api_tokens: vec![],
+            webhooks: vec![],
+            webhook_deliveries: vec![],
+            rate_limits: HashMap::new(),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(MemoryData::default())
+    }
+}
+
+impl MemoryStore {
+    pub fn new(data: MemoryData) -> Self {
+        Self {
+            data: Mutex::new(data),
+        }
+    }
+
+    /// Copy of every row, to persist the store somewhere else.
+    pub fn snapshot(&self) -> MemoryData {
+        self.data().clone()
+    }
+
+    fn data(&self) -> MutexGuard<'_, MemoryData> {
+        // No invariant spans a panic, so a poisoned lock still holds usable data.
+        self.data.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl MemoryData {
+    fn card_details(&self, card: &Card) -> CardDetails {
+        let now = Utc::now();
+        let reserved = self
+            .transactions
+            .iter()
+            .filter(|t| t.card_id == card.id && t.date > now)
+            .map(|t| t.amount)
+            .sum();
+
+        card.clone().with_usage(reserved)
+    }
+
+    /// Splits go away with their transaction, like the cascading foreign key `open_db` enables.
+    fn drop_orphan_splits(&mut self) {
+        let transactions = &self.transactions;
+        self.splits
+            .retain(|s| transactions.iter().any(|t| t.id == s.transaction_id));
+    }
+
+    fn card_mut(&mut self, ledger_id: &str, id: &str) -> Option<&mut Card> {
+        self.cards
+            .iter_mut()
+            .find(|c| c.id == id && c.ledger_id == ledger_id)
+    }
+}
+
+#[async_trait]
+impl CardRepository for MemoryStore {
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<CardDetails>> {
+        let data = self.data();
+
+        Ok(data
+            .cards
+            .iter()
+            .filter(|c| c.ledger_id == ledger_id)
+            .map(|c| data.card_details(c))
+            .collect())
+    }
+
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<CardDetails>> {
+        let data = self.data();
+
+        Ok(data
+            .cards
+            .iter()
+            .find(|c| c.id == id && c.ledger_id == ledger_id)
+            .map(|c| data.card_details(c)))
+    }
+
+    async fn create(&self, card: &Card) -> AppResult<()> {
+        self.data().cards.push(card.clone());
+        Ok(())
+    }
+
+    async fn update(
+        &self,
+        ledger_id: &str,
+        id: &str,
+        update: &UpdateCard,
+    ) -> AppResult<Option<CardDetails>> {
+        let mut data = self.data();
+
+        let Some(card) = data.card_mut(ledger_id, id) else {
+            return Ok(None);
+        };
+
+        if let Some(name) = &update.name {
+            card.name = name.clone();
+        }
+        if let Some(credit_limit) = update.credit_limit {
+            card.credit_limit = Some(credit_limit);
+        }
+        if let Some(limit_policy) = update.limit_policy {
+            card.limit_policy = limit_policy;
+        }
+
+        let card = card.clone();
+        Ok(Some(data.card_details(&card)))
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+
+        data.transactions
+            .retain(|t| !(t.card_id == id && t.ledger_id == ledger_id));
+        data.drop_orphan_splits();
+
+        for goal in data.goals.iter_mut().filter(|g| g.ledger_id == ledger_id) {
+            if goal.card_id.as_deref() == Some(id) {
+                goal.card_id = None;
+            }
+        }
+
+        let before = data.cards.len();
+        data.cards
+            .retain(|c| !(c.id == id && c.ledger_id == ledger_id));
+
+        Ok(data.cards.len() < before)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for MemoryStore {
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Category>> {
+        Ok(self
+            .data()
+            .categories
+            .iter()
+            .filter(|c| c.ledger_id.as_deref().is_none_or(|e| e == ledger_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn create(&self, category: &Category) -> AppResult<()> {
+        self.data().categories.push(category.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+
+        // Kept while in use, like the foreign key in SQLite.
+        if data.transactions.iter().any(|t| t.category_id == id) {
+            return Ok(false);
+        }
+
+        let before = data.categories.len();
+        data.categories
+            .retain(|c| !(c.id == id && c.ledger_id.as_deref() == Some(ledger_id)));
+
+        Ok(data.categories.len() < before)
+    }
+}
+
+#[async_trait]
+impl TransactionRepository for MemoryStore {
+    async fn list(&self, ledger_id: &str, limit: u32, offset: u32) -> AppResult<Vec<Transaction>> {
+        let mut transactions: Vec<_> = self
+            .data()
+            .transactions
+            .iter()
+            .filter(|t| t.ledger_id == ledger_id)
+            .cloned()
+            .collect();
+
+        transactions.sort_by_key(|t| std::cmp::Reverse(t.date));
+
+        Ok(transactions
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<Transaction>> {
+        Ok(self
+            .data()
+            .transactions
+            .iter()
+            .find(|t| t.id == id && t.ledger_id == ledger_id)
+            .cloned())
+    }
+
+    async fn create(&self, transaction: &Transaction) -> AppResult<()> {
+        let mut data = self.data();
+
+        if let Some(card) = data.cards.iter_mut().find(|c| c.id == transaction.card_id) {
+            card.current_balance += transaction.amount;
+        }
+
+        data.transactions.push(transaction.clone());
+        Ok(())
+    }
+
+    async fn create_many(&self, transactions: &[Transaction]) -> AppResult<()> {
+        let mut data = self.data();
+
+        for transaction in transactions {
+            if let Some(card) = data.cards.iter_mut().find(|c| c.id == transaction.card_id) {
+                card.current_balance += transaction.amount;
+            }
+        }
+
+        data.transactions.extend_from_slice(transactions);
+        Ok(())
+    }
+
+    async fn charge(&self, transaction: &Transaction) -> AppResult<Option<Charge>> {
+        let mut data = self.data();
+
+        let Some(card) = data
+            .cards
+            .iter_mut()
+            .find(|c| c.id == transaction.card_id && c.ledger_id == transaction.ledger_id)
+        else {
+            return Ok(None);
+        };
+
+        let exceeds_limit = card.exceeds_limit(transaction.amount);
+        let charge = Charge {
+            card: card.clone(),
+            exceeds_limit,
+            inserted: !(exceeds_limit && card.limit_policy == LimitPolicy::Reject),
+        };
+        if charge.inserted {
+            card.current_balance += transaction.amount;
+            data.transactions.push(transaction.clone());
+        }
+
+        Ok(Some(charge))
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+
+        let Some(index) = data
+            .transactions
+            .iter()
+            .position(|t| t.id == id && t.ledger_id == ledger_id)
+        else {
+            return Ok(false);
+        };
+
+        let transaction = data.transactions.remove(index);
+        data.drop_orphan_splits();
+
+        if let Some(card) = data.cards.iter_mut().find(|c| c.id == transaction.card_id) {
+            card.current_balance -= transaction.amount;
+        }
+
+        Ok(true)
+    }
+
+    async fn expenses(
+        &self,
+        ledger_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> AppResult<i64> {
+        Ok(self
+            .data()
+            .transactions
+            .iter()
+            .filter(|t| {
+                t.ledger_id == ledger_id
+                    && t.transaction_type == TransactionType::Expense
+                    && (from..to).contains(&t.date)
+            })
+            .map(|t| t.amount)
+            .sum())
+    }
+}
+
+#[async_trait]
+impl IntegrityRepository for MemoryStore {
+    async fn check(&self, ledger_id: &str) -> AppResult<Vec<IntegrityIssue>> {
+        let data = self.data();
+
+        let issues = data
+            .transactions
+            .iter()
+            .filter(|t| t.ledger_id == ledger_id)
+            .flat_map(|t| {
+                let card = (!data.cards.iter().any(|c| c.id == t.card_id)).then(|| {
+                    IntegrityIssue::missing_reference(
+                        "transactions",
+                        &t.id,
+                        "card_id",
+                        t.card_id.clone(),
+                    )
+                });
+                let category =
+                    (!data.categories.iter().any(|c| c.id == t.category_id)).then(|| {
+                        IntegrityIssue::missing_reference(
+                            "transactions",
+                            &t.id,
+                            "category_id",
+                            t.category_id.clone(),
+                        )
+                    });
+
+                card.into_iter().chain(category)
+            })
+            .collect();
+
+        Ok(issues)
+    }
+}
+
+#[async_trait]
+impl RateRepository for MemoryStore {
+    async fn history(&self, kind: Option<RateKind>) -> AppResult<Vec<Rate>> {
+        let mut rates: Vec<_> = self
+            .data()
+            .rates
+            .iter()
+            .filter(|r| kind.is_none_or(|k| r.kind == k))
+            .cloned()
+            .collect();
+
+        rates.sort_by_key(|r| (r.effective_from, r.kind));
+
+        Ok(rates)
+    }
+
+    async fn in_effect(&self, date: NaiveDate) -> AppResult<Vec<Rate>> {
+        let data = self.data();
+
+        Ok(RateKind::ALL
+            .iter()
+            .filter_map(|&kind| {
+                data.rates
+                    .iter()
+                    .filter(|r| r.kind == kind && r.effective_from <= date)
+                    .max_by_key(|r| r.effective_from)
+                    .cloned()
+            })
+            .collect())
+    }
+
+    async fn set(&self, rate: &Rate) -> AppResult<()> {
+        let mut data = self.data();
+
+        data.rates
+            .retain(|r| !(r.kind == rate.kind && r.effective_from == rate.effective_from));
+        data.rates.push(rate.clone());
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SeriesRepository for MemoryStore {
+    async fn import(&self, points: &[SeriesPoint]) -> AppResult<usize> {
+        let mut data = self.data();
+
+        for point in points {
+            data.series
+                .retain(|p| !(p.series == point.series && p.date == point.date));
+            data.series.push(point.clone());
+        }
+
+        Ok(points.len())
+    }
+
+    async fn range(
+        &self,
+        series: Series,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<SeriesPoint>> {
+        let mut points: Vec<_> = self
+            .data()
+            .series
+            .iter()
+            .filter(|p| p.series == series && (from..=to).contains(&p.date))
+            .cloned()
+            .collect();
+
+        points.sort_by_key(|p| p.date);
+
+        Ok(points)
+    }
+}
+
+#[async_trait]
+impl GoalRepository for MemoryStore {
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Goal>> {
+        let mut goals: Vec<_> = self
+            .data()
+            .goals
+            .iter()
+            .filter(|g| g.ledger_id == ledger_id)
+            .cloned()
+            .collect();
+
+        goals.sort_by_key(|g| g.deadline);
+
+        Ok(goals)
+    }
+
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<Goal>> {
+        Ok(self
+            .data()
+            .goals
+            .iter()
+            .find(|g| g.id == id && g.ledger_id == ledger_id)
+            .cloned())
+    }
+
+    async fn create(&self, goal: &Goal) -> AppResult<()> {
+        self.data().goals.push(goal.clone());
+        Ok(())
+    }
+
+    async fn update(
+        &self,
+        ledger_id: &str,
+        id: &str,
+        update: &UpdateGoal,
+    ) -> AppResult<Option<Goal>> {
+        let mut data = self.data();
+
+        let Some(goal) = data
+            .goals
+            .iter_mut()
+            .find(|g| g.id == id && g.ledger_id == ledger_id)
+        else {
+            return Ok(None);
+        };
+
+        if let Some(name) = &update.name {
+            goal.name = name.clone();
+        }
+        if let Some(target) = update.target {
+            goal.target = target;
+        }
+        if let Some(deadline) = update.deadline {
+            goal.deadline = deadline;
+        }
+        if let Some(card_id) = &update.card_id {
+            goal.card_id = Some(card_id.clone());
+        }
+        if let Some(saved) = update.saved {
+            goal.saved = saved;
+        }
+        if let Some(monthly_contribution) = update.monthly_contribution {
+            goal.monthly_contribution = monthly_contribution;
+        }
+        if let Some(emergency_months) = update.emergency_months {
+            goal.emergency_months = Some(emergency_months);
+        }
+
+        Ok(Some(goal.clone()))
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+
+        let before = data.goals.len();
+        data.goals
+            .retain(|g| !(g.id == id && g.ledger_id == ledger_id));
+
+        Ok(data.goals.len() < before)
+    }
+}
+
+#[async_trait]
+impl SessionRepository for MemoryStore {
+    async fn create(&self, session: &Session) -> AppResult<()> {
+        self.data().sessions.push(session.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> AppResult<Option<Session>> {
+        Ok(self.data().sessions.iter().find(|s| s.id == id).cloned())
+    }
+
+    async fn list(&self, user_id: &str) -> AppResult<Vec<Session>> {
+        let now = Utc::now();
+        let mut sessions: Vec<_> = self
+            .data()
+            .sessions
+            .iter()
+            .filter(|s| s.user_id == user_id && s.is_active(now))
+            .cloned()
+            .collect();
+
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_seen_at));
+
+        Ok(sessions)
+    }
+
+    async fn seen(&self, id: &str, at: DateTime<Utc>, device: &Device) -> AppResult<()> {
+        if let Some(session) = self.data().sessions.iter_mut().find(|s| s.id == id) {
+            session.last_seen_at = at;
+            session.device = device.clone();
+        }
+
+        Ok(())
+    }
+
+    async fn rotate(
+        &self,
+        id: &str,
+        from_jti: &str,
+        to_jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AppResult<bool> {
+        let mut data = self.data();
+        let now = Utc::now();
+
+        let Some(session) = data
+            .sessions
+            .iter_mut()
+            .find(|s| s.id == id && s.refresh_jti == from_jti && s.is_active(now))
+        else {
+            return Ok(false);
+        };
+
+        session.refresh_jti = to_jti.to_string();
+        session.expires_at = expires_at;
+
+        Ok(true)
+    }
+
+    async fn revoke(&self, user_id: &str, id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+        let now = Utc::now();
+
+        let Some(session) = data
+            .sessions
+            .iter_mut()
+            .find(|s| s.id == id && s.user_id == user_id && s.revoked_at.is_none())
+        else {
+            return Ok(false);
+        };
+
+        session.revoked_at = Some(now);
+
+        Ok(true)
+    }
+
+    async fn revoke_all(&self, user_id: &str) -> AppResult<usize> {
+        let mut data = self.data();
+        let now = Utc::now();
+
+        let mut revoked = 0;
+        for session in data
+            .sessions
+            .iter_mut()
+            .filter(|s| s.user_id == user_id && s.is_active(now))
+        {
+            session.revoked_at = Some(now);
+            revoked += 1;
+        }
+
+        Ok(revoked)
+    }
+}
+
+#[async_trait]
+impl AccountRepository for MemoryStore {
+    async fn create(&self, account: &Account) -> AppResult<bool> {
+        let mut data = self.data();
+
+        match data.accounts.iter_mut().find(|a| a.email == account.email) {
+            Some(existing) if existing.verified_at.is_some() => return Ok(false),
+            Some(existing) => *existing = account.clone(),
+            None => data.accounts.push(account.clone()),
+        }
+        data.account_tokens
+            .retain(|t| !(t.email == account.email && t.purpose == TokenPurpose::Verify));
+
+        Ok(true)
+    }
+
+    async fn get(&self, email: &str) -> AppResult<Option<Account>> {
+        Ok(self
+            .data()
+            .accounts
+            .iter()
+            .find(|a| a.email == email)
+            .cloned())
+    }
+
+    async fn verify(&self, email: &str, at: DateTime<Utc>) -> AppResult<()> {
+        if let Some(account) = self.data().accounts.iter_mut().find(|a| a.email == email) {
+            account.verified_at.get_or_insert(at);
+        }
+
+        Ok(())
+    }
+
+    async fn set_password(&self, email: &str, password_hash: &str) -> AppResult<()> {
+        if let Some(account) = self.data().accounts.iter_mut().find(|a| a.email == email) {
+            account.password_hash = password_hash.to_string();
+        }
+
+        Ok(())
+    }
+
+    async fn create_token(&self, token: &AccountToken) -> AppResult<()> {
+        self.data().account_tokens.push(token.clone());
+        Ok(())
+    }
+
+    async fn use_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<String>> {
+        let mut data = self.data();
+
+        let Some(token) = data.account_tokens.iter_mut().find(|t| {
+            t.token_hash == token_hash
+                && t.purpose == purpose
+                && t.used_at.is_none()
+                && t.expires_at > now
+        }) else {
+            return Ok(None);
+        };
+
+        token.used_at = Some(now);
+
+        Ok(Some(token.email.clone()))
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryStore {
+    async fn resolve(&self, identity: &Identity) -> AppResult<User> {
+        let mut data = self.data();
+
+        let linked = data
+            .user_identities
+            .iter()
+            .find(|i| i.issuer == identity.issuer && i.subject == identity.subject)
+            .map(|i| i.user_id.clone());
+
+        let id = match linked {
+            Some(id) => id,
+            None => {
+                let verified_by = |email: &str| {
+                    data.users
+                        .iter()
+                        .find(|u| u.email == email && u.email_verified)
+                        .map(|u| u.id.clone())
+                };
+                let existing = verified_by(&identity.email).filter(|_| identity.trusted_email);
+                let id = match existing {
+                    Some(id) => id,
+                    None => {
+                        let id = Uuid::now_v7().to_string();
+                        // The email is only verified if nobody else has it verified.
+                        let email_verified =
+                            identity.trusted_email && verified_by(&identity.email).is_none();
+                        data.users.push(User {
+                            id: id.clone(),
+                            email: identity.email.clone(),
+                            email_verified,
+                            name: identity.name.clone(),
+                            picture: identity.picture.clone(),
+                            created_at: Utc::now(),
+                        });
+                        id
+                    }
+                };
+
+                data.user_identities.push(UserIdentity {
+                    issuer: identity.issuer.clone(),
+                    subject: identity.subject.clone(),
+                    user_id: id.clone(),
+                });
+                id
+            }
+        };
+
+        // Keep the old email if another user already has the new one verified.
+        let email_taken = data
+            .users
+            .iter()
+            .any(|u| u.email == identity.email && u.email_verified && u.id != id);
+        let user = data
+            .users
+            .iter_mut()
+            .find(|u| u.id == id)
+            .expect("identities link to existing users");
+
+        if identity.trusted_email && !email_taken {
+            user.email = identity.email.clone();
+            user.email_verified = true;
+        }
+        if !identity.name.is_empty() {
+            user.name = identity.name.clone();
+        }
+        if !identity.picture.is_empty() {
+            user.picture = identity.picture.clone();
+        }
+
+        Ok(user.clone())
+    }
+
+    async fn get(&self, id: &str) -> AppResult<Option<User>> {
+        Ok(self.data().users.iter().find(|u| u.id == id).cloned())
+    }
+}
+
+impl MemoryData {
+    fn membership(&self, ledger_id: &str, user_id: &str) -> Option<Membership> {
+        let role = self
+            .ledger_members
+            .iter()
+            .find(|m| m.ledger_id == ledger_id && m.user_id == user_id)?
+            .role;
+        let ledger = self.ledgers.iter().find(|l| l.id == ledger_id)?;
+
+        Some(Membership {
+            ledger: ledger.clone(),
+            role,
+            personal: false,
+        })
+    }
+}
+
+#[async_trait]
+impl LedgerRepository for MemoryStore {
+    async fn create(&self, ledger: &Ledger, owner_id: &str) -> AppResult<()> {
+        let mut data = self.data();
+
+        data.ledgers.push(ledger.clone());
+        data.ledger_members.push(LedgerMember {
+            ledger_id: ledger.id.clone(),
+            user_id: owner_id.to_string(),
+            role: LedgerRole::Owner,
+            joined_at: ledger.created_at,
+        });
+
+        Ok(())
+    }
+
+    async fn list(&self, user_id: &str) -> AppResult<Vec<Membership>> {
+        let data = self.data();
+
+        let mut memberships: Vec<Membership> = data
+            .ledger_members
+            .iter()
+            .filter(|m| m.user_id == user_id)
+            .filter_map(|m| data.membership(&m.ledger_id, user_id))
+            .collect();
+        memberships.sort_by_key(|m| m.ledger.created_at);
+
+        Ok(memberships)
+    }
+
+    async fn role(&self, ledger_id: &str, user_id: &str) -> AppResult<Option<LedgerRole>> {
+        Ok(self
+            .data()
+            .ledger_members
+            .iter()
+            .find(|m| m.ledger_id == ledger_id && m.user_id == user_id)
+            .map(|m| m.role))
+    }
+
+    async fn members(&self, ledger_id: &str) -> AppResult<Vec<MemberDetails>> {
+        let data = self.data();
+
+        let mut members: Vec<MemberDetails> = data
+            .ledger_members
+            .iter()
+            .filter(|m| m.ledger_id == ledger_id)
+            .map(|m| {
+                // The wasm app has no users, only the one running it.
+                let user = data.users.iter().find(|u| u.id == m.user_id);
+
+                MemberDetails {
+                    member: m.clone(),
+                    email: user.map(|u| u.email.clone()).unwrap_or_default(),
+                    name: user.map(|u| u.name.clone()).unwrap_or_default(),
+                    picture: user.map(|u| u.picture.clone()).unwrap_or_default(),
+                }
+            })
+            .collect();
+        members.sort_by_key(|m| (std::cmp::Reverse(m.member.role), m.member.joined_at));
+
+        Ok(members)
+    }
+
+    async fn set_role(&self, ledger_id: &str, user_id: &str, role: LedgerRole) -> AppResult<bool> {
+        let mut data = self.data();
+
+        let Some(member) = data
+            .ledger_members
+            .iter_mut()
+            .find(|m| m.ledger_id == ledger_id && m.user_id == user_id)
+        else {
+            return Ok(false);
+        };
+
+        member.role = role;
+        Ok(true)
+    }
+
+    async fn remove_member(&self, ledger_id: &str, user_id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+
+        let before = data.ledger_members.len();
+        data.ledger_members
+            .retain(|m| !(m.ledger_id == ledger_id && m.user_id == user_id));
+
+        Ok(data.ledger_members.len() < before)
+    }
+
+    async fn create_invite(&self, invite: &LedgerInvite) -> AppResult<()> {
+        self.data().ledger_invites.push(invite.clone());
+        Ok(())
+    }
+
+    async fn accept_invite(
+        &self,
+        token: &str,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<Membership>> {
+        let mut data = self.data();
+
+        let Some(invite) = data
+            .ledger_invites
+            .iter_mut()
+            .find(|i| i.token == token && i.used_at.is_none() && i.expires_at > now)
+        else {
+            return Ok(None);
+        };
+
+        invite.used_at = Some(now);
+        let (ledger_id, role) = (invite.ledger_id.clone(), invite.role);
+
+        if data.membership(&ledger_id, user_id).is_none() {
+            data.ledger_members.push(LedgerMember {
+                ledger_id: ledger_id.clone(),
+                user_id: user_id.to_string(),
+                role,
+                joined_at: now,
+            });
+        }
+
+        Ok(data.membership(&ledger_id, user_id))
+    }
+}
+
+#[async_trait]
+impl SplitRepository for MemoryStore {
+    async fn contacts(&self, ledger_id: &str) -> AppResult<Vec<Contact>> {
+        let mut contacts: Vec<Contact> = self
+            .data()
+            .contacts
+            .iter()
+            .filter(|c| c.ledger_id == ledger_id)
+            .cloned()
+            .collect();
+        contacts.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(contacts)
+    }
+
+    async fn create_contact(&self, contact: &Contact) -> AppResult<()> {
+        self.data().contacts.push(contact.clone());
+        Ok(())
+    }
+
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Split>> {
+        Ok(self
+            .data()
+            .splits
+            .iter()
+            .filter(|s| s.ledger_id == ledger_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get(&self, ledger_id: &str, transaction_id: &str) -> AppResult<Option<Split>> {
+        Ok(self
+            .data()
+            .splits
+            .iter()
+            .find(|s| s.ledger_id == ledger_id && s.transaction_id == transaction_id)
+            .cloned())
+    }
+
+    async fn save(&self, split: &Split) -> AppResult<()> {
+        let mut data = self.data();
+
+        data.splits
+            .retain(|s| s.transaction_id != split.transaction_id);
+        data.splits.push(split.clone());
+
+        Ok(())
+    }
+
+    async fn delete(&self, ledger_id: &str, transaction_id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+
+        let before = data.splits.len();
+        data.splits
+            .retain(|s| !(s.ledger_id == ledger_id && s.transaction_id == transaction_id));
+
+        Ok(data.splits.len() < before)
+    }
+
+    async fn settle(&self, settlement: &Settlement) -> AppResult<()> {
+        self.data().settlements.push(settlement.clone());
+        Ok(())
+    }
+
+    async fn settlements(&self, ledger_id: &str) -> AppResult<Vec<Settlement>> {
+        let mut settlements: Vec<Settlement> = self
+            .data()
+            .settlements
+            .iter()
+            .filter(|s| s.ledger_id == ledger_id)
+            .cloned()
+            .collect();
+        settlements.sort_by_key(|s| s.date);
+
+        Ok(settlements)
+    }
+}
+
+#[async_trait]
+impl ApiTokenRepository for MemoryStore {
+    async fn create(&self, token: &ApiToken) -> AppResult<()> {
+        self.data().api_tokens.push(token.clone());
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> AppResult<Option<ApiToken>> {
+        Ok(self
+            .data()
+            .api_tokens
+            .iter()
+            .find(|t| t.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn list(&self, user_id: &str) -> AppResult<Vec<ApiToken>> {
+        let mut tokens: Vec<ApiToken> = self
+            .data()
+            .api_tokens
+            .iter()
+            .filter(|t| t.user_id == user_id && t.revoked_at.is_none())
+            .cloned()
+            .collect();
+        tokens.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+
+        Ok(tokens)
+    }
+
+    async fn used(&self, id: &str, at: DateTime<Utc>) -> AppResult<()> {
+        if let Some(token) = self.data().api_tokens.iter_mut().find(|t| t.id == id) {
+            token.last_used_at = Some(at);
+        }
+
+        Ok(())
+    }
+
+    async fn revoke(&self, user_id: &str, id: &str, at: DateTime<Utc>) -> AppResult<bool> {
+        let mut data = self.data();
+
+        let Some(token) = data
+            .api_tokens
+            .iter_mut()
+            .find(|t| t.id == id && t.user_id == user_id && t.revoked_at.is_none())
+        else {
+            return Ok(false);
+        };
+
+        token.revoked_at = Some(at);
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for MemoryStore {
+    async fn create(&self, webhook: &Webhook) -> AppResult<()> {
+        self.data().webhooks.push(webhook.clone());
+        Ok(())
+    }
+
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Webhook>> {
+        let mut webhooks: Vec<Webhook> = self
+            .data()
+            .webhooks
+            .iter()
+            .filter(|w| w.ledger_id == ledger_id)
+            .cloned()
+            .collect();
+        webhooks.sort_by_key(|w| w.created_at);
+
+        Ok(webhooks)
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let mut data = self.data();
+        let before = data.webhooks.len();
+
+        data.webhooks
+            .retain(|w| !(w.id == id && w.ledger_id == ledger_id));
+        let deleted = data.webhooks.len() < before;
+        if deleted {
+            data.webhook_deliveries.retain(|d| d.webhook_id != id);
+        }
+
+        Ok(deleted)
+    }
+
+    async fn enqueue(&self, deliveries: &[Delivery]) -> AppResult<()> {
+        self.data().webhook_deliveries.extend_from_slice(deliveries);
+        Ok(())
+    }
+
+    async fn claim_due(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> AppResult<Vec<(Webhook, Delivery)>> {
+        let mut data = self.data();
+        let data = &mut *data;
+
+        let mut due: Vec<&mut Delivery> = data
+            .webhook_deliveries
+            .iter_mut()
+            .filter(|d| {
+                d.status == DeliveryStatus::Pending && d.next_attempt_at.is_some_and(|at| at <= now)
+            })
+            .collect();
+        due.sort_by_key(|d| (d.next_attempt_at, d.created_at));
+
+        let mut claimed: Vec<(Webhook, Delivery)> = due
+            .into_iter()
+            .take(limit as usize)
+            .filter_map(|delivery| {
+                let webhook = data.webhooks.iter().find(|w| w.id == delivery.webhook_id)?;
+                delivery.next_attempt_at = Some(lease_until);
+                Some((webhook.clone(), delivery.clone()))
+            })
+            .collect();
+        claimed.sort_by_key(|(_, delivery)| delivery.created_at);
+
+        Ok(claimed)
+    }
+
+    async fn update_delivery(&self, delivery: &Delivery) -> AppResult<()> {
+        let mut data = self.data();
+
+        if let Some(stored) = data
+            .webhook_deliveries
+            .iter_mut()
+            .find(|d| d.id == delivery.id)
+        {
+            *stored = delivery.clone();
+        }
+
+        Ok(())
+    }
+
+    async fn deliveries(&self, webhook_id: &str, limit: u32) -> AppResult<Vec<Delivery>> {
+        let mut deliveries: Vec<Delivery> = self
+            .data()
+            .webhook_deliveries
+            .iter()
+            .filter(|d| d.webhook_id == webhook_id)
+            .cloned()
+            .collect();
+        deliveries.sort_by_key(|d| std::cmp::Reverse(d.created_at));
+        deliveries.truncate(limit as usize);
+
+        Ok(deliveries)
+    }
+}
+
+#[async_trait]
+impl RateLimitRepository for MemoryStore {
+    async fn take(
+        &self,
+        key: &str,
+        limit: &RateLimit,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<Duration>> {
+        let mut data = self.data();
+
+        // Only new keys grow the map, so idle buckets are dropped then.
+        if !data.rate_limits.contains_key(key) {
+            data.rate_limits
+                .retain(|_, bucket| bucket.updated_at >= now - IDLE_BUCKET_TTL);
+        }
+        let bucket = data
+            .rate_limits
+            .entry(key.to_string())
+            .or_insert_with(|| limit.full(now));
+
+        Ok(bucket.take(limit, now).err())
+    }
+}
@@ -0,0 +1,664 @@
+//! Storage behind the `lib` handlers, so the same business logic runs on SQLite in the `api`
+//! binary and on the in-memory store in the wasm `app` crate and in tests.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::{
+    AppResult,
+    infra::{
+        Account, AccountToken, ApiToken, Card, CardDetails, Category, Charge, Contact, Delivery,
+        Device, Goal, Identity, IntegrityIssue, Ledger, LedgerInvite, LedgerRole, MemberDetails,
+        Membership, Rate, RateKind, RateLimit, Series, SeriesPoint, Session, Settlement, Split,
+        TokenPurpose, Transaction, UpdateCard, UpdateGoal, User, Webhook,
+    },
+};
+
+mod memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use memory::{MemoryData, MemoryStore};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[async_trait]
+pub trait CardRepository: Send + Sync {
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<CardDetails>>;
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<CardDetails>>;
+    async fn create(&self, card: &Card) -> AppResult<()>;
+    async fn update(
+        &self,
+        ledger_id: &str,
+        id: &str,
+        update: &UpdateCard,
+    ) -> AppResult<Option<CardDetails>>;
+    /// Deletes the card along with its transactions.
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool>;
+}
+
+#[async_trait]
+pub trait CategoryRepository: Send + Sync {
+    /// Default categories plus the ones created in the ledger.
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Category>>;
+    async fn create(&self, category: &Category) -> AppResult<()>;
+    /// Only deletes categories of the ledger, never the default ones nor those still in use.
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool>;
+}
+
+#[async_trait]
+pub trait TransactionRepository: Send + Sync {
+    /// Most recent first.
+    async fn list(&self, ledger_id: &str, limit: u32, offset: u32) -> AppResult<Vec<Transaction>>;
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<Transaction>>;
+    /// Inserts the transaction and adds its amount to the card balance.
+    async fn create(&self, transaction: &Transaction) -> AppResult<()>;
+    /// Inserts all the transactions or, on any error, none of them.
+    async fn create_many(&self, transactions: &[Transaction]) -> AppResult<()>;
+    /// Like [`create`](Self::create), unless the card's [`LimitPolicy::Reject`] turns it down.
+    /// The limit is checked in the same database transaction as the insert, so concurrent ones
+    /// can't all fit under it. `None` when the card isn't in the ledger.
+    ///
+    /// [`LimitPolicy::Reject`]: crate::infra::LimitPolicy::Reject
+    async fn charge(&self, transaction: &Transaction) -> AppResult<Option<Charge>>;
+    /// Deletes the transaction and reverts its amount from the card balance.
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool>;
+    /// Sum of the expenses dated from `from` (inclusive) to `to` (exclusive), in cents.
+    async fn expenses(
+        &self,
+        ledger_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> AppResult<i64>;
+}
+
+#[async_trait]
+pub trait IntegrityRepository: Send + Sync {
+    /// Rows of the ledger that can't be read back, or that reference missing rows.
+    async fn check(&self, ledger_id: &str) -> AppResult<Vec<IntegrityIssue>>;
+}
+
+#[async_trait]
+pub trait RateRepository: Send + Sync {
+    /// Every value a rate ever had, oldest first, optionally only for one kind.
+    async fn history(&self, kind: Option<RateKind>) -> AppResult<Vec<Rate>>;
+    /// Latest value of each kind effective on `date`.
+    async fn in_effect(&self, date: NaiveDate) -> AppResult<Vec<Rate>>;
+    /// Inserts the rate, replacing any value of the same kind effective from the same date.
+    async fn set(&self, rate: &Rate) -> AppResult<()>;
+}
+
+#[async_trait]
+pub trait SeriesRepository: Send + Sync {
+    /// Inserts the points, replacing the values already stored for the same series and date.
+    async fn import(&self, points: &[SeriesPoint]) -> AppResult<usize>;
+    /// Points between `from` and `to`, both inclusive, oldest first.
+    async fn range(
+        &self,
+        series: Series,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<SeriesPoint>>;
+}
+
+#[async_trait]
+pub trait GoalRepository: Send + Sync {
+    /// Earliest deadline first.
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Goal>>;
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<Goal>>;
+    async fn create(&self, goal: &Goal) -> AppResult<()>;
+    async fn update(
+        &self,
+        ledger_id: &str,
+        id: &str,
+        update: &UpdateGoal,
+    ) -> AppResult<Option<Goal>>;
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool>;
+}
+
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    async fn create(&self, session: &Session) -> AppResult<()>;
+    async fn get(&self, id: &str) -> AppResult<Option<Session>>;
+    /// Active sessions of the user, most recently seen first.
+    async fn list(&self, user_id: &str) -> AppResult<Vec<Session>>;
+    /// Records that the session was just used, from `device`.
+    async fn seen(&self, id: &str, at: DateTime<Utc>, device: &Device) -> AppResult<()>;
+    /// Swaps the refresh token of an active session, only if `from_jti` is still its current one.
+    async fn rotate(
+        &self,
+        id: &str,
+        from_jti: &str,
+        to_jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AppResult<bool>;
+    async fn revoke(&self, user_id: &str, id: &str) -> AppResult<bool>;
+    /// Revokes every active session of the user, returning how many there were.
+    async fn revoke_all(&self, user_id: &str) -> AppResult<usize>;
+}
+
+#[async_trait]
+pub trait AccountRepository: Send + Sync {
+    /// Creates the account, or replaces an unverified one and drops the verification links
+    /// mailed for it, so only whoever follows the next link sets the password. Returns false
+    /// when the email already has a verified account.
+    async fn create(&self, account: &Account) -> AppResult<bool>;
+    async fn get(&self, email: &str) -> AppResult<Option<Account>>;
+    async fn verify(&self, email: &str, at: DateTime<Utc>) -> AppResult<()>;
+    async fn set_password(&self, email: &str, password_hash: &str) -> AppResult<()>;
+    async fn create_token(&self, token: &AccountToken) -> AppResult<()>;
+    /// Marks an unused, unexpired token as used, returning the email it was issued for.
+    async fn use_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<String>>;
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// The user linked to the identity, with its profile refreshed. An identity logging in for
+    /// the first time is linked to the user with the same verified email when its own is
+    /// trusted, or to a new user. Only trusted emails replace the stored one. An empty name or
+    /// picture keeps the stored one, as local accounts have no picture.
+    async fn resolve(&self, identity: &Identity) -> AppResult<User>;
+    async fn get(&self, id: &str) -> AppResult<Option<User>>;
+}
+
+#[async_trait]
+pub trait LedgerRepository: Send + Sync {
+    /// Creates a shared ledger with `owner_id` as its owner.
+    async fn create(&self, ledger: &Ledger, owner_id: &str) -> AppResult<()>;
+    /// Shared ledgers the user is a member of, oldest first. Personal ledgers aren't stored.
+    async fn list(&self, user_id: &str) -> AppResult<Vec<Membership>>;
+    async fn role(&self, ledger_id: &str, user_id: &str) -> AppResult<Option<LedgerRole>>;
+    /// Owners first, then editors and viewers, each in the order they joined.
+    async fn members(&self, ledger_id: &str) -> AppResult<Vec<MemberDetails>>;
+    async fn set_role(&self, ledger_id: &str, user_id: &str, role: LedgerRole) -> AppResult<bool>;
+    async fn remove_member(&self, ledger_id: &str, user_id: &str) -> AppResult<bool>;
+    async fn create_invite(&self, invite: &LedgerInvite) -> AppResult<()>;
+    /// Marks an unused, unexpired invite as used and adds the user to its ledger. Members
+    /// already in the ledger keep their role.
+    async fn accept_invite(
+        &self,
+        token: &str,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<Membership>>;
+}
+
+#[async_trait]
+pub trait SplitRepository: Send + Sync {
+    /// Contacts of the ledger, by name.
+    async fn contacts(&self, ledger_id: &str) -> AppResult<Vec<Contact>>;
+    async fn create_contact(&self, contact: &Contact) -> AppResult<()>;
+    /// Every split of the ledger, with its shares in the order they were given.
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Split>>;
+    async fn get(&self, ledger_id: &str, transaction_id: &str) -> AppResult<Option<Split>>;
+    /// Saves the split, replacing the one the transaction already had. Deleting the transaction
+    /// deletes its split.
+    async fn save(&self, split: &Split) -> AppResult<()>;
+    async fn delete(&self, ledger_id: &str, transaction_id: &str) -> AppResult<bool>;
+    async fn settle(&self, settlement: &Settlement) -> AppResult<()>;
+    /// Oldest first.
+    async fn settlements(&self, ledger_id: &str) -> AppResult<Vec<Settlement>>;
+}
+
+#[async_trait]
+pub trait ApiTokenRepository: Send + Sync {
+    async fn create(&self, token: &ApiToken) -> AppResult<()>;
+    /// The token with that hash, revoked or not.
+    async fn find(&self, token_hash: &str) -> AppResult<Option<ApiToken>>;
+    /// The user's tokens that aren't revoked, newest first.
+    async fn list(&self, user_id: &str) -> AppResult<Vec<ApiToken>>;
+    async fn used(&self, id: &str, at: DateTime<Utc>) -> AppResult<()>;
+    /// Revokes one of the user's tokens, returning whether it was active.
+    async fn revoke(&self, user_id: &str, id: &str, at: DateTime<Utc>) -> AppResult<bool>;
+}
+
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn create(&self, webhook: &Webhook) -> AppResult<()>;
+    /// Oldest first.
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Webhook>>;
+    /// Deletes the webhook and its deliveries.
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool>;
+    async fn enqueue(&self, deliveries: &[Delivery]) -> AppResult<()>;
+    /// Pending deliveries due by `now`, oldest first, with their webhook. Their next attempt
+    /// moves to `lease_until`, so another worker doesn't send them meanwhile and they are retried
+    /// if this one stops before recording the outcome.
+    async fn claim_due(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> AppResult<Vec<(Webhook, Delivery)>>;
+    /// Saves the outcome of the last attempt.
+    async fn update_delivery(&self, delivery: &Delivery) -> AppResult<()>;
+    /// The webhook's deliveries, newest first.
+    async fn deliveries(&self, webhook_id: &str, limit: u32) -> AppResult<Vec<Delivery>>;
+}
+
+#[async_trait]
+pub trait RateLimitRepository: Send + Sync {
+    /// Takes a token from the bucket named `key`, returning how long to wait when it's empty.
+    /// Buckets untouched for [`IDLE_BUCKET_TTL`] are dropped, so limits must refill faster.
+    async fn take(
+        &self,
+        key: &str,
+        limit: &RateLimit,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<Duration>>;
+}
+
+/// Buckets left alone this long are full again, and are forgotten.
+pub const IDLE_BUCKET_TTL: Duration = Duration::days(1);
+
+/// A fresh state on each store the crate is built with, for tests to run against all of them.
+#[cfg(test)]
+pub(crate) async fn stores() -> Vec<crate::infra::DbState> {
+    use crate::infra::DbState;
+
+    #[cfg(feature = "sqlite")]
+    let sqlite = Some(DbState::new(
+        crate::infra::init_db(":memory:").await.unwrap(),
+    ));
+    #[cfg(not(feature = "sqlite"))]
+    let sqlite = None;
+
+    sqlite.into_iter().chain([DbState::in_memory()]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, Utc};
+    use rust_decimal::Decimal;
+
+    use super::stores;
+    use crate::infra::{
+        Account, AccountToken, ApiToken, Device, Identity, Rate, RateKind, RateLimit, Session,
+        TokenPurpose, TokenScope,
+    };
+
+    #[tokio::test]
+    async fn rates_keep_their_history() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        for state in stores().await {
+            let rates = state.rates;
+
+            rates
+                .set(&Rate {
+                    kind: RateKind::Selic,
+                    value: Decimal::new(1425, 2),
+                    effective_from: date(2026, 3, 19),
+                })
+                .await
+                .unwrap();
+
+            let selic = |rates: Vec<Rate>| {
+                rates
+                    .into_iter()
+                    .find(|r| r.kind == RateKind::Selic)
+                    .map(|r| r.value)
+            };
+
+            let before = rates.in_effect(date(2026, 3, 18)).await.unwrap();
+            assert_eq!(before.len(), 4);
+            assert_eq!(selic(before), Some(Decimal::new(150, 1)));
+
+            let after = rates.in_effect(date(2026, 3, 19)).await.unwrap();
+            assert_eq!(selic(after), Some(Decimal::new(1425, 2)));
+
+            assert!(rates.in_effect(date(2020, 1, 1)).await.unwrap().is_empty());
+            assert_eq!(rates.history(Some(RateKind::Selic)).await.unwrap().len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn sessions_rotate_their_refresh_token() {
+        for state in stores().await {
+            let sessions = state.sessions;
+            let now = Utc::now();
+            let session = |id: &str| Session {
+                id: id.to_string(),
+                user_id: "user@test.com".to_string(),
+                refresh_jti: "first".to_string(),
+                created_at: now,
+                expires_at: now + Duration::days(30),
+                revoked_at: None,
+                device: Device::default(),
+                last_seen_at: now,
+            };
+
+            sessions.create(&session("laptop")).await.unwrap();
+            sessions.create(&session("phone")).await.unwrap();
+
+            let phone = Device {
+                user_agent: Some("Mobile Safari".to_string()),
+                ip: Some("2001:db8::1".to_string()),
+            };
+            let later = now + Duration::minutes(5);
+            sessions.seen("phone", later, &phone).await.unwrap();
+
+            let listed = sessions.list("user@test.com").await.unwrap();
+            assert_eq!(listed.len(), 2);
+            assert_eq!(listed[0].id, "phone");
+            assert_eq!(listed[0].device, phone);
+            assert_eq!(listed[0].last_seen_at, later);
+
+            let expires = now + Duration::days(31);
+            assert!(
+                sessions
+                    .rotate("laptop", "first", "second", expires)
+                    .await
+                    .unwrap()
+            );
+            // The old token was already used.
+            assert!(
+                !sessions
+                    .rotate("laptop", "first", "third", expires)
+                    .await
+                    .unwrap()
+            );
+
+            let laptop = sessions.get("laptop").await.unwrap().unwrap();
+            assert_eq!(laptop.refresh_jti, "second");
+            assert_eq!(laptop.expires_at, expires);
+
+            assert!(sessions.revoke("user@test.com", "laptop").await.unwrap());
+            assert!(
+                !sessions
+                    .rotate("laptop", "second", "third", expires)
+                    .await
+                    .unwrap()
+            );
+
+            assert_eq!(sessions.revoke_all("user@test.com").await.unwrap(), 1);
+            assert!(
+                !sessions
+                    .get("phone")
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .is_active(Utc::now())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn account_tokens_work_once() {
+        for state in stores().await {
+            let accounts = state.accounts;
+            let now = Utc::now();
+            let account = Account {
+                email: "user@test.com".to_string(),
+                name: "User".to_string(),
+                password_hash: "first".to_string(),
+                verified_at: None,
+                created_at: now,
+            };
+
+            assert!(accounts.create(&account).await.unwrap());
+
+            let token = |hash: &str, purpose| AccountToken {
+                token_hash: hash.to_string(),
+                email: account.email.clone(),
+                purpose,
+                expires_at: now + Duration::hours(1),
+                used_at: None,
+            };
+            accounts
+                .create_token(&token("stale", TokenPurpose::Verify))
+                .await
+                .unwrap();
+
+            // Signing up again before verifying replaces the password and its links.
+            let replaced = Account {
+                password_hash: "replaced".to_string(),
+                ..account.clone()
+            };
+            assert!(accounts.create(&replaced).await.unwrap());
+            let stored = accounts.get(&account.email).await.unwrap().unwrap();
+            assert_eq!(stored.password_hash, "replaced");
+            assert_eq!(
+                accounts
+                    .use_token("stale", TokenPurpose::Verify, now)
+                    .await
+                    .unwrap(),
+                None
+            );
+
+            accounts
+                .create_token(&token("verify", TokenPurpose::Verify))
+                .await
+                .unwrap();
+            accounts
+                .create_token(&token("reset", TokenPurpose::Reset))
+                .await
+                .unwrap();
+
+            // Each token only works for its own purpose, once and before it expires.
+            let use_token = |hash, purpose, at| accounts.use_token(hash, purpose, at);
+            assert_eq!(
+                use_token("verify", TokenPurpose::Reset, now).await.unwrap(),
+                None
+            );
+            assert_eq!(
+                use_token("reset", TokenPurpose::Reset, now + Duration::hours(2))
+                    .await
+                    .unwrap(),
+                None
+            );
+            assert_eq!(
+                use_token("verify", TokenPurpose::Verify, now)
+                    .await
+                    .unwrap(),
+                Some(account.email.clone())
+            );
+            assert_eq!(
+                use_token("verify", TokenPurpose::Verify, now)
+                    .await
+                    .unwrap(),
+                None
+            );
+
+            accounts.verify(&account.email, now).await.unwrap();
+            assert!(!accounts.create(&account).await.unwrap());
+            accounts
+                .set_password(&account.email, "second")
+                .await
+                .unwrap();
+
+            let stored = accounts.get(&account.email).await.unwrap().unwrap();
+            assert_eq!(stored.verified_at, Some(now));
+            assert_eq!(stored.password_hash, "second");
+        }
+    }
+
+    #[tokio::test]
+    async fn api_tokens_are_found_until_revoked() {
+        for state in stores().await {
+            let tokens = state.api_tokens;
+            let now = Utc::now();
+            let token = ApiToken {
+                id: "cron".to_string(),
+                user_id: "user".to_string(),
+                name: "Cron".to_string(),
+                token_hash: "hash".to_string(),
+                scopes: vec![TokenScope::Read, TokenScope::Import],
+                created_at: now,
+                last_used_at: None,
+                revoked_at: None,
+            };
+            tokens.create(&token).await.unwrap();
+
+            let later = now + Duration::minutes(5);
+            tokens.used("cron", later).await.unwrap();
+            let found = tokens.find("hash").await.unwrap().unwrap();
+            assert_eq!(found.scopes, token.scopes);
+            assert_eq!(found.last_used_at, Some(later));
+
+            assert!(!tokens.revoke("someone else", "cron", later).await.unwrap());
+            assert!(tokens.revoke("user", "cron", later).await.unwrap());
+            assert!(!tokens.revoke("user", "cron", later).await.unwrap());
+            assert!(tokens.list("user").await.unwrap().is_empty());
+            assert_eq!(
+                tokens.find("hash").await.unwrap().unwrap().revoked_at,
+                Some(later)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limits_are_kept_per_key() {
+        for state in stores().await {
+            let rate_limits = state.rate_limits;
+            let limit = RateLimit {
+                burst: 1,
+                period: Duration::seconds(10),
+            };
+            let now = Utc::now();
+
+            assert_eq!(rate_limits.take("ana", &limit, now).await.unwrap(), None);
+            assert_eq!(
+                rate_limits.take("ana", &limit, now).await.unwrap(),
+                Some(Duration::seconds(10))
+            );
+            assert_eq!(rate_limits.take("bia", &limit, now).await.unwrap(), None);
+
+            let later = now + Duration::seconds(10);
+            assert_eq!(rate_limits.take("ana", &limit, later).await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn users_keep_their_id_when_the_email_changes() {
+        for state in stores().await {
+            let users = state.users;
+            let identity = |issuer: &str, subject: &str, email: &str| Identity {
+                issuer: issuer.to_string(),
+                subject: subject.to_string(),
+                email: email.to_string(),
+                trusted_email: true,
+                name: "Ana".to_string(),
+                // Local accounts have no picture.
+                picture: match issuer {
+                    "local" => String::new(),
+                    _ => "ana.png".to_string(),
+                },
+            };
+
+            let user = users
+                .resolve(&identity(
+                    "https://accounts.google.com",
+                    "1",
+                    "ana@gmail.com",
+                ))
+                .await
+                .unwrap();
+            assert!(user.email_verified);
+
+            // Another provider with the same email is linked to the same user.
+            let linked = users
+                .resolve(&identity("local", "ana@gmail.com", "ana@gmail.com"))
+                .await
+                .unwrap();
+            assert_eq!(linked.id, user.id);
+            assert_eq!(linked.picture, "ana.png");
+
+            let renamed = users
+                .resolve(&identity(
+                    "https://accounts.google.com",
+                    "1",
+                    "ana@work.com",
+                ))
+                .await
+                .unwrap();
+            assert_eq!(renamed.id, user.id);
+            assert_eq!(renamed.email, "ana@work.com");
+
+            let other = users
+                .resolve(&identity(
+                    "https://accounts.google.com",
+                    "2",
+                    "ana@gmail.com",
+                ))
+                .await
+                .unwrap();
+            assert_ne!(other.id, user.id);
+
+            // The new email already belongs to someone else, so the old one stays.
+            let clash = users
+                .resolve(&identity(
+                    "https://accounts.google.com",
+                    "2",
+                    "ana@work.com",
+                ))
+                .await
+                .unwrap();
+            assert_eq!(clash.email, "ana@gmail.com");
+            assert_eq!(
+                users.get(&user.id).await.unwrap().unwrap().email,
+                "ana@work.com"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn untrusted_emails_get_a_user_of_their_own() {
+        for state in stores().await {
+            let users = state.users;
+            let identity = |issuer: &str, email: &str, trusted_email| Identity {
+                issuer: issuer.to_string(),
+                subject: email.to_string(),
+                email: email.to_string(),
+                trusted_email,
+                name: "Ana".to_string(),
+                picture: String::new(),
+            };
+
+            // Claiming someone's email before they show up doesn't get their data either.
+            let squatter = users
+                .resolve(&identity("https://sso.example.com", "ana@gmail.com", false))
+                .await
+                .unwrap();
+            assert!(!squatter.email_verified);
+
+            let ana = users
+                .resolve(&identity(
+                    "https://accounts.google.com",
+                    "ana@gmail.com",
+                    true,
+                ))
+                .await
+                .unwrap();
+            assert_ne!(ana.id, squatter.id);
+            assert!(ana.email_verified);
+
+            let other = users
+                .resolve(&identity(
+                    "https://other.example.com",
+                    "ana@gmail.com",
+                    false,
+                ))
+                .await
+                .unwrap();
+            assert_ne!(other.id, ana.id);
+            assert_ne!(other.id, squatter.id);
+
+            // Nor can an untrusted login change the email of the user it's linked to.
+            let renamed = users
+                .resolve(&identity("https://sso.example.com", "ana@gmail.com", false))
+                .await
+                .unwrap();
+            assert_eq!(renamed.id, squatter.id);
+            let local = users
+                .resolve(&identity("local", "ana@gmail.com", true))
+                .await
+                .unwrap();
+            assert_eq!(local.id, ana.id);
+        }
+    }
+}
@@ -0,0 +1,2130 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::{
+    OptionalExtension, Row, Rows, TransactionBehavior,
+    types::{FromSql, Type, ValueRef},
+};
+use tokio_rusqlite::Connection;
+use uuid::Uuid;
+
+use crate::{
+    AppResult,
+    infra::{
+        Account, AccountToken, ApiToken, Bucket, Card, CardDetails, CardType, Category, Charge,
+        Contact, Date, DecimalText, Delivery, Device, Goal, Identity, IntegrityIssue, Ledger,
+        LedgerInvite, LedgerMember, LedgerRole, LimitPolicy, MemberDetails, Membership,
+        Participant, Rate, RateKind, RateLimit, Series, SeriesPoint, Session, Settlement, Share,
+        Split, Timestamp, TokenPurpose, Transaction, TransactionType, UpdateCard, UpdateGoal, User,
+        Webhook,
+        sql::{InvalidValue, TextList},
+    },
+};
+
+use super::{
+    AccountRepository, ApiTokenRepository, CardRepository, CategoryRepository, GoalRepository,
+    IDLE_BUCKET_TTL, IntegrityRepository, LedgerRepository, RateLimitRepository, RateRepository,
+    SeriesRepository, SessionRepository, SplitRepository, TransactionRepository, UserRepository,
+    WebhookRepository,
+};
+
+#[derive(Clone)]
+pub struct SqliteStore {
+    conn: Arc<Connection>,
+}
+
+impl SqliteStore {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn: Arc::new(conn),
+        }
+    }
+}
+
+// The reserved amount is the sum of transactions dated after ?1 (future installments).
+const SELECT_CARD_DETAILS: &str = "SELECT c.id, c.ledger_id, c.name, c.card_type, c.credit_limit, c.current_balance, c.limit_policy,
+        (SELECT COALESCE(SUM(t.amount), 0) FROM transactions t WHERE t.card_id = c.id AND t.date > ?1)
+     FROM cards c";
+
+const SELECT_GOAL: &str = "SELECT id, ledger_id, name, target, deadline, card_id, saved, monthly_contribution, emergency_months
+     FROM goals";
+
+const SELECT_SESSION: &str = "SELECT id, user_id, refresh_jti, created_at, expires_at, revoked_at, user_agent, ip, last_seen_at
+     FROM sessions";
+
+const SELECT_ACCOUNT: &str = "SELECT email, name, password_hash, verified_at, created_at
+     FROM accounts";
+
+const SELECT_USER: &str = "SELECT id, email, email_verified, name, picture, created_at FROM users";
+
+const SELECT_MEMBERSHIP: &str = "SELECT l.id, l.name, l.created_at, m.role
+     FROM ledger_members m
+     JOIN ledgers l ON l.id = m.ledger_id";
+
+const SELECT_SPLIT_SHARES: &str =
+    "SELECT s.transaction_id, s.ledger_id, s.paid_by_kind, s.paid_by_id,
+        sh.participant_kind, sh.participant_id, sh.amount
+     FROM transaction_splits s
+     JOIN split_shares sh ON sh.transaction_id = s.transaction_id";
+
+const SELECT_TRANSACTION: &str =
+    "SELECT id, ledger_id, card_id, category_id, amount, description, transaction_type, date
+     FROM transactions";
+
+#[async_trait]
+impl CardRepository for SqliteStore {
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<CardDetails>> {
+        let ledger_id = ledger_id.to_string();
+        let now = Timestamp(Utc::now());
+        let cards = self
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare(&format!("{SELECT_CARD_DETAILS} WHERE c.ledger_id = ?2"))?;
+                let cards = stmt
+                    .query_map((&now, &ledger_id), card_details_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(cards)
+            })
+            .await?;
+
+        Ok(cards)
+    }
+
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<CardDetails>> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let now = Timestamp(Utc::now());
+        let card = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_CARD_DETAILS} WHERE c.id = ?2 AND c.ledger_id = ?3"
+                ))?;
+                let card = stmt
+                    .query_map((&now, &id, &ledger_id), card_details_from_row)?
+                    .next()
+                    .transpose()?;
+                Ok(card)
+            })
+            .await?;
+
+        Ok(card)
+    }
+
+    async fn create(&self, card: &Card) -> AppResult<()> {
+        let card = card.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO cards (id, ledger_id, name, card_type, credit_limit, current_balance, limit_policy)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    (
+                        &card.id,
+                        &card.ledger_id,
+                        &card.name,
+                        &card.card_type,
+                        &card.credit_limit,
+                        &card.current_balance,
+                        &card.limit_policy,
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update(
+        &self,
+        ledger_id: &str,
+        id: &str,
+        update: &UpdateCard,
+    ) -> AppResult<Option<CardDetails>> {
+        let (owner, card_id) = (ledger_id.to_string(), id.to_string());
+        let (name, credit_limit, limit_policy) = (
+            update.name.clone(),
+            update.credit_limit,
+            update.limit_policy,
+        );
+        self.conn
+            .call(move |conn| {
+                let id = card_id;
+
+                // Update fields if provided
+                if let Some(name) = &name {
+                    conn.execute(
+                        "UPDATE cards SET name = ?1 WHERE id = ?2 AND ledger_id = ?3",
+                        (&name, &id, &owner),
+                    )?;
+                }
+                if let Some(credit_limit) = &credit_limit {
+                    conn.execute(
+                        "UPDATE cards SET credit_limit = ?1 WHERE id = ?2 AND ledger_id = ?3",
+                        (credit_limit, &id, &owner),
+                    )?;
+                }
+                if let Some(limit_policy) = limit_policy {
+                    conn.execute(
+                        "UPDATE cards SET limit_policy = ?1 WHERE id = ?2 AND ledger_id = ?3",
+                        (limit_policy, &id, &owner),
+                    )?;
+                }
+                Ok(())
+            })
+            .await?;
+
+        CardRepository::get(self, ledger_id, id).await
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                // Delete related transactions first
+                tx.execute(
+                    "DELETE FROM transactions WHERE card_id = ?1 AND ledger_id = ?2",
+                    [&id, &ledger_id],
+                )?;
+
+                tx.execute(
+                    "UPDATE goals SET card_id = NULL WHERE card_id = ?1 AND ledger_id = ?2",
+                    [&id, &ledger_id],
+                )?;
+
+                // Delete the card
+                let rows = tx.execute(
+                    "DELETE FROM cards WHERE id = ?1 AND ledger_id = ?2",
+                    [&id, &ledger_id],
+                )?;
+
+                tx.commit()?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for SqliteStore {
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Category>> {
+        let ledger_id = ledger_id.to_string();
+        let categories = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, ledger_id, name, color
+                     FROM categories
+                     WHERE ledger_id IS NULL OR ledger_id = ?1",
+                )?;
+                let categories = stmt
+                    .query_map([&ledger_id], |row| {
+                        Ok(Category {
+                            id: row.get(0)?,
+                            ledger_id: row.get(1)?,
+                            name: row.get(2)?,
+                            color: row.get(3)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(categories)
+            })
+            .await?;
+
+        Ok(categories)
+    }
+
+    async fn create(&self, category: &Category) -> AppResult<()> {
+        let category = category.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO categories (id, ledger_id, name, color) VALUES (?1, ?2, ?3, ?4)",
+                    (
+                        &category.id,
+                        &category.ledger_id,
+                        &category.name,
+                        &category.color,
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                // Only allow deleting user's custom categories (ledger_id IS NOT NULL), and
+                // leave the ones transactions still point to for the foreign key.
+                let rows = conn.execute(
+                    "DELETE FROM categories WHERE id = ?1 AND ledger_id = ?2
+                     AND NOT EXISTS (SELECT 1 FROM transactions WHERE category_id = ?1)",
+                    [&id, &ledger_id],
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl TransactionRepository for SqliteStore {
+    async fn list(&self, ledger_id: &str, limit: u32, offset: u32) -> AppResult<Vec<Transaction>> {
+        let ledger_id = ledger_id.to_string();
+        let transactions = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_TRANSACTION} WHERE ledger_id = ?1 ORDER BY date DESC LIMIT ?2 OFFSET ?3"
+                ))?;
+                let transactions = stmt
+                    .query_map((&ledger_id, limit, offset), transaction_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(transactions)
+            })
+            .await?;
+
+        Ok(transactions)
+    }
+
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<Transaction>> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let transaction = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_TRANSACTION} WHERE id = ?1 AND ledger_id = ?2"
+                ))?;
+                let transaction = stmt
+                    .query_map([&id, &ledger_id], transaction_from_row)?
+                    .next()
+                    .transpose()?;
+                Ok(transaction)
+            })
+            .await?;
+
+        Ok(transaction)
+    }
+
+    async fn create(&self, transaction: &Transaction) -> AppResult<()> {
+        let transaction = transaction.clone();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                insert_transaction(&tx, &transaction)?;
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create_many(&self, transactions: &[Transaction]) -> AppResult<()> {
+        let transactions = transactions.to_vec();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                for transaction in &transactions {
+                    insert_transaction(&tx, transaction)?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn charge(&self, transaction: &Transaction) -> AppResult<Option<Charge>> {
+        let transaction = transaction.clone();
+        let charge = self
+            .conn
+            .call(move |conn| {
+                // Takes the write lock before reading the balance, for other connections too.
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+                let Some(card) = tx
+                    .query_row(
+                        "SELECT id, ledger_id, name, card_type, credit_limit, current_balance, limit_policy
+                         FROM cards WHERE id = ?1 AND ledger_id = ?2",
+                        [&transaction.card_id, &transaction.ledger_id],
+                        card_from_row,
+                    )
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+
+                let exceeds_limit = card.exceeds_limit(transaction.amount);
+                let inserted = !(exceeds_limit && card.limit_policy == LimitPolicy::Reject);
+                if inserted {
+                    insert_transaction(&tx, &transaction)?;
+                }
+
+                tx.commit()?;
+                Ok(Some(Charge {
+                    card,
+                    exceeds_limit,
+                    inserted,
+                }))
+            })
+            .await?;
+
+        Ok(charge)
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                // Get transaction details to reverse balance
+                let found: Option<(String, i64)> = tx
+                    .prepare(
+                        "SELECT card_id, amount FROM transactions WHERE id = ?1 AND ledger_id = ?2",
+                    )?
+                    .query_map([&id, &ledger_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .next()
+                    .transpose()?;
+
+                let Some((card_id, amount)) = found else {
+                    return Ok(false);
+                };
+
+                tx.execute(
+                    "UPDATE cards SET current_balance = current_balance - ?1 WHERE id = ?2",
+                    (amount, &card_id),
+                )?;
+
+                tx.execute(
+                    "DELETE FROM transactions WHERE id = ?1 AND ledger_id = ?2",
+                    [&id, &ledger_id],
+                )?;
+
+                tx.commit()?;
+                Ok(true)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+
+    async fn expenses(
+        &self,
+        ledger_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> AppResult<i64> {
+        let ledger_id = ledger_id.to_string();
+        let (from, to) = (Timestamp(from), Timestamp(to));
+        let total = self
+            .conn
+            .call(move |conn| {
+                let total = conn.query_row(
+                    "SELECT COALESCE(SUM(amount), 0) FROM transactions
+                     WHERE ledger_id = ?1 AND transaction_type = ?2 AND date >= ?3 AND date < ?4",
+                    (&ledger_id, TransactionType::Expense, &from, &to),
+                    |row| row.get(0),
+                )?;
+                Ok(total)
+            })
+            .await?;
+
+        Ok(total)
+    }
+}
+
+#[async_trait]
+impl IntegrityRepository for SqliteStore {
+    async fn check(&self, ledger_id: &str) -> AppResult<Vec<IntegrityIssue>> {
+        let ledger_id = ledger_id.to_string();
+        let issues = self
+            .conn
+            .call(move |conn| {
+                let mut issues = vec![];
+
+                let mut stmt = conn.prepare(
+                    "SELECT id, card_type, limit_policy FROM cards WHERE ledger_id = ?1",
+                )?;
+                let mut rows = stmt.query([&ledger_id])?;
+                while let Some(row) = rows.next()? {
+                    let id: String = row.get(0)?;
+                    check_column::<CardType>(&mut issues, row, "cards", &id, 1, "card_type")?;
+                    check_column::<LimitPolicy>(&mut issues, row, "cards", &id, 2, "limit_policy")?;
+                }
+
+                let mut stmt = conn.prepare(
+                    "SELECT t.id, t.transaction_type, t.date, t.card_id, c.id, t.category_id, cat.id
+                     FROM transactions t
+                     LEFT JOIN cards c ON c.id = t.card_id
+                     LEFT JOIN categories cat ON cat.id = t.category_id
+                     WHERE t.ledger_id = ?1",
+                )?;
+                let mut rows = stmt.query([&ledger_id])?;
+                while let Some(row) = rows.next()? {
+                    let id: String = row.get(0)?;
+                    let table = "transactions";
+                    check_column::<TransactionType>(
+                        &mut issues,
+                        row,
+                        table,
+                        &id,
+                        1,
+                        "transaction_type",
+                    )?;
+                    check_column::<Timestamp>(&mut issues, row, table, &id, 2, "date")?;
+
+                    if row.get_ref(4)? == ValueRef::Null {
+                        issues.push(IntegrityIssue::missing_reference(
+                            table,
+                            &id,
+                            "card_id",
+                            row.get(3)?,
+                        ));
+                    }
+                    if row.get_ref(6)? == ValueRef::Null {
+                        issues.push(IntegrityIssue::missing_reference(
+                            table,
+                            &id,
+                            "category_id",
+                            row.get(5)?,
+                        ));
+                    }
+                }
+
+                Ok(issues)
+            })
+            .await?;
+
+        Ok(issues)
+    }
+}
+
+#[async_trait]
+impl RateRepository for SqliteStore {
+    async fn history(&self, kind: Option<RateKind>) -> AppResult<Vec<Rate>> {
+        let mut rates = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT kind, value, effective_from FROM rates WHERE ?1 IS NULL OR kind = ?1",
+                )?;
+                let rates = stmt
+                    .query_map([kind], rate_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rates)
+            })
+            .await?;
+
+        rates.sort_by_key(|r| (r.effective_from, r.kind));
+
+        Ok(rates)
+    }
+
+    async fn in_effect(&self, date: NaiveDate) -> AppResult<Vec<Rate>> {
+        let date = Date(date);
+        let mut rates = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT r.kind, r.value, r.effective_from
+                     FROM rates r
+                     WHERE r.effective_from = (
+                        SELECT MAX(effective_from) FROM rates
+                        WHERE kind = r.kind AND effective_from <= ?1
+                     )",
+                )?;
+                let rates = stmt
+                    .query_map([&date], rate_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rates)
+            })
+            .await?;
+
+        rates.sort_by_key(|r| r.kind);
+
+        Ok(rates)
+    }
+
+    async fn set(&self, rate: &Rate) -> AppResult<()> {
+        let rate = rate.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO rates (kind, effective_from, value) VALUES (?1, ?2, ?3)",
+                    (
+                        &rate.kind,
+                        Date(rate.effective_from),
+                        DecimalText(rate.value),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SeriesRepository for SqliteStore {
+    async fn import(&self, points: &[SeriesPoint]) -> AppResult<usize> {
+        let points = points.to_vec();
+        let imported = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT OR REPLACE INTO series_points (series, date, value) VALUES (?1, ?2, ?3)",
+                    )?;
+                    for point in &points {
+                        stmt.execute((&point.series, Date(point.date), DecimalText(point.value)))?;
+                    }
+                }
+                tx.commit()?;
+                Ok(points.len())
+            })
+            .await?;
+
+        Ok(imported)
+    }
+
+    async fn range(
+        &self,
+        series: Series,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<SeriesPoint>> {
+        let points = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT series, date, value FROM series_points
+                     WHERE series = ?1 AND date BETWEEN ?2 AND ?3
+                     ORDER BY date",
+                )?;
+                let points = stmt
+                    .query_map((series, Date(from), Date(to)), |row| {
+                        Ok(SeriesPoint {
+                            series: row.get(0)?,
+                            date: row.get::<_, Date>(1)?.0,
+                            value: row.get::<_, DecimalText>(2)?.0,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(points)
+            })
+            .await?;
+
+        Ok(points)
+    }
+}
+
+#[async_trait]
+impl GoalRepository for SqliteStore {
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Goal>> {
+        let ledger_id = ledger_id.to_string();
+        let goals = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_GOAL} WHERE ledger_id = ?1 ORDER BY deadline"
+                ))?;
+                let goals = stmt
+                    .query_map([&ledger_id], goal_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(goals)
+            })
+            .await?;
+
+        Ok(goals)
+    }
+
+    async fn get(&self, ledger_id: &str, id: &str) -> AppResult<Option<Goal>> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let goal = self
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare(&format!("{SELECT_GOAL} WHERE id = ?1 AND ledger_id = ?2"))?;
+                let goal = stmt
+                    .query_map([&id, &ledger_id], goal_from_row)?
+                    .next()
+                    .transpose()?;
+                Ok(goal)
+            })
+            .await?;
+
+        Ok(goal)
+    }
+
+    async fn create(&self, goal: &Goal) -> AppResult<()> {
+        let goal = goal.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO goals (id, ledger_id, name, target, deadline, card_id, saved, monthly_contribution, emergency_months)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    (
+                        &goal.id,
+                        &goal.ledger_id,
+                        &goal.name,
+                        goal.target,
+                        Date(goal.deadline),
+                        &goal.card_id,
+                        goal.saved,
+                        goal.monthly_contribution,
+                        goal.emergency_months,
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update(
+        &self,
+        ledger_id: &str,
+        id: &str,
+        update: &UpdateGoal,
+    ) -> AppResult<Option<Goal>> {
+        let (owner, goal_id) = (ledger_id.to_string(), id.to_string());
+        let update = update.clone();
+        self.conn
+            .call(move |conn| {
+                // COALESCE keeps the stored value of every field left out of the update.
+                conn.execute(
+                    "UPDATE goals SET
+                        name = COALESCE(?1, name),
+                        target = COALESCE(?2, target),
+                        deadline = COALESCE(?3, deadline),
+                        card_id = COALESCE(?4, card_id),
+                        saved = COALESCE(?5, saved),
+                        monthly_contribution = COALESCE(?6, monthly_contribution),
+                        emergency_months = COALESCE(?7, emergency_months)
+                     WHERE id = ?8 AND ledger_id = ?9",
+                    (
+                        &update.name,
+                        update.target,
+                        update.deadline.map(Date),
+                        &update.card_id,
+                        update.saved,
+                        update.monthly_contribution,
+                        update.emergency_months,
+                        &goal_id,
+                        &owner,
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        GoalRepository::get(self, ledger_id, id).await
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "DELETE FROM goals WHERE id = ?1 AND ledger_id = ?2",
+                    [&id, &ledger_id],
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl SessionRepository for SqliteStore {
+    async fn create(&self, session: &Session) -> AppResult<()> {
+        let session = session.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO sessions (id, user_id, refresh_jti, created_at, expires_at, revoked_at, user_agent, ip, last_seen_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    (
+                        &session.id,
+                        &session.user_id,
+                        &session.refresh_jti,
+                        Timestamp(session.created_at),
+                        Timestamp(session.expires_at),
+                        session.revoked_at.map(Timestamp),
+                        &session.device.user_agent,
+                        &session.device.ip,
+                        Timestamp(session.last_seen_at),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> AppResult<Option<Session>> {
+        let id = id.to_string();
+        let session = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!("{SELECT_SESSION} WHERE id = ?1"))?;
+                let session = stmt
+                    .query_map([&id], session_from_row)?
+                    .next()
+                    .transpose()?;
+                Ok(session)
+            })
+            .await?;
+
+        Ok(session)
+    }
+
+    async fn list(&self, user_id: &str) -> AppResult<Vec<Session>> {
+        let user_id = user_id.to_string();
+        let now = Timestamp(Utc::now());
+        let sessions = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_SESSION}
+                     WHERE user_id = ?1 AND revoked_at IS NULL AND expires_at > ?2
+                     ORDER BY last_seen_at DESC"
+                ))?;
+                let sessions = stmt
+                    .query_map((&user_id, &now), session_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(sessions)
+            })
+            .await?;
+
+        Ok(sessions)
+    }
+
+    async fn seen(&self, id: &str, at: DateTime<Utc>, device: &Device) -> AppResult<()> {
+        let (id, at, device) = (id.to_string(), Timestamp(at), device.clone());
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE sessions SET last_seen_at = ?1, user_agent = ?2, ip = ?3 WHERE id = ?4",
+                    (&at, &device.user_agent, &device.ip, &id),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn rotate(
+        &self,
+        id: &str,
+        from_jti: &str,
+        to_jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AppResult<bool> {
+        let (id, from_jti, to_jti) = (id.to_string(), from_jti.to_string(), to_jti.to_string());
+        let (now, expires_at) = (Timestamp(Utc::now()), Timestamp(expires_at));
+        let rotated = self
+            .conn
+            .call(move |conn| {
+                // A single statement, so two concurrent refreshes can't both win.
+                let rows = conn.execute(
+                    "UPDATE sessions SET refresh_jti = ?1, expires_at = ?2
+                     WHERE id = ?3 AND refresh_jti = ?4 AND revoked_at IS NULL AND expires_at > ?5",
+                    (&to_jti, &expires_at, &id, &from_jti, &now),
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(rotated)
+    }
+
+    async fn revoke(&self, user_id: &str, id: &str) -> AppResult<bool> {
+        let (user_id, id) = (user_id.to_string(), id.to_string());
+        let now = Timestamp(Utc::now());
+        let revoked = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "UPDATE sessions SET revoked_at = ?1
+                     WHERE id = ?2 AND user_id = ?3 AND revoked_at IS NULL",
+                    (&now, &id, &user_id),
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(revoked)
+    }
+
+    async fn revoke_all(&self, user_id: &str) -> AppResult<usize> {
+        let user_id = user_id.to_string();
+        let now = Timestamp(Utc::now());
+        let revoked = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "UPDATE sessions SET revoked_at = ?1
+                     WHERE user_id = ?2 AND revoked_at IS NULL AND expires_at > ?1",
+                    (&now, &user_id),
+                )?;
+                Ok(rows)
+            })
+            .await?;
+
+        Ok(revoked)
+    }
+}
+
+#[async_trait]
+impl AccountRepository for SqliteStore {
+    async fn create(&self, account: &Account) -> AppResult<bool> {
+        let account = account.clone();
+        let created = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                let rows = tx.execute(
+                    "INSERT INTO accounts (email, name, password_hash, verified_at, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)
+                     ON CONFLICT (email) DO UPDATE SET
+                        name = excluded.name,
+                        password_hash = excluded.password_hash,
+                        created_at = excluded.created_at
+                     WHERE accounts.verified_at IS NULL",
+                    (
+                        &account.email,
+                        &account.name,
+                        &account.password_hash,
+                        account.verified_at.map(Timestamp),
+                        Timestamp(account.created_at),
+                    ),
+                )?;
+                if rows > 0 {
+                    tx.execute(
+                        "DELETE FROM account_tokens WHERE email = ?1 AND purpose = ?2",
+                        (&account.email, TokenPurpose::Verify),
+                    )?;
+                }
+
+                tx.commit()?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(created)
+    }
+
+    async fn get(&self, email: &str) -> AppResult<Option<Account>> {
+        let email = email.to_string();
+        let account = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!("{SELECT_ACCOUNT} WHERE email = ?1"))?;
+                let account = stmt
+                    .query_map([&email], account_from_row)?
+                    .next()
+                    .transpose()?;
+                Ok(account)
+            })
+            .await?;
+
+        Ok(account)
+    }
+
+    async fn verify(&self, email: &str, at: DateTime<Utc>) -> AppResult<()> {
+        let (email, at) = (email.to_string(), Timestamp(at));
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE accounts SET verified_at = ?1 WHERE email = ?2 AND verified_at IS NULL",
+                    (&at, &email),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_password(&self, email: &str, password_hash: &str) -> AppResult<()> {
+        let (email, password_hash) = (email.to_string(), password_hash.to_string());
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE accounts SET password_hash = ?1 WHERE email = ?2",
+                    [&password_hash, &email],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create_token(&self, token: &AccountToken) -> AppResult<()> {
+        let token = token.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO account_tokens (token_hash, email, purpose, expires_at, used_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    (
+                        &token.token_hash,
+                        &token.email,
+                        token.purpose,
+                        Timestamp(token.expires_at),
+                        token.used_at.map(Timestamp),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn use_token(
+        &self,
+        token_hash: &str,
+        purpose: TokenPurpose,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<String>> {
+        let (token_hash, now) = (token_hash.to_string(), Timestamp(now));
+        let email = self
+            .conn
+            .call(move |conn| {
+                // A single statement, so the same link can't be used twice concurrently.
+                let mut stmt = conn.prepare(
+                    "UPDATE account_tokens SET used_at = ?1
+                     WHERE token_hash = ?2 AND purpose = ?3 AND used_at IS NULL AND expires_at > ?1
+                     RETURNING email",
+                )?;
+                let email = stmt
+                    .query_map((&now, &token_hash, purpose), |row| row.get(0))?
+                    .next()
+                    .transpose()?;
+                Ok(email)
+            })
+            .await?;
+
+        Ok(email)
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteStore {
+    async fn resolve(&self, identity: &Identity) -> AppResult<User> {
+        let identity = identity.clone();
+        let user = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                let linked: Option<String> = tx
+                    .query_row(
+                        "SELECT user_id FROM user_identities WHERE issuer = ?1 AND subject = ?2",
+                        [&identity.issuer, &identity.subject],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                let id = match linked {
+                    Some(id) => id,
+                    None => {
+                        let existing: Option<String> = if identity.trusted_email {
+                            tx.query_row(
+                                "SELECT id FROM users WHERE email = ?1 AND email_verified",
+                                [&identity.email],
+                                |row| row.get(0),
+                            )
+                            .optional()?
+                        } else {
+                            None
+                        };
+                        let id = match existing {
+                            Some(id) => id,
+                            None => {
+                                let id = Uuid::now_v7().to_string();
+                                // The email is only verified if nobody else has it verified.
+                                tx.execute(
+                                    "INSERT INTO users (id, email, email_verified, name, picture, created_at)
+                                     VALUES (?1, ?2, ?3 AND NOT EXISTS (
+                                         SELECT 1 FROM users WHERE email = ?2 AND email_verified
+                                     ), ?4, ?5, ?6)",
+                                    (
+                                        &id,
+                                        &identity.email,
+                                        identity.trusted_email,
+                                        &identity.name,
+                                        &identity.picture,
+                                        Timestamp(Utc::now()),
+                                    ),
+                                )?;
+                                id
+                            }
+                        };
+
+                        tx.execute(
+                            "INSERT INTO user_identities (issuer, subject, user_id) VALUES (?1, ?2, ?3)",
+                            [&identity.issuer, &identity.subject, &id],
+                        )?;
+                        id
+                    }
+                };
+
+                tx.execute(
+                    "UPDATE users SET
+                        name = COALESCE(NULLIF(?1, ''), name),
+                        picture = COALESCE(NULLIF(?2, ''), picture)
+                     WHERE id = ?3",
+                    [&identity.name, &identity.picture, &id],
+                )?;
+                // Keep the old email if another user already has the new one verified.
+                if identity.trusted_email {
+                    tx.execute(
+                        "UPDATE users SET email = ?1, email_verified = 1
+                         WHERE id = ?2 AND NOT EXISTS (
+                             SELECT 1 FROM users WHERE email = ?1 AND email_verified AND id != ?2
+                         )",
+                        [&identity.email, &id],
+                    )?;
+                }
+
+                let user = tx.query_row(
+                    &format!("{SELECT_USER} WHERE id = ?1"),
+                    [&id],
+                    user_from_row,
+                )?;
+                tx.commit()?;
+
+                Ok(user)
+            })
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn get(&self, id: &str) -> AppResult<Option<User>> {
+        let id = id.to_string();
+        let user = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!("{SELECT_USER} WHERE id = ?1"))?;
+                let user = stmt.query_map([&id], user_from_row)?.next().transpose()?;
+                Ok(user)
+            })
+            .await?;
+
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl LedgerRepository for SqliteStore {
+    async fn create(&self, ledger: &Ledger, owner_id: &str) -> AppResult<()> {
+        let (ledger, owner_id) = (ledger.clone(), owner_id.to_string());
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "INSERT INTO ledgers (id, name, created_at) VALUES (?1, ?2, ?3)",
+                    (&ledger.id, &ledger.name, Timestamp(ledger.created_at)),
+                )?;
+                tx.execute(
+                    "INSERT INTO ledger_members (ledger_id, user_id, role, joined_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    (
+                        &ledger.id,
+                        &owner_id,
+                        LedgerRole::Owner,
+                        Timestamp(ledger.created_at),
+                    ),
+                )?;
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list(&self, user_id: &str) -> AppResult<Vec<Membership>> {
+        let user_id = user_id.to_string();
+        let memberships = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_MEMBERSHIP} WHERE m.user_id = ?1 ORDER BY l.created_at"
+                ))?;
+                let memberships = stmt
+                    .query_map([&user_id], membership_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(memberships)
+            })
+            .await?;
+
+        Ok(memberships)
+    }
+
+    async fn role(&self, ledger_id: &str, user_id: &str) -> AppResult<Option<LedgerRole>> {
+        let (ledger_id, user_id) = (ledger_id.to_string(), user_id.to_string());
+        let role = self
+            .conn
+            .call(move |conn| {
+                let role = conn
+                    .query_row(
+                        "SELECT role FROM ledger_members WHERE ledger_id = ?1 AND user_id = ?2",
+                        [&ledger_id, &user_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                Ok(role)
+            })
+            .await?;
+
+        Ok(role)
+    }
+
+    async fn members(&self, ledger_id: &str) -> AppResult<Vec<MemberDetails>> {
+        let ledger_id = ledger_id.to_string();
+        let members = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT m.ledger_id, m.user_id, m.role, m.joined_at,
+                        COALESCE(u.email, ''), COALESCE(u.name, ''), COALESCE(u.picture, '')
+                     FROM ledger_members m
+                     LEFT JOIN users u ON u.id = m.user_id
+                     WHERE m.ledger_id = ?1
+                     ORDER BY CASE m.role WHEN 'owner' THEN 0 WHEN 'editor' THEN 1 ELSE 2 END,
+                        m.joined_at",
+                )?;
+                let members = stmt
+                    .query_map([&ledger_id], |row| {
+                        Ok(MemberDetails {
+                            member: LedgerMember {
+                                ledger_id: row.get(0)?,
+                                user_id: row.get(1)?,
+                                role: row.get(2)?,
+                                joined_at: row.get::<_, Timestamp>(3)?.0,
+                            },
+                            email: row.get(4)?,
+                            name: row.get(5)?,
+                            picture: row.get(6)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(members)
+            })
+            .await?;
+
+        Ok(members)
+    }
+
+    async fn set_role(&self, ledger_id: &str, user_id: &str, role: LedgerRole) -> AppResult<bool> {
+        let (ledger_id, user_id) = (ledger_id.to_string(), user_id.to_string());
+        let updated = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "UPDATE ledger_members SET role = ?1 WHERE ledger_id = ?2 AND user_id = ?3",
+                    (role, &ledger_id, &user_id),
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(updated)
+    }
+
+    async fn remove_member(&self, ledger_id: &str, user_id: &str) -> AppResult<bool> {
+        let (ledger_id, user_id) = (ledger_id.to_string(), user_id.to_string());
+        let removed = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "DELETE FROM ledger_members WHERE ledger_id = ?1 AND user_id = ?2",
+                    [&ledger_id, &user_id],
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(removed)
+    }
+
+    async fn create_invite(&self, invite: &LedgerInvite) -> AppResult<()> {
+        let invite = invite.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO ledger_invites (token, ledger_id, role, created_by, expires_at, used_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    (
+                        &invite.token,
+                        &invite.ledger_id,
+                        invite.role,
+                        &invite.created_by,
+                        Timestamp(invite.expires_at),
+                        invite.used_at.map(Timestamp),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn accept_invite(
+        &self,
+        token: &str,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<Membership>> {
+        let (token, user_id) = (token.to_string(), user_id.to_string());
+        let membership = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                let invite: Option<(String, LedgerRole)> = tx
+                    .query_row(
+                        "UPDATE ledger_invites SET used_at = ?1
+                         WHERE token = ?2 AND used_at IS NULL AND expires_at > ?1
+                         RETURNING ledger_id, role",
+                        (Timestamp(now), &token),
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?;
+                let Some((ledger_id, role)) = invite else {
+                    return Ok(None);
+                };
+
+                tx.execute(
+                    "INSERT OR IGNORE INTO ledger_members (ledger_id, user_id, role, joined_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    (&ledger_id, &user_id, role, Timestamp(now)),
+                )?;
+                let membership = tx
+                    .query_row(
+                        &format!("{SELECT_MEMBERSHIP} WHERE m.ledger_id = ?1 AND m.user_id = ?2"),
+                        [&ledger_id, &user_id],
+                        membership_from_row,
+                    )
+                    .optional()?;
+                tx.commit()?;
+
+                Ok(membership)
+            })
+            .await?;
+
+        Ok(membership)
+    }
+}
+
+#[async_trait]
+impl SplitRepository for SqliteStore {
+    async fn contacts(&self, ledger_id: &str) -> AppResult<Vec<Contact>> {
+        let ledger_id = ledger_id.to_string();
+        let contacts = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, ledger_id, name, created_at FROM contacts
+                     WHERE ledger_id = ?1 ORDER BY name",
+                )?;
+                let contacts = stmt
+                    .query_map([&ledger_id], |row| {
+                        Ok(Contact {
+                            id: row.get(0)?,
+                            ledger_id: row.get(1)?,
+                            name: row.get(2)?,
+                            created_at: row.get::<_, Timestamp>(3)?.0,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(contacts)
+            })
+            .await?;
+
+        Ok(contacts)
+    }
+
+    async fn create_contact(&self, contact: &Contact) -> AppResult<()> {
+        let contact = contact.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO contacts (id, ledger_id, name, created_at) VALUES (?1, ?2, ?3, ?4)",
+                    (
+                        &contact.id,
+                        &contact.ledger_id,
+                        &contact.name,
+                        Timestamp(contact.created_at),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Split>> {
+        let ledger_id = ledger_id.to_string();
+        let splits = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_SPLIT_SHARES} WHERE s.ledger_id = ?1
+                     ORDER BY s.transaction_id, sh.position"
+                ))?;
+                let splits = splits_from_rows(stmt.query([&ledger_id])?)?;
+                Ok(splits)
+            })
+            .await?;
+
+        Ok(splits)
+    }
+
+    async fn get(&self, ledger_id: &str, transaction_id: &str) -> AppResult<Option<Split>> {
+        let (ledger_id, transaction_id) = (ledger_id.to_string(), transaction_id.to_string());
+        let split = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_SPLIT_SHARES} WHERE s.ledger_id = ?1 AND s.transaction_id = ?2
+                     ORDER BY sh.position"
+                ))?;
+                let splits = splits_from_rows(stmt.query([&ledger_id, &transaction_id])?)?;
+                Ok(splits.into_iter().next())
+            })
+            .await?;
+
+        Ok(split)
+    }
+
+    async fn save(&self, split: &Split) -> AppResult<()> {
+        let split = split.clone();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                // Replacing the split row cascades to its old shares.
+                tx.execute(
+                    "INSERT OR REPLACE INTO transaction_splits (transaction_id, ledger_id, paid_by_kind, paid_by_id)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    (
+                        &split.transaction_id,
+                        &split.ledger_id,
+                        split.paid_by.kind(),
+                        split.paid_by.id(),
+                    ),
+                )?;
+                for (position, share) in split.shares.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO split_shares (transaction_id, position, participant_kind, participant_id, amount)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        (
+                            &split.transaction_id,
+                            position,
+                            share.participant.kind(),
+                            share.participant.id(),
+                            share.amount,
+                        ),
+                    )?;
+                }
+
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, ledger_id: &str, transaction_id: &str) -> AppResult<bool> {
+        let (ledger_id, transaction_id) = (ledger_id.to_string(), transaction_id.to_string());
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "DELETE FROM transaction_splits WHERE ledger_id = ?1 AND transaction_id = ?2",
+                    [&ledger_id, &transaction_id],
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+
+    async fn settle(&self, settlement: &Settlement) -> AppResult<()> {
+        let settlement = settlement.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO settlements (id, ledger_id, from_kind, from_id, to_kind, to_id, amount, date)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    (
+                        &settlement.id,
+                        &settlement.ledger_id,
+                        settlement.from.kind(),
+                        settlement.from.id(),
+                        settlement.to.kind(),
+                        settlement.to.id(),
+                        settlement.amount,
+                        Timestamp(settlement.date),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn settlements(&self, ledger_id: &str) -> AppResult<Vec<Settlement>> {
+        let ledger_id = ledger_id.to_string();
+        let settlements = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, ledger_id, from_kind, from_id, to_kind, to_id, amount, date
+                     FROM settlements WHERE ledger_id = ?1 ORDER BY date",
+                )?;
+                let settlements = stmt
+                    .query_map([&ledger_id], |row| {
+                        Ok(Settlement {
+                            id: row.get(0)?,
+                            ledger_id: row.get(1)?,
+                            from: participant_from_row(row, 2)?,
+                            to: participant_from_row(row, 4)?,
+                            amount: row.get(6)?,
+                            date: row.get::<_, Timestamp>(7)?.0,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(settlements)
+            })
+            .await?;
+
+        Ok(settlements)
+    }
+}
+
+const SELECT_API_TOKEN: &str =
+    "SELECT id, user_id, name, token_hash, scopes, created_at, last_used_at, revoked_at
+     FROM api_tokens";
+
+#[async_trait]
+impl ApiTokenRepository for SqliteStore {
+    async fn create(&self, token: &ApiToken) -> AppResult<()> {
+        let token = token.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, created_at, last_used_at, revoked_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    (
+                        &token.id,
+                        &token.user_id,
+                        &token.name,
+                        &token.token_hash,
+                        TextList(token.scopes.clone()),
+                        Timestamp(token.created_at),
+                        token.last_used_at.map(Timestamp),
+                        token.revoked_at.map(Timestamp),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> AppResult<Option<ApiToken>> {
+        let token_hash = token_hash.to_string();
+        let token = self
+            .conn
+            .call(move |conn| {
+                let token = conn
+                    .query_row(
+                        &format!("{SELECT_API_TOKEN} WHERE token_hash = ?1"),
+                        [&token_hash],
+                        api_token_from_row,
+                    )
+                    .optional()?;
+                Ok(token)
+            })
+            .await?;
+
+        Ok(token)
+    }
+
+    async fn list(&self, user_id: &str) -> AppResult<Vec<ApiToken>> {
+        let user_id = user_id.to_string();
+        let tokens = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_API_TOKEN} WHERE user_id = ?1 AND revoked_at IS NULL
+                     ORDER BY created_at DESC"
+                ))?;
+                let tokens = stmt
+                    .query_map([&user_id], api_token_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(tokens)
+            })
+            .await?;
+
+        Ok(tokens)
+    }
+
+    async fn used(&self, id: &str, at: DateTime<Utc>) -> AppResult<()> {
+        let id = id.to_string();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2",
+                    (Timestamp(at), &id),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn revoke(&self, user_id: &str, id: &str, at: DateTime<Utc>) -> AppResult<bool> {
+        let (user_id, id) = (user_id.to_string(), id.to_string());
+        let revoked = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "UPDATE api_tokens SET revoked_at = ?1
+                     WHERE id = ?2 AND user_id = ?3 AND revoked_at IS NULL",
+                    (Timestamp(at), &id, &user_id),
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(revoked)
+    }
+}
+
+const SELECT_WEBHOOK: &str = "SELECT id, ledger_id, url, secret, events, created_at FROM webhooks";
+
+const SELECT_DELIVERY: &str =
+    "SELECT id, webhook_id, event, payload, status, attempts, next_attempt_at,
+            last_attempt_at, response_status, error, created_at
+     FROM webhook_deliveries";
+
+#[async_trait]
+impl WebhookRepository for SqliteStore {
+    async fn create(&self, webhook: &Webhook) -> AppResult<()> {
+        let webhook = webhook.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO webhooks (id, ledger_id, url, secret, events, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    (
+                        &webhook.id,
+                        &webhook.ledger_id,
+                        &webhook.url,
+                        &webhook.secret,
+                        TextList(webhook.events.clone()),
+                        Timestamp(webhook.created_at),
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list(&self, ledger_id: &str) -> AppResult<Vec<Webhook>> {
+        let ledger_id = ledger_id.to_string();
+        let webhooks = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_WEBHOOK} WHERE ledger_id = ?1 ORDER BY created_at"
+                ))?;
+                let webhooks = stmt
+                    .query_map([&ledger_id], webhook_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(webhooks)
+            })
+            .await?;
+
+        Ok(webhooks)
+    }
+
+    async fn delete(&self, ledger_id: &str, id: &str) -> AppResult<bool> {
+        let (ledger_id, id) = (ledger_id.to_string(), id.to_string());
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let rows = conn.execute(
+                    "DELETE FROM webhooks WHERE id = ?1 AND ledger_id = ?2",
+                    [&id, &ledger_id],
+                )?;
+                Ok(rows > 0)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+
+    async fn enqueue(&self, deliveries: &[Delivery]) -> AppResult<()> {
+        let deliveries = deliveries.to_vec();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO webhook_deliveries (id, webhook_id, event, payload, status, attempts,
+                             next_attempt_at, last_attempt_at, response_status, error, created_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    )?;
+                    for delivery in &deliveries {
+                        stmt.execute((
+                            &delivery.id,
+                            &delivery.webhook_id,
+                            delivery.event,
+                            &delivery.payload,
+                            delivery.status,
+                            delivery.attempts,
+                            delivery.next_attempt_at.map(Timestamp),
+                            delivery.last_attempt_at.map(Timestamp),
+                            delivery.response_status,
+                            &delivery.error,
+                            Timestamp(delivery.created_at),
+                        ))?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn claim_due(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> AppResult<Vec<(Webhook, Delivery)>> {
+        let claimed = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                let ids = tx
+                    .prepare(
+                        "UPDATE webhook_deliveries SET next_attempt_at = ?2
+                         WHERE id IN (
+                             SELECT id FROM webhook_deliveries
+                             WHERE status = 'pending' AND next_attempt_at <= ?1
+                             ORDER BY next_attempt_at, created_at LIMIT ?3
+                         )
+                         RETURNING id",
+                    )?
+                    .query_map((Timestamp(now), Timestamp(lease_until), limit), |row| {
+                        row.get::<_, String>(0)
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut claimed = Vec::with_capacity(ids.len());
+                for id in ids {
+                    let delivery = tx.query_row(
+                        &format!("{SELECT_DELIVERY} WHERE id = ?1"),
+                        [&id],
+                        delivery_from_row,
+                    )?;
+                    let webhook = tx.query_row(
+                        &format!("{SELECT_WEBHOOK} WHERE id = ?1"),
+                        [&delivery.webhook_id],
+                        webhook_from_row,
+                    )?;
+                    claimed.push((webhook, delivery));
+                }
+                tx.commit()?;
+
+                claimed.sort_by_key(|(_, delivery)| delivery.created_at);
+                Ok(claimed)
+            })
+            .await?;
+
+        Ok(claimed)
+    }
+
+    async fn update_delivery(&self, delivery: &Delivery) -> AppResult<()> {
+        let delivery = delivery.clone();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE webhook_deliveries
+                     SET status = ?1, attempts = ?2, next_attempt_at = ?3, last_attempt_at = ?4,
+                         response_status = ?5, error = ?6
+                     WHERE id = ?7",
+                    (
+                        delivery.status,
+                        delivery.attempts,
+                        delivery.next_attempt_at.map(Timestamp),
+                        delivery.last_attempt_at.map(Timestamp),
+                        delivery.response_status,
+                        &delivery.error,
+                        &delivery.id,
+                    ),
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn deliveries(&self, webhook_id: &str, limit: u32) -> AppResult<Vec<Delivery>> {
+        let webhook_id = webhook_id.to_string();
+        let deliveries = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "{SELECT_DELIVERY} WHERE webhook_id = ?1 ORDER BY created_at DESC LIMIT ?2"
+                ))?;
+                let deliveries = stmt
+                    .query_map((&webhook_id, limit), delivery_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(deliveries)
+            })
+            .await?;
+
+        Ok(deliveries)
+    }
+}
+
+#[async_trait]
+impl RateLimitRepository for SqliteStore {
+    async fn take(
+        &self,
+        key: &str,
+        limit: &RateLimit,
+        now: DateTime<Utc>,
+    ) -> AppResult<Option<Duration>> {
+        let (key, limit) = (key.to_string(), *limit);
+        let wait = self
+            .conn
+            .call(move |conn| {
+                // Takes the write lock first, so instances don't both spend the last token.
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+                tx.execute(
+                    "DELETE FROM rate_limit_buckets WHERE updated_at < ?1",
+                    [Timestamp(now - IDLE_BUCKET_TTL)],
+                )?;
+                let mut bucket = tx
+                    .query_row(
+                        "SELECT tokens, updated_at FROM rate_limit_buckets WHERE key = ?1",
+                        [&key],
+                        |row| {
+                            Ok(Bucket {
+                                tokens: row.get(0)?,
+                                updated_at: row.get::<_, Timestamp>(1)?.0,
+                            })
+                        },
+                    )
+                    .optional()?
+                    .unwrap_or_else(|| limit.full(now));
+
+                let wait = bucket.take(&limit, now).err();
+                tx.execute(
+                    "INSERT INTO rate_limit_buckets (key, tokens, updated_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (key) DO UPDATE SET tokens = ?2, updated_at = ?3",
+                    (&key, bucket.tokens, Timestamp(bucket.updated_at)),
+                )?;
+                tx.commit()?;
+
+                Ok(wait)
+            })
+            .await?;
+
+        Ok(wait)
+    }
+}
+
+/// Inserts the transaction and adds its amount to the card balance.
+fn insert_transaction(
+    tx: &rusqlite::Transaction,
+    transaction: &Transaction,
+) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO transactions (id, ledger_id, card_id, category_id, amount, description, transaction_type, date)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        (
+            &transaction.id,
+            &transaction.ledger_id,
+            &transaction.card_id,
+            &transaction.category_id,
+            &transaction.amount,
+            &transaction.description,
+            &transaction.transaction_type,
+            Timestamp(transaction.date),
+        ),
+    )?;
+
+    tx.execute(
+        "UPDATE cards SET current_balance = current_balance + ?1 WHERE id = ?2",
+        (transaction.amount, &transaction.card_id),
+    )?;
+
+    Ok(())
+}
+
+fn card_from_row(row: &Row) -> rusqlite::Result<Card> {
+    Ok(Card {
+        id: row.get(0)?,
+        ledger_id: row.get(1)?,
+        name: row.get(2)?,
+        card_type: row.get(3)?,
+        credit_limit: row.get(4)?,
+        current_balance: row.get(5)?,
+        limit_policy: row.get(6)?,
+    })
+}
+
+fn card_details_from_row(row: &Row) -> rusqlite::Result<CardDetails> {
+    Ok(card_from_row(row)?.with_usage(row.get(7)?))
+}
+
+fn transaction_from_row(row: &Row) -> rusqlite::Result<Transaction> {
+    Ok(Transaction {
+        id: row.get(0)?,
+        ledger_id: row.get(1)?,
+        card_id: row.get(2)?,
+        category_id: row.get(3)?,
+        amount: row.get(4)?,
+        description: row.get(5)?,
+        transaction_type: row.get(6)?,
+        date: row.get::<_, Timestamp>(7)?.0,
+    })
+}
+
+fn goal_from_row(row: &Row) -> rusqlite::Result<Goal> {
+    Ok(Goal {
+        id: row.get(0)?,
+        ledger_id: row.get(1)?,
+        name: row.get(2)?,
+        target: row.get(3)?,
+        deadline: row.get::<_, Date>(4)?.0,
+        card_id: row.get(5)?,
+        saved: row.get(6)?,
+        monthly_contribution: row.get(7)?,
+        emergency_months: row.get(8)?,
+    })
+}
+
+fn session_from_row(row: &Row) -> rusqlite::Result<Session> {
+    Ok(Session {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        refresh_jti: row.get(2)?,
+        created_at: row.get::<_, Timestamp>(3)?.0,
+        expires_at: row.get::<_, Timestamp>(4)?.0,
+        revoked_at: row.get::<_, Option<Timestamp>>(5)?.map(|t| t.0),
+        device: Device {
+            user_agent: row.get(6)?,
+            ip: row.get(7)?,
+        },
+        last_seen_at: row.get::<_, Timestamp>(8)?.0,
+    })
+}
+
+fn account_from_row(row: &Row) -> rusqlite::Result<Account> {
+    Ok(Account {
+        email: row.get(0)?,
+        name: row.get(1)?,
+        password_hash: row.get(2)?,
+        verified_at: row.get::<_, Option<Timestamp>>(3)?.map(|t| t.0),
+        created_at: row.get::<_, Timestamp>(4)?.0,
+    })
+}
+
+fn user_from_row(row: &Row) -> rusqlite::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        email: row.get(1)?,
+        email_verified: row.get(2)?,
+        name: row.get(3)?,
+        picture: row.get(4)?,
+        created_at: row.get::<_, Timestamp>(5)?.0,
+    })
+}
+
+fn membership_from_row(row: &Row) -> rusqlite::Result<Membership> {
+    Ok(Membership {
+        ledger: Ledger {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get::<_, Timestamp>(2)?.0,
+        },
+        role: row.get(3)?,
+        personal: false,
+    })
+}
+
+/// Reads a participant from its kind column at `index` and its id in the next one.
+fn participant_from_row(row: &Row, index: usize) -> rusqlite::Result<Participant> {
+    let kind: String = row.get(index)?;
+
+    Participant::from_parts(&kind, row.get(index + 1)?).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            index,
+            Type::Text,
+            Box::new(InvalidValue {
+                kind: "Participant",
+                value: kind,
+            }),
+        )
+    })
+}
+
+/// Groups the rows of [`SELECT_SPLIT_SHARES`], ordered by transaction, into splits.
+fn splits_from_rows(mut rows: Rows) -> rusqlite::Result<Vec<Split>> {
+    let mut splits: Vec<Split> = vec![];
+
+    while let Some(row) = rows.next()? {
+        let transaction_id: String = row.get(0)?;
+        let share = Share {
+            participant: participant_from_row(row, 4)?,
+            amount: row.get(6)?,
+        };
+
+        match splits.last_mut() {
+            Some(split) if split.transaction_id == transaction_id => split.shares.push(share),
+            _ => splits.push(Split {
+                transaction_id,
+                ledger_id: row.get(1)?,
+                paid_by: participant_from_row(row, 2)?,
+                shares: vec![share],
+            }),
+        }
+    }
+
+    Ok(splits)
+}
+
+fn api_token_from_row(row: &Row) -> rusqlite::Result<ApiToken> {
+    Ok(ApiToken {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        token_hash: row.get(3)?,
+        scopes: row.get::<_, TextList<_>>(4)?.0,
+        created_at: row.get::<_, Timestamp>(5)?.0,
+        last_used_at: row.get::<_, Option<Timestamp>>(6)?.map(|t| t.0),
+        revoked_at: row.get::<_, Option<Timestamp>>(7)?.map(|t| t.0),
+    })
+}
+
+fn webhook_from_row(row: &Row) -> rusqlite::Result<Webhook> {
+    Ok(Webhook {
+        id: row.get(0)?,
+        ledger_id: row.get(1)?,
+        url: row.get(2)?,
+        secret: row.get(3)?,
+        events: row.get::<_, TextList<_>>(4)?.0,
+        created_at: row.get::<_, Timestamp>(5)?.0,
+    })
+}
+
+fn delivery_from_row(row: &Row) -> rusqlite::Result<Delivery> {
+    Ok(Delivery {
+        id: row.get(0)?,
+        webhook_id: row.get(1)?,
+        event: row.get(2)?,
+        payload: row.get(3)?,
+        status: row.get(4)?,
+        attempts: row.get(5)?,
+        next_attempt_at: row.get::<_, Option<Timestamp>>(6)?.map(|t| t.0),
+        last_attempt_at: row.get::<_, Option<Timestamp>>(7)?.map(|t| t.0),
+        response_status: row.get(8)?,
+        error: row.get(9)?,
+        created_at: row.get::<_, Timestamp>(10)?.0,
+    })
+}
+
+fn rate_from_row(row: &Row) -> rusqlite::Result<Rate> {
+    Ok(Rate {
+        kind: row.get(0)?,
+        value: row.get::<_, DecimalText>(1)?.0,
+        effective_from: row.get::<_, Date>(2)?.0,
+    })
+}
+
+fn check_column<T: FromSql>(
+    issues: &mut Vec<IntegrityIssue>,
+    row: &Row,
+    table: &'static str,
+    id: &str,
+    index: usize,
+    column: &'static str,
+) -> rusqlite::Result<()> {
+    let value = row.get_ref(index)?;
+
+    if let Err(err) = T::column_result(value) {
+        issues.push(IntegrityIssue {
+            table,
+            id: id.to_string(),
+            column,
+            value: display_value(value),
+            reason: err.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+fn display_value(value: ValueRef) -> Option<String> {
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) => Some(i.to_string()),
+        ValueRef::Real(f) => Some(f.to_string()),
+        ValueRef::Text(t) | ValueRef::Blob(t) => Some(String::from_utf8_lossy(t).into_owned()),
+    }
+}
@@ -1,10 +1,25 @@
-import type { Card, Category, Transaction, User } from '$lib/types';
+import type {
+  ApiToken,
+  Card,
+  Category,
+  Contact,
+  Debt,
+  Ledger,
+  LedgerRole,
+  Participant,
+  TokenScope,
+  Transaction,
+  User,
+  Webhook,
+  WebhookDelivery,
+  WebhookEvent,
+} from '$lib/types';
 
 const API_BASE = '/api';
 
 type ApiCard = {
   id: string;
-  userEmail: string;
+  ledgerId: string;
   name: string;
   cardType: 'credit' | 'debit';
   creditLimit?: number | null;
@@ -13,14 +28,14 @@ type ApiCard = {
 
 type ApiCategory = {
   id: string;
-  userEmail?: string | null;
+  ledgerId?: string | null;
   name: string;
   color?: string | null;
 };
 
 type ApiTransaction = {
   id: string;
-  userEmail: string;
+  ledgerId: string;
   cardId: string;
   categoryId: string;
   amount: number;
@@ -30,6 +45,7 @@ type ApiTransaction = {
 };
 
 type ApiUserClaims = {
+  id: string;
   email: string;
   name: string;
   picture: string;
@@ -65,23 +81,54 @@ const toTransaction = (tx: ApiTransaction): Transaction => ({
 });
 
 const toUser = (claims: ApiUserClaims): User => ({
-  id: claims.email,
+  id: claims.id,
   email: claims.email,
   name: claims.name,
   avatarUrl: claims.picture,
 });
 
+type ApiLedgerInvite = {
+  token: string;
+  ledgerId: string;
+  role: LedgerRole;
+  expiresAt: string;
+};
+
+// Requests without a ledger work on the personal one.
+let activeLedger: string | null = null;
+
+export function setActiveLedger(id: string | null) {
+  activeLedger = id;
+}
+
+type ApiDebt = {
+  from: Participant;
+  to: Participant;
+  amount: number;
+};
+
+export type SplitShares =
+  | { method: 'equal'; participants: Participant[] }
+  | { method: 'percent'; shares: { participant: Participant; percent: number }[] }
+  | { method: 'exact'; shares: { participant: Participant; amount: number }[] };
+
 let refreshPromise: Promise<boolean> | null = null;
 
+// Tabs share the refresh cookie, and a refresh token used twice revokes the session. Refreshing
+// under a lock held across tabs makes the others wait, and send the token this one got back.
+function acrossTabs<T>(refresh: () => Promise<T>): Promise<T> {
+  return 'locks' in navigator ? navigator.locks.request('session-refresh', refresh) : refresh();
+}
+
 async function refreshAccessToken(): Promise<boolean> {
   if (!refreshPromise) {
-    refreshPromise = (async () => {
+    refreshPromise = acrossTabs(async () => {
       const response = await fetch(`${API_BASE}/user/session/refresh`, {
         method: 'POST',
         credentials: 'include',
       });
       return response.ok;
-    })().finally(() => {
+    }).finally(() => {
       refreshPromise = null;
     });
   }
@@ -94,6 +141,7 @@ async function apiFetch<T>(path: string, options: RequestInit = {}, canRetry = t
     ...rest,
     headers: {
       'Content-Type': 'application/json',
+      ...(activeLedger ? { 'X-Ledger': activeLedger } : {}),
       ...headers,
     },
     credentials: 'include',
@@ -214,10 +262,10 @@ export async function deleteTransaction(id: string): Promise<void> {
   await apiFetch<void>(`/transaction/${id}`, { method: 'DELETE' });
 }
 
-export async function createSession(token: string): Promise<User> {
+export async function createSession(token: string, provider?: string): Promise<User> {
   const claims = await apiFetch<ApiUserClaims>('/user/session', {
     method: 'POST',
-    body: JSON.stringify({ token }),
+    body: JSON.stringify({ token, provider }),
   });
   return toUser(claims);
 }
@@ -225,3 +273,109 @@ export async function createSession(token: string): Promise<User> {
 export async function clearSession(): Promise<void> {
   await apiFetch<void>('/user/session', { method: 'DELETE' });
 }
+
+export async function fetchApiTokens(): Promise<ApiToken[]> {
+  return apiFetch<ApiToken[]>('/user/tokens');
+}
+
+// The token itself is only returned here, it can't be fetched again.
+export async function createApiToken(
+  name: string,
+  scopes: TokenScope[],
+): Promise<ApiToken & { token: string }> {
+  return apiFetch<ApiToken & { token: string }>('/user/tokens', {
+    method: 'POST',
+    body: JSON.stringify({ name, scopes }),
+  });
+}
+
+export async function revokeApiToken(id: string): Promise<void> {
+  await apiFetch<void>(`/user/tokens/${id}`, { method: 'DELETE' });
+}
+
+export async function fetchLedgers(): Promise<Ledger[]> {
+  return apiFetch<Ledger[]>('/ledger');
+}
+
+export async function createLedger(name: string): Promise<Ledger> {
+  return apiFetch<Ledger>('/ledger', {
+    method: 'POST',
+    body: JSON.stringify({ name }),
+  });
+}
+
+export async function createLedgerInvite(ledgerId: string, role: LedgerRole): Promise<string> {
+  const invite = await apiFetch<ApiLedgerInvite>(`/ledger/${ledgerId}/invites`, {
+    method: 'POST',
+    body: JSON.stringify({ role }),
+  });
+  return invite.token;
+}
+
+export async function acceptLedgerInvite(token: string): Promise<Ledger> {
+  return apiFetch<Ledger>(`/ledger/invites/${token}`, { method: 'POST' });
+}
+
+export async function fetchContacts(): Promise<Contact[]> {
+  return apiFetch<Contact[]>('/split/contacts');
+}
+
+export async function createContact(name: string): Promise<Contact> {
+  return apiFetch<Contact>('/split/contacts', {
+    method: 'POST',
+    body: JSON.stringify({ name }),
+  });
+}
+
+// Exact shares are in dollars, like the rest of the app.
+export async function splitTransaction(transactionId: string, shares: SplitShares): Promise<void> {
+  const payload =
+    shares.method === 'exact'
+      ? {
+          ...shares,
+          shares: shares.shares.map((share) => ({
+            ...share,
+            amount: dollarsToCents(share.amount) ?? 0,
+          })),
+        }
+      : shares;
+  await apiFetch<void>(`/split/transaction/${transactionId}`, {
+    method: 'PUT',
+    body: JSON.stringify(payload),
+  });
+}
+
+export async function fetchDebts(): Promise<Debt[]> {
+  const { debts } = await apiFetch<{ debts: ApiDebt[] }>('/split/balances');
+  return debts.map((debt) => ({ ...debt, amount: centsToDollars(debt.amount) ?? 0 }));
+}
+
+export async function settleUp(from: Participant, to: Participant, amount: number): Promise<void> {
+  await apiFetch<void>('/split/settlements', {
+    method: 'POST',
+    body: JSON.stringify({ from, to, amount: dollarsToCents(amount) }),
+  });
+}
+
+export async function fetchWebhooks(): Promise<Webhook[]> {
+  return apiFetch<Webhook[]>('/webhook');
+}
+
+// The signing secret is only returned here, it can't be fetched again.
+export async function createWebhook(
+  url: string,
+  events: WebhookEvent[],
+): Promise<Webhook & { secret: string }> {
+  return apiFetch<Webhook & { secret: string }>('/webhook', {
+    method: 'POST',
+    body: JSON.stringify({ url, events }),
+  });
+}
+
+export async function deleteWebhook(id: string): Promise<void> {
+  await apiFetch<void>(`/webhook/${id}`, { method: 'DELETE' });
+}
+
+export async function fetchWebhookDeliveries(id: string): Promise<WebhookDelivery[]> {
+  return apiFetch<WebhookDelivery[]>(`/webhook/${id}/deliveries`);
+}
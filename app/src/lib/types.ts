@@ -31,3 +31,62 @@ export interface User {
   name: string;
   avatarUrl?: string;
 }
+
+export type LedgerRole = 'owner' | 'editor' | 'viewer';
+
+export interface Ledger {
+  id: string;
+  name: string;
+  role: LedgerRole;
+  personal: boolean;
+}
+
+// One of the ledger members, or a contact without an account.
+export type Participant = { user: string } | { contact: string };
+
+export interface Contact {
+  id: string;
+  name: string;
+}
+
+export interface Debt {
+  from: Participant;
+  to: Participant;
+  amount: number;
+}
+
+export type TokenScope = 'read' | 'transactions' | 'import';
+
+export interface ApiToken {
+  id: string;
+  name: string;
+  scopes: TokenScope[];
+  createdAt: string;
+  lastUsedAt?: string | null;
+}
+
+export type WebhookEvent =
+  | 'transaction.created'
+  | 'transaction.updated'
+  | 'transaction.deleted'
+  | 'credit_limit.exceeded'
+  | 'import.completed';
+
+export interface Webhook {
+  id: string;
+  url: string;
+  events: WebhookEvent[];
+  createdAt: string;
+}
+
+export interface WebhookDelivery {
+  id: string;
+  event: WebhookEvent;
+  status: 'pending' | 'delivered' | 'failed';
+  attempts: number;
+  nextAttemptAt?: string | null;
+  lastAttemptAt?: string | null;
+  responseStatus?: number | null;
+  error?: string | null;
+  createdAt: string;
+}